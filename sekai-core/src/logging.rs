@@ -0,0 +1,95 @@
+use std::io::Write;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn as_str(self) -> &'static str {
+        match self {
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+}
+
+/// Minimum level, read once from `SEKAI_LOG` (defaults to `warn`).
+fn threshold() -> Level {
+    static LEVEL: OnceLock<Level> = OnceLock::new();
+    *LEVEL.get_or_init(|| {
+        match std::env::var("SEKAI_LOG")
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+            .as_str()
+        {
+            "debug" => Level::Debug,
+            "info" => Level::Info,
+            "error" => Level::Error,
+            _ => Level::Warn,
+        }
+    })
+}
+
+pub fn enabled(level: Level) -> bool {
+    level >= threshold()
+}
+
+/// Writes one JSON line to stderr. Stdout is reserved for protocol responses.
+pub fn log(level: Level, target: &str, message: &str) {
+    if !enabled(level) {
+        return;
+    }
+
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+
+    let line = json!({
+        "ts": ts as u64,
+        "level": level.as_str(),
+        "target": target,
+        "msg": message,
+    });
+
+    let _ = writeln!(std::io::stderr().lock(), "{line}");
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    ($target:expr, $($arg:tt)*) => {
+        $crate::logging::log($crate::logging::Level::Debug, $target, &format!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! log_info {
+    ($target:expr, $($arg:tt)*) => {
+        $crate::logging::log($crate::logging::Level::Info, $target, &format!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! log_warn {
+    ($target:expr, $($arg:tt)*) => {
+        $crate::logging::log($crate::logging::Level::Warn, $target, &format!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($target:expr, $($arg:tt)*) => {
+        $crate::logging::log($crate::logging::Level::Error, $target, &format!($($arg)*))
+    };
+}
@@ -1,16 +1,39 @@
 #![windows_subsystem = "windows"]
 use std::io::{self, BufRead, Write};
+use std::sync::Mutex;
 
+mod logging;
 mod model;
 mod parsers;
 mod protocol;
+mod server;
 mod services;
 
+/// Requests are handled one at a time whichever connection they come from:
+/// project files other than the TM are not locked against concurrent
+/// writers, and long AI runs would otherwise race on checkpoints.
+static HANDLE_LOCK: Mutex<()> = Mutex::new(());
+
 fn main() {
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
+    match server::listen_port(std::env::args().skip(1)) {
+        Ok(Some(port)) => {
+            if let Err(e) = server::listen(port) {
+                log_error!("main", "{e}");
+                std::process::exit(1);
+            }
+        }
+        Ok(None) => serve(io::stdin().lock(), io::stdout()),
+        Err(e) => {
+            log_error!("main", "{e}");
+            std::process::exit(2);
+        }
+    }
+}
 
-    for line in stdin.lock().lines() {
+/// Answers each non-empty line of `input` with one line on `output` until
+/// either side closes.
+fn serve(input: impl BufRead, mut output: impl Write) {
+    for line in input.lines() {
         let line = match line {
             Ok(l) => l,
             Err(_) => continue,
@@ -20,21 +43,31 @@ fn main() {
             continue;
         }
 
-        let result = std::panic::catch_unwind(|| protocol::handle(&line));
+        let response = respond(&line);
 
-        let response = match result {
-            Ok(resp) => resp,
-            Err(_) => serde_json::json!({
+        if writeln!(output, "{response}").is_err() {
+            break;
+        }
+
+        let _ = output.flush();
+    }
+}
+
+fn respond(line: &str) -> String {
+    // A panic while handling is caught below, so a poisoned lock is fine.
+    let _guard = HANDLE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let result = std::panic::catch_unwind(|| protocol::handle(line));
+
+    match result {
+        Ok(resp) => resp,
+        Err(_) => {
+            log_error!("main", "panic while handling request");
+            serde_json::json!({
                 "status": "error",
                 "message": "internal core error"
             })
-            .to_string(),
-        };
-
-        if writeln!(stdout, "{response}").is_err() {
-            break;
+            .to_string()
         }
-
-        let _ = stdout.flush();
     }
 }
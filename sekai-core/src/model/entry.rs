@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
 
+/// Earlier translations kept per entry; the oldest are dropped past this.
+pub const HISTORY_LIMIT: usize = 10;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CoreEntry {
     pub entry_id: String,
@@ -30,19 +33,142 @@ pub struct CoreEntry {
 
     #[serde(default)]
     pub speaker: Option<String>,
+
+    /// Every `<name>` tag leading a dialogue line, in order; `speaker` is the
+    /// first of them.
+    #[serde(default)]
+    pub speakers: Vec<String>,
+
+    #[serde(default)]
+    pub register: Option<Register>,
+
+    /// Shared by the pieces of a split entry so rebuild keeps them on one line.
+    #[serde(default)]
+    pub group: Option<String>,
+
+    /// Reviewer note; never sent to the AI nor written on rebuild.
+    #[serde(default)]
+    pub comment: Option<String>,
+
+    /// Extra instruction for this line's prompt only ("this is sarcastic");
+    /// never written on rebuild nor kept in the TM.
+    #[serde(default)]
+    pub prompt_hint: Option<String>,
+
+    /// Set on an entry merged from adjacent lines: what stood between piece
+    /// n and n + 1 in the source (the earlier suffix, the line break and the
+    /// later prefix). `original` holds the pieces joined by `\n`, and rebuild
+    /// puts line n of the text back in front of join n.
+    #[serde(default)]
+    pub joins: Vec<String>,
+
+    /// Longest translation, in characters, the text box fits; overrides the
+    /// run-wide `max_chars`.
+    #[serde(default)]
+    pub max_chars: Option<usize>,
+
+    /// Translations this entry had before the current one, oldest first and
+    /// at most `HISTORY_LIMIT`; never written on rebuild.
+    #[serde(default)]
+    pub history: Vec<PastTranslation>,
+}
+
+/// A replaced translation and the status it had then.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PastTranslation {
+    pub translation: String,
+    pub status: EntryStatus,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+impl CoreEntry {
+    /// Replaces the translation, keeping the previous one in `history`, with
+    /// the current status, when it was non-empty and differs. Callers set the
+    /// new status afterwards.
+    pub fn set_translation(&mut self, translation: String) {
+        if translation == self.translation {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.translation, translation);
+        if !previous.trim().is_empty() {
+            self.history.push(PastTranslation {
+                translation: previous,
+                status: self.status,
+            });
+            if self.history.len() > HISTORY_LIMIT {
+                self.history.remove(0);
+            }
+        }
+    }
+
+    /// Puts the latest `history` entry back, translation and status,
+    /// dropping the current one. False when there is nothing to go back to.
+    pub fn undo_translation(&mut self) -> bool {
+        match self.history.pop() {
+            Some(previous) => {
+                self.translation = previous.translation;
+                self.status = previous.status;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum EntryStatus {
+    #[default]
     Untranslated,
     InProgress,
     Translated,
     Reviewed,
 }
 
-impl Default for EntryStatus {
-    fn default() -> Self {
-        EntryStatus::Untranslated
+/// Politeness level the translation should carry over from the source.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Register {
+    Formal,
+    Casual,
+    #[default]
+    Auto,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(original: &str) -> CoreEntry {
+        serde_json::from_value(serde_json::json!({ "entry_id": "1-text", "original": original, "is_translatable": true }))
+            .unwrap()
+    }
+
+    #[test]
+    fn undo_restores_the_previous_translation_and_status() {
+        let mut e = entry("こんにちは");
+        e.set_translation("Olá".into());
+        e.status = EntryStatus::Reviewed;
+        e.set_translation("Oi".into());
+        e.status = EntryStatus::Translated;
+
+        assert!(e.undo_translation());
+        assert_eq!(e.translation, "Olá");
+        assert_eq!(e.status, EntryStatus::Reviewed);
+        assert!(!e.undo_translation());
+        assert_eq!(e.translation, "Olá");
+    }
+
+    #[test]
+    fn history_skips_blanks_and_repeats_and_is_capped() {
+        let mut e = entry("a");
+        e.set_translation("v0".into());
+        e.set_translation("v0".into());
+        assert!(e.history.is_empty());
+
+        for n in 1..=HISTORY_LIMIT + 2 {
+            e.set_translation(format!("v{n}"));
+        }
+        assert_eq!(e.history.len(), HISTORY_LIMIT);
+        assert_eq!(e.history[0].translation, "v2");
     }
 }
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GlossaryTerm {
+    pub source: String,
+
+    #[serde(default)]
+    pub target: String,
+
+    #[serde(default)]
+    pub note: String,
+
+    /// Only used on lines by this speaker, overriding an unscoped term with
+    /// the same source there; applies to every line when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+}
+
+impl GlossaryTerm {
+    pub fn is_scoped(&self) -> bool {
+        self.speaker.as_deref().is_some_and(|s| !s.trim().is_empty())
+    }
+
+    /// Whether the term may be used on a line by any of `speakers`.
+    pub fn applies_to(&self, speakers: &[&str]) -> bool {
+        match self.speaker.as_deref().map(str::trim) {
+            None | Some("") => true,
+            Some(scope) => speakers.iter().any(|s| s.trim() == scope),
+        }
+    }
+}
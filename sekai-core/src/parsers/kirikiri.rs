@@ -1,59 +1,195 @@
 use crate::model::entry::{CoreEntry, EntryStatus};
 use regex::Regex;
 
-pub fn parse(text: &str) -> Vec<CoreEntry> {
+/// Which lines outside the recognised dialogue/choice forms are translatable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strictness {
+    /// Any non-empty, non-command line is narration.
+    #[default]
+    Lenient,
+    /// Only bracketed monologue/quotes (`(...)`, `（...）`, `「...」`, `『...』`)
+    /// and lines matching `translatable_patterns`; everything else is raw.
+    /// Keeps scripting lines in mixed files away from the AI.
+    Strict,
+}
+
+impl Strictness {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "lenient" => Ok(Strictness::Lenient),
+            "strict" => Ok(Strictness::Strict),
+            other => Err(format!("unknown strictness: {other} (expected lenient or strict)")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// `tag.attr` pairs (e.g. `ruby.text`) whose value is translatable on
+    /// single-command lines, in both `[tag ...]` and `@tag ...` form.
+    pub attributes: Vec<String>,
+    /// Tag names (e.g. `p`, `np`) that end a text block. When set,
+    /// consecutive narration lines are one entry up to and including the
+    /// line ending in one of these tags; empty keeps one entry per line.
+    pub block_terminators: Vec<String>,
+    pub strictness: Strictness,
+    /// Extra narration lines accepted under `Strict`, matched against the
+    /// trimmed line.
+    pub translatable_patterns: Vec<Regex>,
+    /// Bracket pairs that name the speaker at the start of a plain line
+    /// (`【明日香】おはよう`). The bracket stays in the prefix. With `[`/`]`,
+    /// names that read like a KAG tag (`[r]`, `[wait time=10]`, any
+    /// lower-case identifier) are left to the command rules.
+    pub inline_speakers: Vec<(char, char)>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            attributes: Vec::new(),
+            block_terminators: Vec::new(),
+            strictness: Strictness::default(),
+            translatable_patterns: Vec::new(),
+            inline_speakers: DEFAULT_INLINE_SPEAKERS.to_vec(),
+        }
+    }
+}
+
+pub const DEFAULT_INLINE_SPEAKERS: &[(char, char)] = &[('【', '】')];
+
+/// Whole-line bracket forms that stay translatable under `Strict`.
+const STRICT_ENCLOSURES: &[(char, char)] = &[('(', ')'), ('（', '）'), ('「', '」'), ('『', '』')];
+
+impl ParseOptions {
+    /// Whether a narration-branch line counts as text rather than raw.
+    fn narration_allowed(&self, logical: &str) -> bool {
+        match self.strictness {
+            Strictness::Lenient => true,
+            Strictness::Strict => {
+                STRICT_ENCLOSURES
+                    .iter()
+                    .any(|&(open, close)| logical.starts_with(open) && logical.ends_with(close))
+                    || self.translatable_patterns.iter().any(|re| re.is_match(logical))
+            }
+        }
+    }
+}
+
+pub fn parse(text: &str, opts: &ParseOptions) -> Vec<CoreEntry> {
     let mut entries = Vec::new();
 
+    // `<A>"..."`, or `<A><B>"..."` for several speakers sharing a line.
     let dialog_re = Regex::new(
-        r#"^(?P<prefix>\s*<(?P<speaker>[^>]+)>[\"\(])(?P<text>.*?)(?P<suffix>[\"\)]\s*)$"#,
+        r#"^(?P<prefix>\s*(?P<speakers>(?:<[^>]+>)+)[\"\(])(?P<text>.*?)(?P<suffix>[\"\)]\s*)$"#,
     )
     .unwrap();
 
-    for (i, line) in text.lines().enumerate() {
-        let ln = i + 1;
+    // `[link ...]text[/link]` or `...[endlink]`; likewise for `select`. The
+    // text runs to the first closing tag, inline tags (`[r]`, `[ruby ...]`)
+    // included.
+    let choice_re = Regex::new(
+        r#"\[(?P<tag>link|select)\b[^\]]*\](?P<text>.+?)\[(?:/(?P<close>link|select)|end(?P<end>link|select))\]"#,
+    )
+    .unwrap();
 
-        let line_clean = line.trim_end_matches('\r');
+    // The tag name of a `[tag ...]` or `@tag ...` command line.
+    let head_re = Regex::new(r"^\s*[\[@](?P<tag>[A-Za-z_][\w]*)").unwrap();
+    let attribute_rules = attribute_rules(&opts.attributes);
+
+    let lines: Vec<&str> = text.lines().map(|l| l.trim_end_matches('\r')).collect();
+    // First line not yet consumed by a merged block.
+    let mut next_line = 0usize;
+    // Right after an `@link`/`@select` line: plain lines are its choice text
+    // until `@endlink`/`@endselect` or any other non-text line.
+    let mut in_at_choice = false;
+
+    for (i, &line_clean) in lines.iter().enumerate() {
+        if i < next_line {
+            continue;
+        }
+        let ln = i + 1;
 
         let logical = line_clean.trim();
 
         if logical.is_empty() {
             entries.push(raw_entry(ln, line_clean));
+            in_at_choice = false;
             continue;
         }
 
-        if logical.starts_with('[') && logical.ends_with(']') {
-            entries.push(raw_entry(ln, line_clean));
+        if in_at_choice {
+            if logical.starts_with(['@', '[', '*', ';']) {
+                in_at_choice = false;
+            } else {
+                let start = line_clean.len() - line_clean.trim_start().len();
+                entries.push(text_entry(ln, line_clean, start, start + logical.len(), None));
+                continue;
+            }
+        }
+
+        let choices = choice_spans(line_clean, &choice_re);
+        if !choices.is_empty() {
+            entries.extend(choice_entries(ln, line_clean, &choices));
+            continue;
+        }
+
+        if let Some((speaker, start, end)) = inline_speaker(line_clean, &opts.inline_speakers) {
+            let mut entry = text_entry(ln, line_clean, start, end, Some(speaker.clone()));
+            entry.speakers = vec![speaker];
+            entries.push(entry);
+            continue;
+        }
+
+        let is_bracket_command = logical.starts_with('[') && logical.ends_with(']');
+        let is_at_command = logical.starts_with('@');
+
+        if is_bracket_command || is_at_command {
+            in_at_choice = is_at_command && opens_at_choice(logical);
+            match command_attribute_span(line_clean, &head_re, &attribute_rules) {
+                Some((start, end)) => entries.push(text_entry(ln, line_clean, start, end, None)),
+                None => entries.push(raw_entry(ln, line_clean)),
+            }
             continue;
         }
 
         if let Some(caps) = dialog_re.captures(line_clean) {
-            let speaker = caps
-                .name("speaker")
-                .map(|m| m.as_str().to_string())
-                .unwrap_or_default();
+            let speakers: Vec<String> = caps["speakers"]
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .split("><")
+                .map(str::to_string)
+                .collect();
 
             let text_m = caps.name("text").unwrap();
-            let text = text_m.as_str().to_string();
-
-            let start = text_m.start();
-            let end = text_m.end();
-
-            entries.push(CoreEntry {
-                entry_id: format!("{}-text", ln),
-                original: text,
-                translation: String::new(),
-                status: EntryStatus::Untranslated,
-                is_translatable: true,
-                line_number: ln,
-                raw_line: None,
-                prefix: Some(line_clean[..start].to_string()),
-                suffix: Some(line_clean[end..].to_string()),
-                speaker: Some(speaker),
-            });
+            let mut entry = text_entry(ln, line_clean, text_m.start(), text_m.end(), speakers.first().cloned());
+            entry.speakers = speakers;
+            entries.push(entry);
+
+            continue;
+        }
 
+        if !opts.narration_allowed(logical) {
+            entries.push(raw_entry(ln, line_clean));
             continue;
         }
 
+        if !opts.block_terminators.is_empty() && opens_block(logical) {
+            let mut last = i;
+            while !ends_block(lines[last].trim(), &opts.block_terminators)
+                && last + 1 < lines.len()
+                && is_narration(lines[last + 1], &dialog_re, &choice_re, &opts.inline_speakers)
+                && opts.narration_allowed(lines[last + 1].trim())
+            {
+                last += 1;
+            }
+
+            if last > i {
+                entries.push(block_entry(ln, &lines[i..=last]));
+                next_line = last + 1;
+                continue;
+            }
+        }
+
         let original = logical.to_string();
 
         let start = match line_clean.find(&original) {
@@ -65,23 +201,187 @@ pub fn parse(text: &str) -> Vec<CoreEntry> {
         };
         let end = start + original.len();
 
-        entries.push(CoreEntry {
-            entry_id: format!("{}-text", ln),
-            original,
-            translation: String::new(),
-            status: EntryStatus::Untranslated,
-            is_translatable: true,
-            line_number: ln,
-            raw_line: None,
-            prefix: Some(line_clean[..start].to_string()),
-            suffix: Some(line_clean[end..].to_string()),
-            speaker: None,
-        });
+        entries.push(text_entry(ln, line_clean, start, end, None));
     }
 
     entries
 }
 
+/// Whether `line` falls through to the narration branch of `parse`, so it
+/// may continue a text block.
+fn is_narration(line: &str, dialog_re: &Regex, choice_re: &Regex, inline_speakers: &[(char, char)]) -> bool {
+    let logical = line.trim();
+    if logical.is_empty() || !opens_block(logical) {
+        return false;
+    }
+    if inline_speaker(line, inline_speakers).is_some() {
+        return false;
+    }
+    if logical.starts_with('@') || (logical.starts_with('[') && logical.ends_with(']')) {
+        return false;
+    }
+    choice_spans(line, choice_re).is_empty() && !dialog_re.is_match(line)
+}
+
+/// Byte spans of the choice texts on a line that opens with a `[link]` or
+/// `[select]` tag, one per well-closed tag pair with non-empty text.
+fn choice_spans(line: &str, choice_re: &Regex) -> Vec<(usize, usize)> {
+    let indent = line.len() - line.trim_start().len();
+    let mut spans = Vec::new();
+
+    for caps in choice_re.captures_iter(line) {
+        if spans.is_empty() && caps.get(0).unwrap().start() != indent {
+            break;
+        }
+        let close = caps.name("close").or_else(|| caps.name("end")).unwrap().as_str();
+        let text = caps.name("text").unwrap();
+        if &caps["tag"] == close && !text.as_str().trim().is_empty() && !has_choice_tag(text.as_str()) {
+            spans.push((text.start(), text.end()));
+        }
+    }
+    spans
+}
+
+/// Whether `text` holds a `[link ...]`/`[select ...]` tag, i.e. the choice
+/// before it was never closed.
+fn has_choice_tag(text: &str) -> bool {
+    text.split('[').skip(1).any(|tag| {
+        let name = tag.split(|c: char| c.is_whitespace() || c == ']').next().unwrap_or("");
+        name == "link" || name == "select"
+    })
+}
+
+/// One entry per choice on the line. Several share a `group`, like split
+/// pieces, so rebuild puts them back on the one line; what lies between two
+/// choices is the prefix of the later one.
+fn choice_entries(line_number: usize, line: &str, spans: &[(usize, usize)]) -> Vec<CoreEntry> {
+    if let [(start, end)] = spans {
+        return vec![text_entry(line_number, line, *start, *end, None)];
+    }
+
+    let last = spans.len() - 1;
+    spans
+        .iter()
+        .enumerate()
+        .map(|(n, &(start, end))| {
+            let mut e = text_entry(line_number, line, start, end, None);
+            e.entry_id = format!("{line_number}-text.{}", n + 1);
+            e.group = Some(format!("{line_number}-text"));
+            if n > 0 {
+                e.prefix = Some(line[spans[n - 1].1..start].to_string());
+            }
+            if n < last {
+                e.suffix = Some(String::new());
+            }
+            e
+        })
+        .collect()
+}
+
+/// `@link ...` or `@select ...`, whose choice text follows on its own lines.
+fn opens_at_choice(logical: &str) -> bool {
+    let tag = logical[1..].split_whitespace().next().unwrap_or("");
+    tag.eq_ignore_ascii_case("link") || tag.eq_ignore_ascii_case("select")
+}
+
+/// Speaker and byte span of the text for a line opening with one of
+/// `pairs` around a name, followed by non-empty text.
+fn inline_speaker(line: &str, pairs: &[(char, char)]) -> Option<(String, usize, usize)> {
+    let indent = line.len() - line.trim_start().len();
+    let rest = &line[indent..];
+
+    for &(open, close) in pairs {
+        let Some(inner) = rest.strip_prefix(open) else {
+            continue;
+        };
+        let Some(close_at) = inner.find(close) else {
+            continue;
+        };
+        let name = inner[..close_at].trim();
+        if name.is_empty() || name.contains(open) || (open == '[' && looks_like_tag(name)) {
+            continue;
+        }
+
+        let after = &inner[close_at + close.len_utf8()..];
+        let text = after.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let start = indent + open.len_utf8() + close_at + close.len_utf8() + (after.len() - after.trim_start().len());
+        return Some((name.to_string(), start, start + text.len()));
+    }
+    None
+}
+
+/// `r`, `wait time=10`, `/link`: the inside of a KAG tag rather than a name.
+/// Tags are lower-case by convention, so `[Yuki]` still reads as a name.
+fn looks_like_tag(inner: &str) -> bool {
+    let head = inner.split_whitespace().next().unwrap_or("");
+    inner.contains('=')
+        || inner.starts_with('/')
+        || head.chars().next().is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+            && head.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Labels and comments never belong to a text block.
+fn opens_block(logical: &str) -> bool {
+    !logical.starts_with('*') && !logical.starts_with(';')
+}
+
+/// Whether `logical` ends with `[tag]` (attributes allowed) for one of
+/// `terminators`, compared case-insensitively.
+fn ends_block(logical: &str, terminators: &[String]) -> bool {
+    let Some(inner) = logical.strip_suffix(']') else {
+        return false;
+    };
+    let Some(open) = inner.rfind('[') else {
+        return false;
+    };
+    let tag = inner[open + 1..].split_whitespace().next().unwrap_or("");
+    terminators.iter().any(|t| t.eq_ignore_ascii_case(tag))
+}
+
+/// Translatable entry for `line[start..end]`; the rest of the line becomes
+/// prefix/suffix.
+fn text_entry(line_number: usize, line: &str, start: usize, end: usize, speaker: Option<String>) -> CoreEntry {
+    CoreEntry {
+        entry_id: format!("{}-text", line_number),
+        original: line[start..end].to_string(),
+        translation: String::new(),
+        status: EntryStatus::Untranslated,
+        is_translatable: true,
+        line_number,
+        raw_line: None,
+        prefix: Some(line[..start].to_string()),
+        suffix: Some(line[end..].to_string()),
+        speaker,
+        speakers: Vec::new(),
+        register: None,
+        group: None,
+        comment: None,
+        prompt_hint: None,
+        joins: Vec::new(),
+        history: Vec::new(),
+        max_chars: None,
+    }
+}
+
+/// Translatable entry for a text block over `lines`: `original` holds each
+/// line's text on a line of its own, and `joins` what stood between them, so
+/// rebuild writes the block back over as many lines.
+fn block_entry(line_number: usize, lines: &[&str]) -> CoreEntry {
+    let block = lines.join("\n");
+    let start = block.len() - block.trim_start().len();
+    let mut entry = text_entry(line_number, &block, start, block.trim_end().len(), None);
+    entry.original = lines.iter().map(|l| l.trim()).collect::<Vec<_>>().join("\n");
+    entry.joins = lines
+        .windows(2)
+        .map(|w| format!("{}\n{}", &w[0][w[0].trim_end().len()..], &w[1][..w[1].len() - w[1].trim_start().len()]))
+        .collect();
+    entry
+}
+
 fn raw_entry(line_number: usize, line: &str) -> CoreEntry {
     CoreEntry {
         entry_id: format!("{}-raw", line_number),
@@ -94,5 +394,254 @@ fn raw_entry(line_number: usize, line: &str) -> CoreEntry {
         prefix: None,
         suffix: None,
         speaker: None,
+        speakers: Vec::new(),
+        register: None,
+        group: None,
+        comment: None,
+        prompt_hint: None,
+        joins: Vec::new(),
+        history: Vec::new(),
+        max_chars: None,
+    }
+}
+
+/// Rough 0.0..=1.0 likelihood that `text` is a KAG script, from the share of
+/// non-empty lines carrying KAG signatures (`<Name>"..."`, `[tag]`, `@tag`,
+/// `*label`, `;comment`).
+pub fn score(text: &str) -> f32 {
+    let dialog_re = Regex::new(r#"^\s*(?:<[^>]+>)+["\(].*["\)]\s*$"#).unwrap();
+
+    let mut non_empty = 0usize;
+    let mut signatures = 0usize;
+
+    for line in text.lines() {
+        let logical = line.trim();
+        if logical.is_empty() {
+            continue;
+        }
+        non_empty += 1;
+
+        let is_command = (logical.starts_with('[') && logical.contains(']'))
+            || logical.starts_with('@')
+            || logical.starts_with('*')
+            || logical.starts_with(';');
+
+        if is_command || dialog_re.is_match(logical) {
+            signatures += 1;
+        }
+    }
+
+    if non_empty == 0 {
+        return 0.0;
+    }
+
+    signatures as f32 / non_empty as f32
+}
+
+/// A configured `tag.attr` pair, with the regex finding the attribute's value.
+struct AttributeRule {
+    tag: String,
+    value_re: Regex,
+}
+
+/// Compiles `tag.attr` specs once per parse; malformed ones are skipped.
+fn attribute_rules(attributes: &[String]) -> Vec<AttributeRule> {
+    attributes
+        .iter()
+        .filter_map(|spec| {
+            let (tag, attr) = spec.split_once('.')?;
+            let value_re = Regex::new(&format!(
+                r#"\s{}\s*=\s*(?:"(?P<dq>[^"]*)"|'(?P<sq>[^']*)'|(?P<bare>[^\s\]"']+))"#,
+                regex::escape(attr)
+            ))
+            .ok()?;
+            Some(AttributeRule { tag: tag.to_string(), value_re })
+        })
+        .collect()
+}
+
+/// Byte span of the first configured attribute value on a single-command
+/// line (`[tag a="v"]` or `@tag a="v"`), if any. `head_re` captures the
+/// command's `tag`.
+fn command_attribute_span(line: &str, head_re: &Regex, rules: &[AttributeRule]) -> Option<(usize, usize)> {
+    if rules.is_empty() {
+        return None;
+    }
+
+    let tag = head_re.captures(line)?.name("tag")?.as_str();
+
+    // A bracket line holding several tags is not a single command.
+    if line.trim_start().starts_with('[') && line.matches('[').count() > 1 {
+        return None;
+    }
+
+    for rule in rules.iter().filter(|r| r.tag.eq_ignore_ascii_case(tag)) {
+        if let Some(caps) = rule.value_re.captures(line) {
+            let m = caps.name("dq").or_else(|| caps.name("sq")).or_else(|| caps.name("bare"))?;
+            if !m.as_str().trim().is_empty() {
+                return Some((m.start(), m.end()));
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::rebuild::{self, RebuildConfig};
+
+    fn round_trip(text: &str) -> Vec<CoreEntry> {
+        round_trip_with(text, &ParseOptions::default())
+    }
+
+    fn round_trip_with(text: &str, opts: &ParseOptions) -> Vec<CoreEntry> {
+        let entries = parse(text, opts);
+        assert_eq!(rebuild::rebuild(&entries, &RebuildConfig::default()), text);
+        entries
+    }
+
+    #[test]
+    fn link_choice_round_trips_with_tags_in_prefix_and_suffix() {
+        let mut entries = round_trip("[link target=*yes]はい[/link]");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].original, "はい");
+        assert_eq!(entries[0].prefix.as_deref(), Some("[link target=*yes]"));
+        assert_eq!(entries[0].suffix.as_deref(), Some("[/link]"));
+
+        entries[0].translation = "Sim".into();
+        assert_eq!(rebuild::rebuild(&entries, &RebuildConfig::default()), "[link target=*yes]Sim[/link]");
+    }
+
+    #[test]
+    fn every_choice_on_a_line_is_an_entry() {
+        let mut entries = round_trip("[link target=*a]はい[/link][select target=*b]いいえ[endselect]");
+        let originals: Vec<&str> = entries.iter().map(|e| e.original.as_str()).collect();
+        assert_eq!(originals, ["はい", "いいえ"]);
+        assert!(entries.iter().all(|e| e.group.as_deref() == Some("1-text")));
+
+        entries[0].translation = "Sim".into();
+        entries[1].translation = "Não".into();
+        assert_eq!(
+            rebuild::rebuild(&entries, &RebuildConfig::default()),
+            "[link target=*a]Sim[/link][select target=*b]Não[endselect]"
+        );
+    }
+
+    #[test]
+    fn inline_tags_stay_inside_the_choice_text() {
+        let mut entries = round_trip("[link target=*c]行く[r]行かない[/link]");
+        assert!(entries[0].is_translatable);
+        assert_eq!(entries[0].original, "行く[r]行かない");
+
+        entries[0].translation = "Ir[r]Não ir".into();
+        assert_eq!(rebuild::rebuild(&entries, &RebuildConfig::default()), "[link target=*c]Ir[r]Não ir[/link]");
+
+        let entries = round_trip("[link target=*d][ruby text=\"あした\"]明日[/link]");
+        assert_eq!(entries[0].original, "[ruby text=\"あした\"]明日");
+    }
+
+    #[test]
+    fn unclosed_or_mismatched_choices_are_not_text() {
+        assert!(!round_trip("[link target=*a]はい[/select]")[0].is_translatable);
+        let entries = round_trip("[link target=*a]はい[link target=*b]いいえ[/link]");
+        assert_eq!(entries.len(), 1);
+        assert!(!entries[0].is_translatable);
+    }
+
+    #[test]
+    fn at_link_body_is_choice_text_even_when_strict() {
+        let text = "@link target=*a\n左へ\n@endlink\n次へ";
+        let opts = ParseOptions { strictness: Strictness::Strict, ..Default::default() };
+        let entries = parse(text, &opts);
+        let kinds: Vec<bool> = entries.iter().map(|e| e.is_translatable).collect();
+        assert_eq!(kinds, [false, true, false, false]);
+        assert_eq!(entries[1].original, "左へ");
+        assert_eq!(rebuild::rebuild(&entries, &RebuildConfig::default()), text);
+    }
+
+    #[test]
+    fn command_is_raw_unless_its_attribute_is_configured() {
+        let text = "@ruby text=\"かな\"\n[ruby text='よみ']\n[link target=*a]はい[/link]\n@wait time=10";
+        let raw = round_trip(text);
+        assert!(!raw[0].is_translatable && !raw[1].is_translatable);
+        assert_eq!(raw[0].raw_line.as_deref(), Some("@ruby text=\"かな\""));
+
+        let opts = ParseOptions { attributes: vec!["RUBY.text".into(), "nodot".into()], ..Default::default() };
+        let mut extracted = parse(text, &opts);
+        let originals: Vec<&str> = extracted.iter().filter(|e| e.is_translatable).map(|e| e.original.as_str()).collect();
+        assert_eq!(originals, ["かな", "よみ", "はい"]);
+        assert_eq!(rebuild::rebuild(&extracted, &RebuildConfig::default()), text);
+
+        extracted[0].translation = "kana".into();
+        assert!(rebuild::rebuild(&extracted, &RebuildConfig::default()).starts_with("@ruby text=\"kana\"\n"));
+    }
+
+    #[test]
+    fn score_is_the_share_of_lines_with_a_script_signature() {
+        assert_eq!(score("[cm]\n<A>\"おはよう\"\n\n@wait time=10\nいい天気だ"), 0.75);
+        assert_eq!(score("plain prose\nwith no tags"), 0.0);
+        assert_eq!(score("\n  \n"), 0.0);
+    }
+
+    #[test]
+    fn two_speaker_line_round_trips() {
+        let mut entries = round_trip("<A><B>\"せーの\"");
+        assert_eq!(entries[0].speaker.as_deref(), Some("A"));
+        assert_eq!(entries[0].speakers, ["A", "B"]);
+
+        entries[0].translation = "Um, dois".into();
+        assert_eq!(rebuild::rebuild(&entries, &RebuildConfig::default()), "<A><B>\"Um, dois\"");
+    }
+
+    #[test]
+    fn terminator_set_decides_where_blocks_end() {
+        let text = "一行目\n二行目[p]\n三行目[np]\n四行目";
+        let by_p = round_trip_with(text, &ParseOptions { block_terminators: vec!["p".into()], ..Default::default() });
+        let by_np = parse(text, &ParseOptions { block_terminators: vec!["np".into()], ..Default::default() });
+
+        let originals = |entries: &[CoreEntry]| entries.iter().map(|e| e.original.clone()).collect::<Vec<_>>();
+        assert_eq!(originals(&by_p), ["一行目\n二行目[p]", "三行目[np]\n四行目"]);
+        assert_eq!(originals(&by_np), ["一行目\n二行目[p]\n三行目[np]", "四行目"]);
+    }
+
+    #[test]
+    fn block_keeps_its_line_count_on_rebuild() {
+        let text = "*start\n  こんにちは\n世界です[p]\n@wait time=10";
+        let opts = ParseOptions { block_terminators: vec!["p".into()], ..Default::default() };
+        let mut entries = round_trip_with(text, &opts);
+        let block = entries.iter_mut().find(|e| e.original.contains('\n')).unwrap();
+        assert_eq!(block.original, "こんにちは\n世界です[p]");
+        assert_eq!(block.joins, ["\n"]);
+
+        block.translation = "Olá\nmundo[p]".into();
+        assert_eq!(rebuild::rebuild(&entries, &RebuildConfig::default()), "*start\n  Olá\nmundo[p]\n@wait time=10");
+    }
+
+    #[test]
+    fn bare_scripting_line_is_raw_only_when_strict() {
+        let text = "if f.flag then jump";
+        let lenient = parse(text, &ParseOptions::default());
+        let strict = parse(text, &ParseOptions { strictness: Strictness::Strict, ..Default::default() });
+        assert!(lenient[0].is_translatable);
+        assert!(!strict[0].is_translatable);
+    }
+
+    #[test]
+    fn inline_speaker_is_read_and_round_trips() {
+        let mut entries = round_trip("【明日香】おはよう");
+        assert_eq!(entries[0].speaker.as_deref(), Some("明日香"));
+        assert_eq!(entries[0].original, "おはよう");
+
+        entries[0].translation = "Bom dia".into();
+        assert_eq!(rebuild::rebuild(&entries, &RebuildConfig::default()), "【明日香】Bom dia");
+    }
+
+    #[test]
+    fn lower_case_bracket_names_stay_tags() {
+        let opts = ParseOptions { inline_speakers: vec![('[', ']')], ..Default::default() };
+        assert_eq!(parse("[Yuki]おはよう", &opts)[0].speaker.as_deref(), Some("Yuki"));
+        assert!(parse("[r]おはよう", &opts)[0].speaker.is_none());
     }
 }
@@ -0,0 +1,102 @@
+use serde::Serialize;
+
+use crate::model::entry::CoreEntry;
+
+/// File-level facts reported by `parse_text` next to the entries.
+#[derive(Debug, Serialize)]
+pub struct FileMeta {
+    /// `crlf`, `lf`, `cr`, `mixed`, or `none` for a single-line file.
+    pub line_ending: &'static str,
+    pub bom: bool,
+    pub total_lines: usize,
+    /// Source lines covered by translatable entries; a merged block counts
+    /// every line it spans.
+    pub translatable_lines: usize,
+    /// Script most letters of the translatable text are in: `japanese`
+    /// (kana, or kanji next to kana), `han`, `hangul`, `latin`, `cyrillic`,
+    /// `other`; absent when there are no letters.
+    pub dominant_script: Option<&'static str>,
+}
+
+pub fn describe(text: &str, bom: bool, entries: &[CoreEntry]) -> FileMeta {
+    let translatable: Vec<&CoreEntry> = entries.iter().filter(|e| e.is_translatable).collect();
+
+    FileMeta {
+        line_ending: line_ending(text),
+        bom,
+        total_lines: text.lines().count(),
+        translatable_lines: translatable.iter().map(|e| e.original.lines().count().max(1)).sum(),
+        dominant_script: dominant_script(translatable.iter().map(|e| e.original.as_str())),
+    }
+}
+
+fn line_ending(text: &str) -> &'static str {
+    let crlf = text.matches("\r\n").count();
+    let lf = text.matches('\n').count() - crlf;
+    let cr = text.matches('\r').count() - crlf;
+
+    match (crlf > 0, lf > 0, cr > 0) {
+        (false, false, false) => "none",
+        (true, false, false) => "crlf",
+        (false, true, false) => "lf",
+        (false, false, true) => "cr",
+        _ => "mixed",
+    }
+}
+
+fn dominant_script<'t>(texts: impl Iterator<Item = &'t str>) -> Option<&'static str> {
+    // kana, han, hangul, latin, cyrillic, other
+    let mut counts = [0usize; 6];
+    for c in texts.flat_map(str::chars).filter(|c| c.is_alphabetic()) {
+        let slot = match c as u32 {
+            0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9D => 0,
+            0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF => 1,
+            0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => 2,
+            _ if c.is_ascii_alphabetic() || ('\u{C0}'..='\u{24F}').contains(&c) || ('\u{FF21}'..='\u{FF5A}').contains(&c) => 3,
+            0x0400..=0x04FF => 4,
+            _ => 5,
+        };
+        counts[slot] += 1;
+    }
+
+    // Japanese mixes kanji and kana; either way it is one script here.
+    let [kana, han, hangul, latin, cyrillic, other] = counts;
+    let japanese = if kana > 0 { kana + han } else { 0 };
+    let han_only = if kana > 0 { 0 } else { han };
+
+    [
+        ("japanese", japanese),
+        ("han", han_only),
+        ("hangul", hangul),
+        ("latin", latin),
+        ("cyrillic", cyrillic),
+        ("other", other),
+    ]
+    .into_iter()
+    .filter(|&(_, n)| n > 0)
+    .max_by_key(|&(_, n)| n)
+    .map(|(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::kirikiri::{self, ParseOptions};
+
+    #[test]
+    fn crlf_script_is_described() {
+        let text = "[cm]\r\n<A>\"おはよう\"\r\n先生と話す";
+        let meta = describe(text, true, &kirikiri::parse(text, &ParseOptions::default()));
+        assert_eq!((meta.line_ending, meta.bom, meta.total_lines, meta.translatable_lines), ("crlf", true, 3, 2));
+        assert_eq!(meta.dominant_script, Some("japanese"));
+    }
+
+    #[test]
+    fn line_endings_are_told_apart() {
+        assert_eq!(line_ending("a\nb\r\nc"), "mixed");
+        assert_eq!(line_ending("a\rb"), "cr");
+        assert_eq!(line_ending("a"), "none");
+        assert_eq!(dominant_script(["Hello"].into_iter()), Some("latin"));
+        assert_eq!(dominant_script(["……"].into_iter()), None);
+    }
+}
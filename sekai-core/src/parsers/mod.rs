@@ -1 +1,29 @@
 pub mod kirikiri;
+pub mod meta;
+
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct ParserScore {
+    pub parser_id: String,
+    pub confidence: f32,
+}
+
+type Scorer = fn(&str) -> f32;
+
+/// Parsers known to the core, each with a cheap format-signature scorer.
+const KNOWN: &[(&str, Scorer)] = &[("kirikiri", kirikiri::score)];
+
+/// Scores every known parser against `text`, best match first.
+pub fn detect(text: &str) -> Vec<ParserScore> {
+    let mut scores: Vec<ParserScore> = KNOWN
+        .iter()
+        .map(|(id, score)| ParserScore {
+            parser_id: id.to_string(),
+            confidence: score(text),
+        })
+        .collect();
+
+    scores.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    scores
+}
@@ -3,14 +3,55 @@ pub enum Command {
     Ping,
     ParseText,
     RebuildText,
+    RebuildFile,
+    RebuildPatch,
+    RebuildVerifyAgainstFile,
     RunQa,
+    QaRunBatch,
+    QaDetectMojibake,
     DetectEncoding,
+    EncodingDetectDir,
+    EncodingRoundtripCheck,
     TranslateEntries,
     TranslateWithTm,
+    TranslateResume,
+    TranslateCompareModels,
     ProjectList,
     ProjectCreate,
     ProjectOpen,
     ProjectSave,
+    ProjectRename,
+    ProjectSetEncoding,
+    FileFingerprint,
+    FileCheckFingerprint,
+    ProjectExportBundle,
+    ProjectImportBundle,
+    NormalizePreview,
+    TmSearch,
+    TmCoverage,
+    TmInconsistencies,
+    TmBootstrapFromEntries,
+    ParserDetect,
+    GlossaryGet,
+    GlossarySave,
+    GlossaryImportCsv,
+    GlossaryExportCsv,
+    EntriesQuery,
+    EntriesSplit,
+    EntriesMerge,
+    EntriesRenumber,
+    EntriesValidate,
+    EntriesReview,
+    EntriesSpeakers,
+    EntriesSetComment,
+    EntriesClearComment,
+    EntriesUndoTranslation,
+    EntriesHistory,
+    EntriesFillUntranslatedFrom,
+    EntriesBulkTranslateFromMap,
+    StatsProgress,
+    TextSplitSentences,
+    TokensCount,
     Unknown,
 }
 
@@ -20,14 +61,55 @@ impl From<&str> for Command {
             "ping" => Command::Ping,
             "parse_text" => Command::ParseText,
             "rebuild_text" => Command::RebuildText,
+            "rebuild_file" => Command::RebuildFile,
+            "rebuild.patch" => Command::RebuildPatch,
+            "rebuild.verify_against_file" => Command::RebuildVerifyAgainstFile,
             "run_qa" => Command::RunQa,
+            "qa.run_batch" => Command::QaRunBatch,
+            "qa.detect_mojibake" => Command::QaDetectMojibake,
             "detect_encoding" => Command::DetectEncoding,
+            "encoding.detect_dir" => Command::EncodingDetectDir,
+            "encoding.roundtrip_check" => Command::EncodingRoundtripCheck,
             "translate_entries" => Command::TranslateEntries,
             "translate_with_tm" => Command::TranslateWithTm,
+            "translate.resume" => Command::TranslateResume,
+            "translate.compare_models" => Command::TranslateCompareModels,
             "project.list" => Command::ProjectList,
             "project.create" => Command::ProjectCreate,
             "project.open" => Command::ProjectOpen,
             "project.save" => Command::ProjectSave,
+            "project.rename" => Command::ProjectRename,
+            "project.set_encoding" => Command::ProjectSetEncoding,
+            "file.fingerprint" => Command::FileFingerprint,
+            "file.check_fingerprint" => Command::FileCheckFingerprint,
+            "project.export_bundle" => Command::ProjectExportBundle,
+            "project.import_bundle" => Command::ProjectImportBundle,
+            "normalize.preview" => Command::NormalizePreview,
+            "tm.search" => Command::TmSearch,
+            "tm.coverage" => Command::TmCoverage,
+            "tm.inconsistencies" => Command::TmInconsistencies,
+            "tm.bootstrap_from_entries" => Command::TmBootstrapFromEntries,
+            "parser.detect" => Command::ParserDetect,
+            "glossary.get" => Command::GlossaryGet,
+            "glossary.save" => Command::GlossarySave,
+            "glossary.import_csv" => Command::GlossaryImportCsv,
+            "glossary.export_csv" => Command::GlossaryExportCsv,
+            "entries.query" => Command::EntriesQuery,
+            "entries.split" => Command::EntriesSplit,
+            "entries.merge" => Command::EntriesMerge,
+            "entries.renumber" => Command::EntriesRenumber,
+            "entries.validate" => Command::EntriesValidate,
+            "entries.review" => Command::EntriesReview,
+            "entries.speakers" => Command::EntriesSpeakers,
+            "entries.set_comment" => Command::EntriesSetComment,
+            "entries.clear_comment" => Command::EntriesClearComment,
+            "entries.undo_translation" => Command::EntriesUndoTranslation,
+            "entries.history" => Command::EntriesHistory,
+            "entries.fill_untranslated_from" => Command::EntriesFillUntranslatedFrom,
+            "entries.bulk_translate_from_map" => Command::EntriesBulkTranslateFromMap,
+            "stats.progress" => Command::StatsProgress,
+            "text.split_sentences" => Command::TextSplitSentences,
+            "tokens.count" => Command::TokensCount,
             _ => Command::Unknown,
         }
     }
@@ -1,11 +1,22 @@
 use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-use crate::model::entry::CoreEntry;
+use crate::model::entry::{CoreEntry, Register};
+use crate::model::glossary::GlossaryTerm;
 use crate::model::project::ProjectInfo;
 use crate::parsers;
-use crate::services::{ai, encoding, pipeline, project, qa, rebuild};
+use crate::services::cleanup::CleanupRules;
+use crate::services::substitutions::{self, Substitution};
+use crate::services::translation_memory::model::DedupStrategy;
+use crate::services::nontext::NontextRule;
+use crate::services::trivial::TrivialRule;
+use crate::services::translation_memory::{audit, coverage, hash, normalize, search, seed, store};
+use crate::services::{
+    ai, ai_audit, bundle, checkpoint, compare, encoding, fingerprint, glossary, mojibake, pipeline, project, propagate, providers, qa, query, rebuild, review, rtl, segment, stats, text, tokens,
+};
 
 mod command;
+mod validate;
 use command::Command;
 
 fn get_cmd(req: &Value) -> &str {
@@ -16,7 +27,7 @@ fn get_id(req: &Value) -> Value {
     req.get("id").cloned().unwrap_or(Value::Null)
 }
 
-fn get_payload<'a>(req: &'a Value) -> &'a Value {
+fn get_payload(req: &Value) -> &Value {
     static EMPTY: Value = Value::Null;
     req.get("payload").unwrap_or(&EMPTY)
 }
@@ -39,17 +50,41 @@ fn err(id: Value, message: impl Into<String>) -> String {
     .to_string()
 }
 
+fn err_code(id: Value, code: &str, path: &str, message: impl Into<String>) -> String {
+    json!({
+        "id": id,
+        "status": "error",
+        "code": code,
+        "path": path,
+        "message": message.into()
+    })
+    .to_string()
+}
+
 fn parse_entries_from_payload(payload: &Value) -> Result<Vec<CoreEntry>, String> {
     let arr = payload
         .get("entries")
         .and_then(|v| v.as_array())
         .ok_or_else(|| "payload.entries must be an array".to_string())?;
 
+    parse_entries(arr)
+}
+
+fn parse_entries(arr: &[Value]) -> Result<Vec<CoreEntry>, String> {
     let mut entries: Vec<CoreEntry> = Vec::with_capacity(arr.len());
+    let mut seen: HashMap<String, usize> = HashMap::with_capacity(arr.len());
 
     for (i, v) in arr.iter().cloned().enumerate() {
         match serde_json::from_value::<CoreEntry>(v) {
-            Ok(e) => entries.push(e),
+            Ok(e) => {
+                if let Some(first) = seen.insert(e.entry_id.clone(), i) {
+                    return Err(format!(
+                        "duplicate entry_id '{}' at index {} (first seen at index {})",
+                        e.entry_id, i, first
+                    ));
+                }
+                entries.push(e)
+            }
             Err(e) => return Err(format!("invalid entry at index {}: {}", i, e)),
         }
     }
@@ -57,6 +92,259 @@ fn parse_entries_from_payload(payload: &Value) -> Result<Vec<CoreEntry>, String>
     Ok(entries)
 }
 
+/// Reads `payload.files` as a map of file name to entry array.
+fn parse_files_from_payload(payload: &Value) -> Result<BTreeMap<String, Vec<CoreEntry>>, String> {
+    let obj = payload
+        .get("files")
+        .and_then(|v| v.as_object())
+        .ok_or_else(|| "payload.files must be an object".to_string())?;
+
+    let mut files = BTreeMap::new();
+    for (name, v) in obj {
+        let arr = v
+            .as_array()
+            .ok_or_else(|| format!("payload.files[{name}] must be an array"))?;
+        let entries = parse_entries(arr).map_err(|e| format!("{name}: {e}"))?;
+        files.insert(name.clone(), entries);
+    }
+
+    Ok(files)
+}
+
+fn parse_glossary_from_payload(payload: &Value) -> Result<Vec<GlossaryTerm>, String> {
+    match payload.get("glossary") {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(v) => serde_json::from_value::<Vec<GlossaryTerm>>(v.clone())
+            .map_err(|e| format!("invalid payload.glossary: {e}")),
+    }
+}
+
+/// Reads the provider/model/language options shared by every AI-backed command.
+fn ai_config_from_payload(payload: &Value) -> Result<ai::AiConfig<'_>, String> {
+    let provider = payload.get("provider").and_then(|v| v.as_str()).unwrap_or("");
+    let api_key = payload.get("api_key").and_then(|v| v.as_str()).unwrap_or("");
+    let model = payload.get("model").and_then(|v| v.as_str()).unwrap_or("");
+    let source_lang = payload.get("source_lang").and_then(|v| v.as_str()).unwrap_or("ja");
+    let target_lang = payload.get("target_lang").and_then(|v| v.as_str()).unwrap_or("pt-BR");
+    let context_lines = payload
+        .get("context_lines")
+        .and_then(|v| v.as_u64())
+        .map(|n| n as usize)
+        .unwrap_or(ai::DEFAULT_CONTEXT_LINES);
+    let style_reference_lines = payload
+        .get("style_reference_lines")
+        .and_then(|v| v.as_u64())
+        .map(|n| n as usize)
+        .unwrap_or(0);
+    let register: Register = match payload.get("register") {
+        None | Some(Value::Null) => Register::Auto,
+        Some(v) => serde_json::from_value(v.clone())
+            .map_err(|e| format!("invalid payload.register: {e}"))?,
+    };
+
+    if provider.is_empty() { return Err("payload.provider is required".into()); }
+    let spec = providers::resolve(provider)?;
+    if api_key.is_empty() && spec.auth_style != providers::AuthStyle::None {
+        return Err("payload.api_key is required".into());
+    }
+    let model = spec.resolve_model(provider, model)?;
+
+    let checkpoint_dir = if payload.get("checkpoint").and_then(|v| v.as_bool()).unwrap_or(false) {
+        match payload.get("project_path").and_then(|v| v.as_str()) {
+            Some(p) if !p.is_empty() => Some(p),
+            _ => return Err("payload.project_path is required for checkpoint".into()),
+        }
+    } else {
+        None
+    };
+
+    let cleanup: CleanupRules = match payload.get("cleanup") {
+        None | Some(Value::Null) => CleanupRules::default(),
+        Some(v) => serde_json::from_value(v.clone()).map_err(|e| format!("invalid payload.cleanup: {e}"))?,
+    };
+
+    // The env switch only applies where there is a project to write into.
+    let audit = match (payload.get("audit").and_then(|v| v.as_bool()), payload.get("project_path").and_then(|v| v.as_str())) {
+        (Some(false), _) => None,
+        (Some(true), None | Some("")) => return Err("payload.project_path is required for audit".into()),
+        (Some(true), Some(p)) => Some(ai_audit::AuditLog::for_run(p)?),
+        (None, Some(p)) if !p.is_empty() && ai_audit::enabled_by_env() => Some(ai_audit::AuditLog::for_run(p)?),
+        (None, _) => None,
+    };
+
+    let trivial: TrivialRule = match payload.get("trivial") {
+        None | Some(Value::Null) => TrivialRule::default(),
+        Some(v) => serde_json::from_value(v.clone()).map_err(|e| format!("invalid payload.trivial: {e}"))?,
+    };
+
+    let nontext = nontext_rule_from_payload(payload)?.compile()?;
+    let glossary = parse_glossary_from_payload(payload)?;
+
+    let headers: BTreeMap<String, String> = match payload.get("headers") {
+        None | Some(Value::Null) => BTreeMap::new(),
+        Some(v) => serde_json::from_value(v.clone()).map_err(|e| format!("invalid payload.headers (expected string values): {e}"))?,
+    };
+    let on_failure = ai::AiFailurePolicy::parse(payload.get("on_ai_failure").and_then(|v| v.as_str()).unwrap_or(""))?;
+    let allow_auth_override = payload.get("allow_auth_override").and_then(|v| v.as_bool()).unwrap_or(false);
+    let headers = ai::request_headers(&headers, allow_auth_override)?;
+
+    let mock: ai::MockOptions = match payload.get("mock") {
+        None | Some(Value::Null) => ai::MockOptions::default(),
+        Some(v) => serde_json::from_value(v.clone()).map_err(|e| format!("invalid payload.mock: {e}"))?,
+    };
+
+    Ok(ai::AiConfig {
+        provider,
+        api_key,
+        model,
+        source_lang,
+        target_lang,
+        context_lines,
+        style_reference_lines,
+        glossary,
+        register,
+        allow_break_reposition: payload
+            .get("allow_break_reposition")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        checkpoint_dir,
+        cleanup,
+        trivial,
+        nontext,
+        revise: payload.get("revise").and_then(|v| v.as_bool()).unwrap_or(false),
+        batch_requests: payload.get("batch_requests").and_then(|v| v.as_bool()).unwrap_or(false),
+        json_batches: payload.get("json_batches").and_then(|v| v.as_bool()).unwrap_or(false),
+        max_tokens: payload.get("max_tokens").and_then(|v| v.as_u64()),
+        max_chars: payload.get("max_chars").and_then(|v| v.as_u64()).map(|n| n as usize),
+        max_backoff_ms: payload
+            .get("max_backoff_ms")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(ai::DEFAULT_MAX_BACKOFF_MS),
+        max_consecutive_failures: payload
+            .get("max_consecutive_failures")
+            .and_then(|v| v.as_u64())
+            .map(|n| n as usize)
+            .unwrap_or(ai::DEFAULT_MAX_CONSECUTIVE_FAILURES),
+        on_failure,
+        mock,
+        audit,
+        headers,
+    })
+}
+
+fn parse_options_from_payload(payload: &Value) -> Result<parsers::kirikiri::ParseOptions, String> {
+    let strings = |key: &str| -> Vec<String> {
+        payload
+            .get(key)
+            .and_then(|v| v.as_array())
+            .map(|a| a.iter().filter_map(|s| s.as_str()).map(str::to_string).collect())
+            .unwrap_or_default()
+    };
+
+    let strictness = parsers::kirikiri::Strictness::parse(payload.get("strictness").and_then(|v| v.as_str()).unwrap_or(""))?;
+    let translatable_patterns = strings("translatable_patterns")
+        .iter()
+        .map(|p| regex::Regex::new(p).map_err(|e| format!("invalid translatable pattern {p:?}: {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let inline_speakers = match payload.get("inline_speakers") {
+        None | Some(Value::Null) => parsers::kirikiri::DEFAULT_INLINE_SPEAKERS.to_vec(),
+        Some(v) => serde_json::from_value(v.clone())
+            .map_err(|e| format!("invalid payload.inline_speakers (expected [[open, close], ...] of single characters): {e}"))?,
+    };
+
+    Ok(parsers::kirikiri::ParseOptions {
+        attributes: strings("attributes"),
+        block_terminators: strings("block_terminators"),
+        strictness,
+        translatable_patterns,
+        inline_speakers,
+    })
+}
+
+fn nontext_rule_from_payload(payload: &Value) -> Result<NontextRule, String> {
+    match payload.get("nontext") {
+        None | Some(Value::Null) => Ok(NontextRule::default()),
+        Some(v) => serde_json::from_value(v.clone()).map_err(|e| format!("invalid payload.nontext: {e}")),
+    }
+}
+
+fn qa_options_from_payload(payload: &Value) -> Result<qa::QaOptions, String> {
+    let mut opts = qa::QaOptions {
+        nontext: nontext_rule_from_payload(payload)?.compile()?,
+        glossary: parse_glossary_from_payload(payload)?,
+        max_chars: payload.get("max_chars").and_then(|v| v.as_u64()).map(|n| n as usize),
+        ..Default::default()
+    };
+    if let Some(lang) = payload.get("target_lang").and_then(|v| v.as_str()) {
+        opts.target_lang = lang.to_string();
+    }
+    if let Some(v) = payload.get("residue_whitelist").filter(|v| !v.is_null()) {
+        opts.residue_whitelist =
+            serde_json::from_value(v.clone()).map_err(|e| format!("invalid payload.residue_whitelist: {e}"))?;
+    }
+    if let Some(n) = payload.get("residue_max_chars").and_then(|v| v.as_u64()) {
+        opts.residue_max_chars = n as usize;
+    }
+    if let Some(v) = payload.get("bracket_pairs").filter(|v| !v.is_null()) {
+        opts.bracket_pairs = serde_json::from_value(v.clone())
+            .map_err(|e| format!("invalid payload.bracket_pairs (expected [[open, close], ...] of single characters): {e}"))?;
+    }
+    Ok(opts)
+}
+
+fn rebuild_config_from_payload(payload: &Value) -> Result<rebuild::RebuildConfig, String> {
+    let glossary = parse_glossary_from_payload(payload)?;
+    let on_untranslated = rebuild::UntranslatedPolicy::parse(
+        payload.get("on_untranslated").and_then(|v| v.as_str()).unwrap_or(""),
+        payload
+            .get("untranslated_marker")
+            .and_then(|v| v.as_str())
+            .unwrap_or(rebuild::DEFAULT_UNTRANSLATED_MARKER),
+    )?;
+
+    let substitutions: Vec<Substitution> = match payload.get("substitutions") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(v) => serde_json::from_value(v.clone()).map_err(|e| format!("invalid payload.substitutions: {e}"))?,
+    };
+    substitutions::validate(&substitutions)?;
+
+    let normalization: rebuild::OutputNormalization = match payload.get("normalization") {
+        None | Some(Value::Null) => rebuild::OutputNormalization::default(),
+        Some(v) => serde_json::from_value(v.clone()).map_err(|e| format!("invalid payload.normalization: {e}"))?,
+    };
+
+    let rtl_options: rtl::RtlOptions = match payload.get("rtl") {
+        None | Some(Value::Null) => rtl::RtlOptions::default(),
+        Some(v) => serde_json::from_value(v.clone()).map_err(|e| format!("invalid payload.rtl: {e}"))?,
+    };
+    let target_lang = payload.get("target_lang").and_then(|v| v.as_str()).unwrap_or("");
+    let rtl = rtl_options.active_for(target_lang).then_some(rtl_options);
+
+    Ok(rebuild::RebuildConfig { glossary, on_untranslated, substitutions, normalization, rtl })
+}
+
+/// The query under `key`, its `qa_code` checked with the QA options found
+/// in the rest of the payload.
+fn query_from_payload(payload: &Value, key: &str) -> Result<Option<query::EntryQuery>, String> {
+    match payload.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => {
+            let mut q: query::EntryQuery =
+                serde_json::from_value(v.clone()).map_err(|e| format!("invalid payload.{key}: {e}"))?;
+            q.qa = qa_options_from_payload(payload)?;
+            Ok(Some(q))
+        }
+    }
+}
+
+fn tm_dedup_from_payload(payload: &Value) -> Result<DedupStrategy, String> {
+    match payload.get("tm_dedup") {
+        None | Some(Value::Null) => Ok(DedupStrategy::default()),
+        Some(v) => serde_json::from_value(v.clone()).map_err(|e| format!("invalid payload.tm_dedup: {e}")),
+    }
+}
+
 pub fn handle(input: &str) -> String {
     let req: Value = match serde_json::from_str(input) {
         Ok(v) => v,
@@ -75,13 +363,32 @@ pub fn handle(input: &str) -> String {
 
     let _cmd = Command::from(cmd_str);
 
+    if let Err(v) = validate::validate(cmd_str, payload) {
+        return err_code(id, v.code, &v.path, v.message);
+    }
+
     match cmd_str {
         "ping" => ok(id, json!({ "message": "sekai-core alive" })),
 
         "parse_text" => {
-            let text = payload.get("text").and_then(|v| v.as_str()).unwrap_or("");
-            let entries = parsers::kirikiri::parse(text);
-            ok(id, json!({ "entries": entries }))
+            let path_str = payload.get("path").and_then(|v| v.as_str()).unwrap_or("");
+            let (text, bom) = if !path_str.is_empty() {
+                let encoding_name = payload.get("encoding").and_then(|v| v.as_str());
+                match encoding::read_text_with_bom(std::path::Path::new(path_str), encoding_name) {
+                    Ok(v) => v,
+                    Err(e) => return err(id, e),
+                }
+            } else {
+                let raw = payload.get("text").and_then(|v| v.as_str()).unwrap_or("");
+                (encoding::strip_bom(raw).to_string(), raw.starts_with('\u{feff}'))
+            };
+            let opts = match parse_options_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let entries = parsers::kirikiri::parse(&text, &opts);
+            let meta = parsers::meta::describe(&text, bom, &entries);
+            ok(id, json!({ "entries": entries, "meta": meta }))
         }
 
         "rebuild_text" => {
@@ -89,19 +396,137 @@ pub fn handle(input: &str) -> String {
                 Ok(v) => v,
                 Err(e) => return err(id, e),
             };
-            let output = rebuild::rebuild(&entries);
+            if let Some(original_text) = payload.get("original_text").and_then(|v| v.as_str()) {
+                let opts = match parse_options_from_payload(payload) {
+                    Ok(v) => v,
+                    Err(e) => return err(id, e),
+                };
+                let original = parsers::kirikiri::parse(original_text, &opts);
+                if let Err(e) = rebuild::verify_structure(&entries, &original) {
+                    return err(id, e);
+                }
+            }
+            let cfg = match rebuild_config_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            if let Err(e) = rebuild::check_line_counts(&entries) {
+                return err(id, e);
+            }
+            let output = rebuild::rebuild(&entries, &cfg);
             ok(id, json!({ "text": output }))
         }
 
+        "rebuild.patch" => {
+            let entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let cfg = match rebuild_config_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            ok(id, json!({ "changes": rebuild::patch(&entries, &cfg) }))
+        }
+
+        "rebuild.verify_against_file" => {
+            let entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let path_str = payload.get("path").and_then(|v| v.as_str()).unwrap_or("");
+            if path_str.is_empty() { return err(id, "payload.path is required"); }
+            let encoding_name = payload.get("encoding").and_then(|v| v.as_str()).filter(|s| !s.is_empty());
+
+            let (text, _) = match encoding::read_text_with_bom(std::path::Path::new(path_str), encoding_name) {
+                Ok(v) => v,
+                Err(e) => return err(id, format!("failed to read {path_str}: {e}")),
+            };
+            let text = encoding::strip_bom(&text);
+            let opts = match parse_options_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let cfg = match rebuild_config_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+
+            let original = parsers::kirikiri::parse(text, &opts);
+            let discrepancies = rebuild::verify_against(&entries, &cfg, text, &original);
+            ok(id, json!({ "ok": discrepancies.is_empty(), "discrepancies": discrepancies }))
+        }
+
+        "rebuild_file" => {
+            let entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let path_str = payload.get("path").and_then(|v| v.as_str()).unwrap_or("");
+            if path_str.is_empty() { return err(id, "payload.path is required"); }
+            let encoding_name = payload.get("encoding").and_then(|v| v.as_str()).unwrap_or("utf-8");
+            let check_only = payload.get("check_only").and_then(|v| v.as_bool()).unwrap_or(false);
+            let allow_lossy = payload.get("allow_lossy").and_then(|v| v.as_bool()).unwrap_or(false);
+            let suggest_substitutes = payload.get("suggest_substitutes").and_then(|v| v.as_bool()).unwrap_or(false);
+
+            let cfg = match rebuild_config_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+
+            match rebuild::rebuild_file(
+                &entries,
+                &cfg,
+                std::path::Path::new(path_str),
+                encoding_name,
+                check_only,
+                allow_lossy,
+                suggest_substitutes,
+            ) {
+                Ok(report) => ok(id, json!({ "report": report })),
+                Err(e) => err(id, e),
+            }
+        }
+
         "run_qa" => {
             let entries = match parse_entries_from_payload(payload) {
                 Ok(v) => v,
                 Err(e) => return err(id, e),
             };
-            let issues = qa::run(&entries);
+            let opts = match qa_options_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let issues = qa::run_with(&entries, &opts);
             ok(id, json!({ "issues": issues }))
         }
 
+        "qa.run_batch" => {
+            let files = match parse_files_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let opts = match qa_options_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            ok(id, serde_json::to_value(qa::run_batch(&files, &opts)).unwrap_or(json!({})))
+        }
+
+        "qa.detect_mojibake" => {
+            let entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let threshold = payload
+                .get("threshold")
+                .and_then(|v| v.as_f64())
+                .map(|v| v as f32)
+                .unwrap_or(mojibake::DEFAULT_THRESHOLD);
+
+            ok(id, json!({ "suspects": mojibake::detect(&entries, threshold) }))
+        }
+
         "encoding.detect" | "detect_encoding" => {
             let path_str = payload.get("path").and_then(|v| v.as_str()).unwrap_or("");
             if path_str.is_empty() {
@@ -114,46 +539,162 @@ pub fn handle(input: &str) -> String {
             }
         }
 
-        "translate_entries" => {
-            let provider = payload.get("provider").and_then(|v| v.as_str()).unwrap_or("");
-            let api_key = payload.get("api_key").and_then(|v| v.as_str()).unwrap_or("");
-            let model = payload.get("model").and_then(|v| v.as_str()).unwrap_or("");
-            let source_lang = payload.get("source_lang").and_then(|v| v.as_str()).unwrap_or("ja");
-            let target_lang = payload.get("target_lang").and_then(|v| v.as_str()).unwrap_or("pt-BR");
+        "encoding.detect_dir" => {
+            let path_str = payload.get("path").and_then(|v| v.as_str()).unwrap_or("");
+            if path_str.is_empty() { return err(id, "payload.path is required"); }
+            let extensions: Vec<String> = payload
+                .get("extensions")
+                .and_then(|v| v.as_array())
+                .map(|a| a.iter().filter_map(|s| s.as_str()).map(str::to_string).collect())
+                .unwrap_or_default();
+            let recursive = payload.get("recursive").and_then(|v| v.as_bool()).unwrap_or(false);
+
+            match encoding::detect_dir(std::path::Path::new(path_str), &extensions, recursive) {
+                Ok(result) => ok(id, serde_json::to_value(result).unwrap_or(json!({}))),
+                Err(e) => err(id, e),
+            }
+        }
 
-            if provider.is_empty() { return err(id, "payload.provider is required"); }
-            if api_key.is_empty() { return err(id, "payload.api_key is required"); }
-            if model.is_empty() { return err(id, "payload.model is required"); }
+        "encoding.roundtrip_check" => {
+            let text = payload.get("text").and_then(|v| v.as_str()).unwrap_or("");
+            let encoding_name = payload.get("encoding").and_then(|v| v.as_str()).unwrap_or("");
+            if encoding_name.is_empty() { return err(id, "payload.encoding is required"); }
+
+            match encoding::roundtrip_check(text, encoding_name) {
+                Ok(result) => ok(id, serde_json::to_value(result).unwrap_or(json!({}))),
+                Err(e) => err(id, e),
+            }
+        }
+
+        "translate_entries" => {
+            let cfg = match ai_config_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
 
             let mut entries = match parse_entries_from_payload(payload) {
                 Ok(v) => v,
                 Err(e) => return err(id, e),
             };
 
-            let cfg = ai::AiConfig { provider, api_key, model, source_lang, target_lang };
-            match ai::translate_entries(&mut entries, cfg) {
+            let filter = match query_from_payload(payload, "filter") {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+
+            match ai::translate_entries(&mut entries, cfg, filter.as_ref()) {
                 Ok(report) => ok(id, json!({ "entries": entries, "report": report })),
                 Err(e) => err(id, e),
             }
         }
 
-        "translate_with_tm" => {
-            let provider = payload.get("provider").and_then(|v| v.as_str()).unwrap_or("");
-            let api_key = payload.get("api_key").and_then(|v| v.as_str()).unwrap_or("");
-            let model = payload.get("model").and_then(|v| v.as_str()).unwrap_or("");
-            let source_lang = payload.get("source_lang").and_then(|v| v.as_str()).unwrap_or("ja");
-            let target_lang = payload.get("target_lang").and_then(|v| v.as_str()).unwrap_or("pt-BR");
+        "translate.resume" => {
+            let project_path = payload.get("project_path").and_then(|v| v.as_str()).unwrap_or("");
+            if project_path.is_empty() { return err(id, "payload.project_path is required"); }
+
+            let mut cfg = match ai_config_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            cfg.checkpoint_dir = Some(project_path);
+
+            let cp = match checkpoint::load(project_path) {
+                Ok(Some(cp)) => cp,
+                Ok(None) => return err(id, "no checkpoint to resume"),
+                Err(e) => return err(id, e),
+            };
+
+            let mut entries = cp.entries;
+            let pending: HashSet<&str> = cp.pending.iter().map(String::as_str).collect();
+            let indices: Vec<usize> = entries
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| pending.contains(e.entry_id.as_str()))
+                .map(|(i, _)| i)
+                .collect();
+
+            match ai::translate_indices(&mut entries, &indices, cfg) {
+                Ok(report) => ok(id, json!({ "entries": entries, "report": report })),
+                Err(e) => err(id, e),
+            }
+        }
+
+        "translate.compare_models" => {
+            let entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+
+            // Each side is the shared payload with its own fields on top.
+            let side = |key: &str| -> Value {
+                let mut merged = payload.clone();
+                if let (Some(m), Some(own)) = (merged.as_object_mut(), payload.get(key).and_then(|v| v.as_object())) {
+                    m.remove("a");
+                    m.remove("b");
+                    m.extend(own.clone());
+                }
+                merged
+            };
+            let (payload_a, payload_b) = (side("a"), side("b"));
+            let cfg_a = match ai_config_from_payload(&payload_a) {
+                Ok(v) => v,
+                Err(e) => return err(id, format!("payload.a: {e}")),
+            };
+            let cfg_b = match ai_config_from_payload(&payload_b) {
+                Ok(v) => v,
+                Err(e) => return err(id, format!("payload.b: {e}")),
+            };
 
-            if provider.is_empty() { return err(id, "payload.provider is required"); }
-            if api_key.is_empty() { return err(id, "payload.api_key is required"); }
-            if model.is_empty() { return err(id, "payload.model is required"); }
+            match compare::compare(&entries, cfg_a, cfg_b) {
+                Ok(comparison) => ok(id, json!(comparison)),
+                Err(e) => err(id, e),
+            }
+        }
+
+        "translate_with_tm" => {
+            let ai_cfg = match ai_config_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
 
             let mut entries = match parse_entries_from_payload(payload) {
                 Ok(v) => v,
                 Err(e) => return err(id, e),
             };
 
-            let cfg = pipeline::PipelineConfig { provider, api_key, model, source_lang, target_lang };
+            let fuzzy_auto_threshold = payload
+                .get("fuzzy_auto_threshold")
+                .and_then(|v| v.as_f64())
+                .map(|n| n as f32)
+                .unwrap_or(pipeline::DEFAULT_FUZZY_AUTO_THRESHOLD);
+            let fuzzy_review_threshold = payload
+                .get("fuzzy_review_threshold")
+                .and_then(|v| v.as_f64())
+                .map(|n| n as f32)
+                .unwrap_or(pipeline::DEFAULT_FUZZY_REVIEW_THRESHOLD);
+
+            let tm_dedup = match tm_dedup_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+
+            let cfg = pipeline::PipelineConfig {
+                ai: ai_cfg,
+                fuzzy_auto_threshold,
+                fuzzy_review_threshold,
+                tm_dedup,
+                write_tm: payload.get("write_tm").and_then(|v| v.as_bool()).unwrap_or(true),
+                tm_flush_every: payload
+                    .get("tm_flush_every")
+                    .and_then(|v| v.as_u64())
+                    .map(|n| n as usize)
+                    .unwrap_or(pipeline::DEFAULT_TM_FLUSH_EVERY),
+                blank_failures: payload.get("blank_failures").and_then(|v| v.as_bool()).unwrap_or(false),
+                filter: match query_from_payload(payload, "filter") {
+                    Ok(v) => v,
+                    Err(e) => return err(id, e),
+                },
+            };
             match pipeline::run(&mut entries, cfg) {
                 Ok(report) => ok(id, json!({ "entries": entries, "report": report })),
                 Err(e) => err(id, e),
@@ -217,6 +758,562 @@ pub fn handle(input: &str) -> String {
             }
         }
 
+        "project.rename" => {
+            let project_path = payload.get("project_path").and_then(|v| v.as_str()).unwrap_or("").to_string();
+            if project_path.is_empty() { return err(id, "payload.project_path is required"); }
+            let name = payload.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string();
+            if name.trim().is_empty() { return err(id, "payload.name is required"); }
+            let move_dir = payload.get("move_dir").and_then(|v| v.as_bool()).unwrap_or(false);
+
+            match project::rename_project(project_path, name, move_dir) {
+                Ok(p) => ok(id, json!({ "project": p })),
+                Err(e) => err(id, e),
+            }
+        }
+
+        "project.set_encoding" => {
+            let project_path = payload.get("project_path").and_then(|v| v.as_str()).unwrap_or("").to_string();
+            if project_path.is_empty() { return err(id, "payload.project_path is required"); }
+            let encoding = payload.get("encoding").and_then(|v| v.as_str()).unwrap_or("");
+            if encoding.trim().is_empty() { return err(id, "payload.encoding is required"); }
+
+            // Files on disk are only rewritten when explicitly asked to.
+            let files: Vec<String> = if payload.get("reconvert").and_then(|v| v.as_bool()).unwrap_or(false) {
+                let files: Vec<String> = payload
+                    .get("files")
+                    .and_then(|v| v.as_array())
+                    .map(|a| a.iter().filter_map(|s| s.as_str()).map(str::to_string).collect())
+                    .unwrap_or_default();
+                if files.is_empty() { return err(id, "payload.files is required for reconvert"); }
+                files
+            } else {
+                Vec::new()
+            };
+            let allow_lossy = payload.get("allow_lossy").and_then(|v| v.as_bool()).unwrap_or(false);
+
+            match project::set_encoding(project_path, encoding, &files, allow_lossy) {
+                Ok(change) => ok(id, json!(change)),
+                Err(e) => err(id, e),
+            }
+        }
+
+        "file.fingerprint" | "file.check_fingerprint" => {
+            let project_path = payload.get("project_path").and_then(|v| v.as_str()).unwrap_or("");
+            if project_path.is_empty() { return err(id, "payload.project_path is required"); }
+            let files: Vec<String> = payload
+                .get("files")
+                .and_then(|v| v.as_array())
+                .map(|a| a.iter().filter_map(|s| s.as_str()).map(str::to_string).collect())
+                .unwrap_or_default();
+            if files.is_empty() { return err(id, "payload.files is required"); }
+
+            if cmd_str == "file.fingerprint" {
+                match fingerprint::record(project_path, &files) {
+                    Ok(fps) => ok(id, json!({ "fingerprints": fps })),
+                    Err(e) => err(id, e),
+                }
+            } else {
+                match fingerprint::check(project_path, &files) {
+                    Ok(files) => ok(id, json!({ "files": files })),
+                    Err(e) => err(id, e),
+                }
+            }
+        }
+
+        "project.export_bundle" => {
+            let project_path = payload.get("project_path").and_then(|v| v.as_str()).unwrap_or("");
+            if project_path.is_empty() { return err(id, "payload.project_path is required"); }
+
+            let files = if payload.get("files").is_some() {
+                match parse_files_from_payload(payload) {
+                    Ok(v) => v,
+                    Err(e) => return err(id, e),
+                }
+            } else {
+                BTreeMap::new()
+            };
+
+            let b = match bundle::export_bundle(project_path, files) {
+                Ok(b) => b,
+                Err(e) => return err(id, e),
+            };
+
+            match payload.get("path").and_then(|v| v.as_str()).filter(|p| !p.is_empty()) {
+                Some(path) => {
+                    let json = match serde_json::to_string_pretty(&b) {
+                        Ok(s) => s,
+                        Err(e) => return err(id, e.to_string()),
+                    };
+                    match std::fs::write(path, json) {
+                        Ok(()) => ok(id, json!({ "path": path })),
+                        Err(e) => err(id, format!("failed to write bundle: {e}")),
+                    }
+                }
+                None => ok(id, json!({ "bundle": b })),
+            }
+        }
+
+        "project.import_bundle" => {
+            let mut b: bundle::ProjectBundle = match (payload.get("bundle"), payload.get("path").and_then(|v| v.as_str())) {
+                (Some(v), _) if !v.is_null() => match serde_json::from_value(v.clone()) {
+                    Ok(b) => b,
+                    Err(e) => return err(id, format!("invalid payload.bundle: {e}")),
+                },
+                (_, Some(path)) if !path.is_empty() => match bundle::read_bundle(std::path::Path::new(path)) {
+                    Ok(b) => b,
+                    Err(e) => return err(id, e),
+                },
+                _ => return err(id, "payload.bundle or payload.path is required"),
+            };
+
+            let name = payload.get("name").and_then(|v| v.as_str()).map(str::to_string);
+            let game_root = payload.get("game_root").and_then(|v| v.as_str()).unwrap_or("").to_string();
+            let files = std::mem::take(&mut b.files);
+
+            match bundle::import_bundle(b, name, game_root) {
+                Ok(p) => ok(id, json!({ "project": p, "files": files })),
+                Err(e) => err(id, e),
+            }
+        }
+
+        "normalize.preview" => {
+            let text = payload.get("text").and_then(|v| v.as_str()).unwrap_or("");
+            let normalized = normalize::normalize(text);
+            let h = hash::hash_norm(&normalized);
+            ok(id, json!({ "normalized": normalized, "hash": h }))
+        }
+
+        "tm.inconsistencies" => {
+            let glossary = match payload.get("project_path").and_then(|v| v.as_str()).filter(|p| !p.is_empty()) {
+                Some(project_path) => match glossary::load(project_path) {
+                    Ok(v) => v,
+                    Err(e) => return err(id, e),
+                },
+                None => match parse_glossary_from_payload(payload) {
+                    Ok(v) => v,
+                    Err(e) => return err(id, e),
+                },
+            };
+
+            let tm_entries = store::peek();
+            ok(id, json!(audit::inconsistencies(&tm_entries, &glossary)))
+        }
+
+        "tm.coverage" => {
+            let source_lang = payload.get("source_lang").and_then(|v| v.as_str()).unwrap_or("");
+            let target_lang = payload.get("target_lang").and_then(|v| v.as_str()).unwrap_or("");
+            if source_lang.is_empty() { return err(id, "payload.source_lang is required"); }
+            if target_lang.is_empty() { return err(id, "payload.target_lang is required"); }
+
+            let entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+
+            let fuzzy_threshold = payload.get("fuzzy").and_then(|v| v.as_bool()).unwrap_or(false).then(|| {
+                payload
+                    .get("fuzzy_review_threshold")
+                    .and_then(|v| v.as_f64())
+                    .map(|n| n as f32)
+                    .unwrap_or(pipeline::DEFAULT_FUZZY_REVIEW_THRESHOLD)
+            });
+
+            let tm_entries = store::peek();
+            let report = coverage::coverage(&entries, &tm_entries, source_lang, target_lang, fuzzy_threshold);
+            ok(id, json!({ "report": report }))
+        }
+
+        "tm.search" => {
+            let text = payload.get("query").and_then(|v| v.as_str()).unwrap_or("");
+
+            let fields: Vec<&str> = payload
+                .get("fields")
+                .and_then(|v| v.as_array())
+                .map(|a| a.iter().filter_map(|f| f.as_str()).collect())
+                .unwrap_or_else(|| vec!["original", "translation"]);
+
+            let q = search::SearchQuery {
+                text,
+                in_original: fields.contains(&"original"),
+                in_translation: fields.contains(&"translation"),
+                offset: payload.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+                limit: payload
+                    .get("limit")
+                    .and_then(|v| v.as_u64())
+                    .map(|n| n as usize)
+                    .unwrap_or(search::DEFAULT_LIMIT),
+            };
+
+            let tm_entries = store::peek();
+            let page = search::search(&tm_entries, &q);
+            ok(id, json!({ "total": page.total, "entries": page.entries }))
+        }
+
+        "tm.bootstrap_from_entries" => {
+            let source_lang = payload.get("source_lang").and_then(|v| v.as_str()).unwrap_or("");
+            let target_lang = payload.get("target_lang").and_then(|v| v.as_str()).unwrap_or("");
+            if source_lang.is_empty() { return err(id, "payload.source_lang is required"); }
+            if target_lang.is_empty() { return err(id, "payload.target_lang is required"); }
+
+            let entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let tm_dedup = match tm_dedup_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+
+            match seed::seed_from_entries(&entries, source_lang, target_lang, tm_dedup) {
+                Ok(report) => ok(id, json!({ "report": report })),
+                Err(e) => err(id, e),
+            }
+        }
+
+        "parser.detect" => {
+            let path_str = payload.get("path").and_then(|v| v.as_str()).unwrap_or("");
+            let text = if !path_str.is_empty() {
+                match encoding::read_text(std::path::Path::new(path_str)) {
+                    Ok(t) => t,
+                    Err(e) => return err(id, e),
+                }
+            } else {
+                match payload.get("text").and_then(|v| v.as_str()) {
+                    Some(t) => encoding::strip_bom(t).to_string(),
+                    None => return err(id, "payload.path or payload.text is required"),
+                }
+            };
+
+            ok(id, json!({ "parsers": parsers::detect(&text) }))
+        }
+
+        "glossary.get" => {
+            let project_path = payload.get("project_path").and_then(|v| v.as_str()).unwrap_or("");
+            if project_path.is_empty() { return err(id, "payload.project_path is required"); }
+
+            match glossary::load(project_path) {
+                Ok(terms) => ok(id, json!({ "glossary": terms })),
+                Err(e) => err(id, e),
+            }
+        }
+
+        "glossary.save" => {
+            let project_path = payload.get("project_path").and_then(|v| v.as_str()).unwrap_or("");
+            if project_path.is_empty() { return err(id, "payload.project_path is required"); }
+
+            let terms = match parse_glossary_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+
+            match glossary::save(project_path, &terms) {
+                Ok(()) => ok(id, json!({ "count": terms.len() })),
+                Err(e) => err(id, e),
+            }
+        }
+
+        "glossary.import_csv" => {
+            let project_path = payload.get("project_path").and_then(|v| v.as_str()).unwrap_or("");
+            if project_path.is_empty() { return err(id, "payload.project_path is required"); }
+
+            let path_str = payload.get("path").and_then(|v| v.as_str()).unwrap_or("");
+            let text = if !path_str.is_empty() {
+                match encoding::read_text(std::path::Path::new(path_str)) {
+                    Ok(t) => t,
+                    Err(e) => return err(id, e),
+                }
+            } else {
+                match payload.get("csv").and_then(|v| v.as_str()) {
+                    Some(t) => encoding::strip_bom(t).to_string(),
+                    None => return err(id, "payload.path or payload.csv is required"),
+                }
+            };
+
+            match glossary::import_csv(project_path, &text) {
+                Ok(report) => ok(id, json!({ "report": report })),
+                Err(e) => err(id, e),
+            }
+        }
+
+        "glossary.export_csv" => {
+            let project_path = payload.get("project_path").and_then(|v| v.as_str()).unwrap_or("");
+            if project_path.is_empty() { return err(id, "payload.project_path is required"); }
+
+            match glossary::export_csv(project_path) {
+                Ok(csv) => ok(id, json!({ "csv": csv })),
+                Err(e) => err(id, e),
+            }
+        }
+
+        "entries.query" => {
+            let entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+
+            let q = match query_from_payload(payload, "query") {
+                Ok(q) => q.unwrap_or_default(),
+                Err(e) => return err(id, e),
+            };
+
+            let matched = query::matching_indices(&entries, &q);
+            let entry_ids: Vec<&str> = matched.iter().map(|&i| entries[i].entry_id.as_str()).collect();
+
+            if payload.get("include_entries").and_then(|v| v.as_bool()).unwrap_or(false) {
+                let selected: Vec<&CoreEntry> = matched.iter().map(|&i| &entries[i]).collect();
+                ok(id, json!({ "entry_ids": entry_ids, "entries": selected }))
+            } else {
+                ok(id, json!({ "entry_ids": entry_ids }))
+            }
+        }
+
+        "entries.split" => {
+            let entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let entry_id = payload.get("entry_id").and_then(|v| v.as_str()).unwrap_or("");
+            if entry_id.is_empty() { return err(id, "payload.entry_id is required"); }
+
+            let points = if let Some(arr) = payload.get("offsets").and_then(|v| v.as_array()) {
+                segment::SplitPoints::Offsets(
+                    arr.iter().filter_map(|v| v.as_u64()).map(|n| n as usize).collect(),
+                )
+            } else if let Some(d) = payload.get("delimiter").and_then(|v| v.as_str()) {
+                segment::SplitPoints::Delimiter(d)
+            } else {
+                return err(id, "payload.offsets or payload.delimiter is required");
+            };
+
+            match segment::split(&entries, entry_id, points) {
+                Ok(out) => ok(id, json!({ "entries": out })),
+                Err(e) => err(id, e),
+            }
+        }
+
+        "entries.merge" => {
+            let entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let entry_ids: Vec<String> = payload
+                .get("entry_ids")
+                .and_then(|v| v.as_array())
+                .map(|a| a.iter().filter_map(|s| s.as_str()).map(str::to_string).collect())
+                .unwrap_or_default();
+
+            match segment::merge(&entries, &entry_ids) {
+                Ok(out) => ok(id, json!({ "entries": out })),
+                Err(e) => err(id, e),
+            }
+        }
+
+        "entries.fill_untranslated_from" => {
+            let mut entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let source = match payload.get("source").and_then(|v| v.as_array()) {
+                Some(arr) => match parse_entries(arr) {
+                    Ok(v) => v,
+                    Err(e) => return err(id, format!("payload.source: {e}")),
+                },
+                None => return err(id, "payload.source is required"),
+            };
+
+            let report = propagate::fill_untranslated_from(&source, &mut entries);
+            ok(id, json!({ "entries": entries, "report": report }))
+        }
+
+        "entries.bulk_translate_from_map" => {
+            let mut entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let map = |key: &str| -> Result<HashMap<String, String>, String> {
+                match payload.get(key) {
+                    None | Some(Value::Null) => Ok(HashMap::new()),
+                    Some(v) => serde_json::from_value(v.clone())
+                        .map_err(|e| format!("invalid payload.{key} (expected an object of strings): {e}")),
+                }
+            };
+            let (by_id, by_original) = match (map("by_id"), map("by_original")) {
+                (Ok(a), Ok(b)) => (a, b),
+                (Err(e), _) | (_, Err(e)) => return err(id, e),
+            };
+            if by_id.is_empty() && by_original.is_empty() { return err(id, "payload.by_id or payload.by_original is required"); }
+
+            let report = propagate::apply_map(&mut entries, &by_id, &by_original);
+            ok(id, json!({ "entries": entries, "report": report }))
+        }
+
+        "entries.set_comment" | "entries.clear_comment" => {
+            let mut entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let entry_id = payload.get("entry_id").and_then(|v| v.as_str()).unwrap_or("");
+            if entry_id.is_empty() { return err(id, "payload.entry_id is required"); }
+
+            let comment = if cmd_str == "entries.set_comment" {
+                match payload.get("comment").and_then(|v| v.as_str()).map(str::trim) {
+                    Some(c) if !c.is_empty() => Some(c.to_string()),
+                    _ => return err(id, "payload.comment is required"),
+                }
+            } else {
+                None
+            };
+
+            match entries.iter_mut().find(|e| e.entry_id == entry_id) {
+                Some(e) => e.comment = comment,
+                None => return err(id, format!("entry not found: {entry_id}")),
+            }
+            ok(id, json!({ "entries": entries }))
+        }
+
+        "entries.undo_translation" => {
+            let mut entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let ids: Vec<String> = payload
+                .get("entry_ids")
+                .and_then(|v| v.as_array())
+                .map(|a| a.iter().filter_map(|s| s.as_str()).map(str::to_string).collect())
+                .unwrap_or_default();
+            if ids.is_empty() { return err(id, "payload.entry_ids is required"); }
+
+            // Entries with no earlier translation are left alone and not listed.
+            let mut undone = Vec::new();
+            for entry_id in &ids {
+                match entries.iter_mut().find(|e| &e.entry_id == entry_id) {
+                    Some(e) => {
+                        if e.undo_translation() {
+                            undone.push(entry_id.clone());
+                        }
+                    }
+                    None => return err(id, format!("entry not found: {entry_id}")),
+                }
+            }
+            ok(id, json!({ "entries": entries, "undone": undone }))
+        }
+
+        "entries.history" => {
+            let entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let entry_id = payload.get("entry_id").and_then(|v| v.as_str()).unwrap_or("");
+            if entry_id.is_empty() { return err(id, "payload.entry_id is required"); }
+
+            match entries.iter().find(|e| e.entry_id == entry_id) {
+                Some(e) => ok(id, json!({ "translation": e.translation, "history": e.history })),
+                None => err(id, format!("entry not found: {entry_id}")),
+            }
+        }
+
+        "entries.review" => {
+            let project_path = payload.get("project_path").and_then(|v| v.as_str()).unwrap_or("");
+            if project_path.is_empty() { return err(id, "payload.project_path is required"); }
+            let reviewer = payload.get("reviewer").and_then(|v| v.as_str()).unwrap_or("").trim();
+            if reviewer.is_empty() { return err(id, "payload.reviewer is required"); }
+
+            let mut entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+            let ids: Vec<String> = payload
+                .get("entry_ids")
+                .and_then(|v| v.as_array())
+                .map(|a| a.iter().filter_map(|s| s.as_str()).map(str::to_string).collect())
+                .unwrap_or_default();
+            if ids.is_empty() { return err(id, "payload.entry_ids is required"); }
+            let qa_opts = match qa_options_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+
+            match review::review(project_path, &mut entries, &ids, reviewer, &qa_opts) {
+                Ok(report) => ok(id, json!({ "entries": entries, "report": report })),
+                Err(e) => err(id, e),
+            }
+        }
+
+        "entries.renumber" => {
+            let entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+
+            ok(id, json!(segment::renumber(&entries)))
+        }
+
+        "entries.validate" => {
+            let entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+
+            let issues = rebuild::inconsistencies(&entries);
+            ok(id, json!({ "valid": issues.is_empty(), "issues": issues }))
+        }
+
+        "entries.speakers" => {
+            let entries = match parse_entries_from_payload(payload) {
+                Ok(v) => v,
+                Err(e) => return err(id, e),
+            };
+
+            ok(id, json!({ "speakers": stats::speakers(&entries) }))
+        }
+
+        "stats.progress" => {
+            if payload.get("files").is_none() && payload.get("entries").is_none() {
+                return err(id, "payload.entries or payload.files is required");
+            }
+            let report = if payload.get("files").is_some() {
+                match parse_files_from_payload(payload) {
+                    Ok(files) => stats::progress(files.values().map(Vec::as_slice)),
+                    Err(e) => return err(id, e),
+                }
+            } else {
+                match parse_entries_from_payload(payload) {
+                    Ok(entries) => stats::progress([entries.as_slice()]),
+                    Err(e) => return err(id, e),
+                }
+            };
+
+            ok(id, json!(report))
+        }
+
+        "text.split_sentences" => {
+            let text = payload.get("text").and_then(|v| v.as_str()).unwrap_or("");
+            let lang = payload.get("lang").and_then(|v| v.as_str()).unwrap_or("ja");
+
+            let sentences: Vec<Value> = text::split_sentences(text, lang)
+                .into_iter()
+                .map(|r| json!({ "start": r.start, "end": r.end, "text": &text[r] }))
+                .collect();
+            ok(id, json!({ "sentences": sentences }))
+        }
+
+        "tokens.count" => {
+            let model = payload.get("model").and_then(|v| v.as_str()).unwrap_or("");
+            let texts: Vec<&str> = match (payload.get("text").and_then(|v| v.as_str()), payload.get("texts")) {
+                (Some(t), _) => vec![t],
+                (None, Some(Value::Array(arr))) => arr.iter().filter_map(|v| v.as_str()).collect(),
+                _ => return err(id, "payload.text or payload.texts is required"),
+            };
+
+            let counts: Vec<usize> = texts.iter().map(|t| tokens::count(model, t)).collect();
+
+            ok(id, json!({
+                "total": counts.iter().sum::<usize>(),
+                "counts": counts,
+                "method": tokens::method(model),
+            }))
+        }
+
         _ => err(id, "unknown command"),
     }
 }
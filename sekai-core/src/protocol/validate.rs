@@ -0,0 +1,347 @@
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    String,
+    Number,
+    Bool,
+    Array,
+    Object,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::String => "string",
+            Kind::Number => "number",
+            Kind::Bool => "boolean",
+            Kind::Array => "array",
+            Kind::Object => "object",
+        }
+    }
+
+    fn matches(self, v: &Value) -> bool {
+        match self {
+            Kind::String => v.is_string(),
+            Kind::Number => v.is_number(),
+            Kind::Bool => v.is_boolean(),
+            Kind::Array => v.is_array(),
+            Kind::Object => v.is_object(),
+        }
+    }
+}
+
+pub struct Field {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub required: bool,
+}
+
+macro_rules! req {
+    ($name:literal, $kind:ident) => {
+        Field { name: $name, kind: Kind::$kind, required: true }
+    };
+}
+
+macro_rules! opt {
+    ($name:literal, $kind:ident) => {
+        Field { name: $name, kind: Kind::$kind, required: false }
+    };
+}
+
+pub struct ValidationError {
+    pub code: &'static str,
+    pub path: String,
+    pub message: String,
+}
+
+const AI_FIELDS: &[Field] = &[
+    req!("provider", String),
+    opt!("api_key", String),
+    opt!("model", String),
+    opt!("source_lang", String),
+    opt!("target_lang", String),
+    opt!("context_lines", Number),
+    opt!("style_reference_lines", Number),
+    opt!("glossary", Array),
+    opt!("register", String),
+    opt!("allow_break_reposition", Bool),
+    opt!("cleanup", Object),
+    opt!("trivial", Object),
+    opt!("nontext", Object),
+    opt!("revise", Bool),
+    opt!("batch_requests", Bool),
+    opt!("json_batches", Bool),
+    opt!("max_tokens", Number),
+    opt!("max_chars", Number),
+    opt!("max_backoff_ms", Number),
+    opt!("mock", Object),
+    opt!("headers", Object),
+    opt!("allow_auth_override", Bool),
+    opt!("checkpoint", Bool),
+    opt!("audit", Bool),
+    opt!("max_consecutive_failures", Number),
+    opt!("on_ai_failure", String),
+    opt!("project_path", String),
+];
+
+const REBUILD_FIELDS: &[Field] = &[
+    req!("entries", Array),
+    opt!("original_text", String),
+    opt!("attributes", Array),
+    opt!("block_terminators", Array),
+    opt!("strictness", String),
+    opt!("translatable_patterns", Array),
+    opt!("inline_speakers", Array),
+    opt!("glossary", Array),
+    opt!("on_untranslated", String),
+    opt!("untranslated_marker", String),
+    opt!("substitutions", Array),
+    opt!("normalization", String),
+    opt!("target_lang", String),
+    opt!("rtl", Object),
+];
+
+/// Options read by `qa_options_from_payload`.
+const QA_FIELDS: &[Field] = &[
+    opt!("bracket_pairs", Array),
+    opt!("nontext", Object),
+    opt!("glossary", Array),
+    opt!("max_chars", Number),
+    opt!("target_lang", String),
+    opt!("residue_whitelist", Array),
+    opt!("residue_max_chars", Number),
+];
+
+/// Expected payload fields per command; commands without a schema are not
+/// checked here.
+fn schema(cmd: &str) -> &'static [&'static [Field]] {
+    match cmd {
+        "parse_text" => &[&[
+            opt!("text", String),
+            opt!("path", String),
+            opt!("encoding", String),
+            opt!("attributes", Array),
+            opt!("block_terminators", Array),
+            opt!("strictness", String),
+            opt!("translatable_patterns", Array),
+            opt!("inline_speakers", Array),
+        ]],
+        "rebuild_text" | "rebuild.patch" => &[REBUILD_FIELDS],
+        "rebuild.verify_against_file" => &[REBUILD_FIELDS, &[req!("path", String), opt!("encoding", String)]],
+        "rebuild_file" => &[
+            REBUILD_FIELDS,
+            &[
+                req!("path", String),
+                opt!("encoding", String),
+                opt!("check_only", Bool),
+                opt!("allow_lossy", Bool),
+                opt!("suggest_substitutes", Bool),
+            ],
+        ],
+        "run_qa" => &[QA_FIELDS, &[req!("entries", Array)]],
+        "qa.run_batch" => &[QA_FIELDS, &[req!("files", Object)]],
+        "qa.detect_mojibake" => &[&[req!("entries", Array), opt!("threshold", Number)]],
+        "encoding.detect" | "detect_encoding" => &[&[req!("path", String)]],
+        "encoding.detect_dir" => &[&[req!("path", String), opt!("extensions", Array), opt!("recursive", Bool)]],
+        "encoding.roundtrip_check" => &[&[opt!("text", String), req!("encoding", String)]],
+        "translate_entries" => &[AI_FIELDS, &[req!("entries", Array), opt!("filter", Object)]],
+        "translate.resume" => &[AI_FIELDS, &[req!("project_path", String)]],
+        "translate.compare_models" => &[&[req!("entries", Array), req!("a", Object), req!("b", Object)]],
+        "translate_with_tm" => &[
+            AI_FIELDS,
+            &[
+                req!("entries", Array),
+                opt!("filter", Object),
+                opt!("fuzzy_auto_threshold", Number),
+                opt!("fuzzy_review_threshold", Number),
+                opt!("tm_dedup", String),
+                opt!("write_tm", Bool),
+                opt!("tm_flush_every", Number),
+                opt!("blank_failures", Bool),
+            ],
+        ],
+        "project.create" => &[&[
+            req!("name", String),
+            req!("game_root", String),
+            opt!("encoding", String),
+            opt!("engine", String),
+            opt!("parser_id", String),
+            opt!("source_language", String),
+            opt!("target_language", String),
+        ]],
+        "project.open" => &[&[req!("project_path", String)]],
+        "project.save" => &[&[req!("project", Object)]],
+        "project.export_bundle" => &[&[
+            req!("project_path", String),
+            opt!("files", Object),
+            opt!("path", String),
+        ]],
+        "project.import_bundle" => &[&[
+            opt!("bundle", Object),
+            opt!("path", String),
+            opt!("name", String),
+            opt!("game_root", String),
+        ]],
+        "project.rename" => &[&[
+            req!("project_path", String),
+            req!("name", String),
+            opt!("move_dir", Bool),
+        ]],
+        "project.set_encoding" => &[&[
+            req!("project_path", String),
+            req!("encoding", String),
+            opt!("reconvert", Bool),
+            opt!("files", Array),
+            opt!("allow_lossy", Bool),
+        ]],
+        "file.fingerprint" | "file.check_fingerprint" => &[&[req!("project_path", String), req!("files", Array)]],
+        "normalize.preview" => &[&[opt!("text", String)]],
+        "tm.inconsistencies" => &[&[opt!("project_path", String), opt!("glossary", Array)]],
+        "tm.search" => &[&[
+            opt!("query", String),
+            opt!("fields", Array),
+            opt!("offset", Number),
+            opt!("limit", Number),
+        ]],
+        "tm.coverage" => &[&[
+            req!("source_lang", String),
+            req!("target_lang", String),
+            req!("entries", Array),
+            opt!("fuzzy", Bool),
+            opt!("fuzzy_review_threshold", Number),
+        ]],
+        "tm.bootstrap_from_entries" => &[&[
+            req!("source_lang", String),
+            req!("target_lang", String),
+            req!("entries", Array),
+            opt!("tm_dedup", String),
+        ]],
+        "parser.detect" => &[&[opt!("path", String), opt!("text", String)]],
+        "glossary.get" | "glossary.export_csv" => &[&[req!("project_path", String)]],
+        "glossary.save" => &[&[req!("project_path", String), opt!("glossary", Array)]],
+        "glossary.import_csv" => &[&[
+            req!("project_path", String),
+            opt!("path", String),
+            opt!("csv", String),
+        ]],
+        "entries.query" => &[
+            QA_FIELDS,
+            &[
+                req!("entries", Array),
+                opt!("query", Object),
+                opt!("include_entries", Bool),
+            ],
+        ],
+        "entries.split" => &[&[
+            req!("entries", Array),
+            req!("entry_id", String),
+            opt!("offsets", Array),
+            opt!("delimiter", String),
+        ]],
+        "entries.merge" => &[&[req!("entries", Array), req!("entry_ids", Array)]],
+        "entries.fill_untranslated_from" => &[&[req!("entries", Array), req!("source", Array)]],
+        "entries.bulk_translate_from_map" => &[&[
+            req!("entries", Array),
+            opt!("by_id", Object),
+            opt!("by_original", Object),
+        ]],
+        "entries.set_comment" => &[&[
+            req!("entries", Array),
+            req!("entry_id", String),
+            req!("comment", String),
+        ]],
+        "entries.clear_comment" => &[&[req!("entries", Array), req!("entry_id", String)]],
+        "entries.undo_translation" => &[&[req!("entries", Array), req!("entry_ids", Array)]],
+        "entries.history" => &[&[req!("entries", Array), req!("entry_id", String)]],
+        "entries.renumber" | "entries.speakers" | "entries.validate" => &[&[req!("entries", Array)]],
+        "entries.review" => &[
+            QA_FIELDS,
+            &[
+                req!("project_path", String),
+                req!("reviewer", String),
+                req!("entries", Array),
+                req!("entry_ids", Array),
+            ],
+        ],
+        "stats.progress" => &[&[opt!("entries", Array), opt!("files", Object)]],
+        "text.split_sentences" => &[&[req!("text", String), opt!("lang", String)]],
+        "tokens.count" => &[&[opt!("model", String), opt!("text", String), opt!("texts", Array)]],
+        _ => &[],
+    }
+}
+
+/// Checks presence and JSON type of the payload fields `cmd` expects.
+/// Required strings must also be non-empty.
+pub fn validate(cmd: &str, payload: &Value) -> Result<(), ValidationError> {
+    for group in schema(cmd) {
+        for field in group.iter() {
+            let path = format!("payload.{}", field.name);
+
+            match payload.get(field.name) {
+                None | Some(Value::Null) => {
+                    if field.required {
+                        return Err(ValidationError {
+                            code: "MISSING_FIELD",
+                            message: format!("{path} is required"),
+                            path,
+                        });
+                    }
+                }
+                Some(v) => {
+                    if !field.kind.matches(v) {
+                        return Err(ValidationError {
+                            code: "WRONG_TYPE",
+                            message: format!("{path} must be of type {}", field.kind.name()),
+                            path,
+                        });
+                    }
+                    if field.required && v.as_str().is_some_and(|s| s.is_empty()) {
+                        return Err(ValidationError {
+                            code: "MISSING_FIELD",
+                            message: format!("{path} is required"),
+                            path,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn error(cmd: &str, payload: Value) -> (&'static str, String) {
+        let Err(e) = validate(cmd, &payload) else {
+            panic!("{cmd} payload should be rejected");
+        };
+        (e.code, e.path)
+    }
+
+    #[test]
+    fn required_fields_must_be_present_and_non_empty() {
+        let missing = ("MISSING_FIELD", "payload.provider".to_string());
+        assert_eq!(error("translate_entries", json!({ "entries": [] })), missing);
+        assert_eq!(error("translate_entries", json!({ "provider": "", "entries": [] })), missing);
+        assert_eq!(error("translate_entries", json!({ "provider": null, "entries": [] })), missing);
+        assert!(validate("translate_entries", &json!({ "provider": "mock", "entries": [] })).is_ok());
+    }
+
+    #[test]
+    fn fields_must_have_their_type() {
+        assert_eq!(
+            error("translate_entries", json!({ "provider": "mock", "entries": "1-text" })),
+            ("WRONG_TYPE", "payload.entries".to_string())
+        );
+    }
+
+    #[test]
+    fn commands_without_a_schema_pass() {
+        assert!(validate("ping", &Value::Null).is_ok());
+    }
+}
@@ -0,0 +1,91 @@
+use std::io::BufReader;
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::thread;
+
+use crate::{log_info, log_warn};
+
+/// Port given with `--listen <port>` (or `--listen=<port>`); `None` keeps
+/// the stdin/stdout transport.
+pub fn listen_port(mut args: impl Iterator<Item = String>) -> Result<Option<u16>, String> {
+    let mut port = None;
+
+    while let Some(arg) = args.next() {
+        let value = if arg == "--listen" {
+            args.next().ok_or("--listen needs a port")?
+        } else if let Some(v) = arg.strip_prefix("--listen=") {
+            v.to_string()
+        } else {
+            return Err(format!("unknown argument: {arg}"));
+        };
+        let parsed: u16 = value.trim().parse().map_err(|_| format!("invalid port for --listen: {value}"))?;
+        port = Some(parsed);
+    }
+
+    Ok(port)
+}
+
+/// Serves the same newline-delimited JSON as stdin/stdout to every TCP
+/// client on `127.0.0.1:<port>`, one thread per connection. Requests from
+/// all connections still run one at a time.
+pub fn listen(port: u16) -> Result<(), String> {
+    let listener =
+        TcpListener::bind((Ipv4Addr::LOCALHOST, port)).map_err(|e| format!("failed to listen on port {port}: {e}"))?;
+    log_info!("server", "listening on {}", listener.local_addr().map(|a| a.to_string()).unwrap_or_default());
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                thread::spawn(move || handle_connection(stream));
+            }
+            Err(e) => log_warn!("server", "failed to accept connection: {e}"),
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_connection(stream: TcpStream) {
+    let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+    log_info!("server", "client connected: {peer}");
+
+    match stream.try_clone() {
+        Ok(writer) => crate::serve(BufReader::new(stream), writer),
+        Err(e) => log_warn!("server", "failed to set up connection {peer}: {e}"),
+    }
+
+    log_info!("server", "client disconnected: {peer}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, Write};
+
+    fn args(list: &[&str]) -> impl Iterator<Item = String> {
+        list.iter().map(|s| s.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn listen_port_is_read_from_either_form() {
+        assert_eq!(listen_port(args(&[])), Ok(None));
+        assert_eq!(listen_port(args(&["--listen", "7001"])), Ok(Some(7001)));
+        assert_eq!(listen_port(args(&["--listen=7002"])), Ok(Some(7002)));
+        assert!(listen_port(args(&["--listen"])).is_err());
+        assert!(listen_port(args(&["--listen", "http"])).is_err());
+        assert!(listen_port(args(&["--verbose"])).is_err());
+    }
+
+    #[test]
+    fn socket_answers_like_stdin() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || handle_connection(listener.accept().unwrap().0));
+
+        let request = r#"{"id":"1","cmd":"ping"}"#;
+        let mut client = TcpStream::connect(addr).unwrap();
+        writeln!(client, "\n{request}").unwrap();
+        let mut reply = String::new();
+        BufReader::new(client).read_line(&mut reply).unwrap();
+        assert_eq!(reply.trim_end(), crate::respond(request));
+    }
+}
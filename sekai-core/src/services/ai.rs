@@ -1,195 +1,1059 @@
-use crate::model::entry::{CoreEntry, EntryStatus};
-use crate::services::ai_types::{AiItemResult, AiRunReport};
+use crate::model::entry::{CoreEntry, EntryStatus, Register};
+use crate::model::glossary::GlossaryTerm;
+use crate::services::ai_audit::{AuditLog, Exchange};
+use crate::services::ai_types::{AiItemResult, AiRunReport, RunAbort};
+use crate::services::checkpoint::{self, Checkpoint};
+use crate::services::cleanup::{self, CleanupRules};
+use crate::services::glossary;
+use crate::services::linebreaks;
+use crate::services::nontext::NontextFilter;
+use crate::services::providers::{self, AuthStyle, ContextFormat, ProviderSpec, RequestFormat};
+use crate::services::qa;
+use crate::services::query::{self, EntryQuery};
+use crate::services::text;
+use crate::services::tokens;
+use crate::services::translation_memory::normalize;
+use crate::services::trivial::{self, TrivialRule};
+use crate::{log_debug, log_info, log_warn};
 
 use rand::{thread_rng, Rng};
 use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
 use reqwest::StatusCode;
+use serde::Deserialize;
 use serde_json::json;
 
-use std::{thread, time::Duration};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
 
+#[derive(Clone)]
 pub struct AiConfig<'a> {
     pub provider: &'a str,
     pub api_key: &'a str,
     pub model: &'a str,
     pub source_lang: &'a str,
     pub target_lang: &'a str,
+    pub context_lines: usize,
+    /// Already-translated lines preceding an entry (up to this many) shown
+    /// to the model as a style reference; 0 sends none. Only lines finished
+    /// before the request are seen, so this relies on entries being sent in
+    /// file order one batch after another. Were batches ever sent
+    /// concurrently, a batch would lose the references its predecessors are
+    /// still producing, so this must stay sequential when enabled.
+    pub style_reference_lines: usize,
+    /// Terms listed in the prompt of every line whose original contains
+    /// them, respecting speaker scopes.
+    pub glossary: Vec<GlossaryTerm>,
+    /// Project-wide politeness level; an entry's own `register` overrides it.
+    pub register: Register,
+    /// Lets the model move `[r]`/`\n` breaks to suit the target language;
+    /// their count is preserved either way.
+    pub allow_break_reposition: bool,
+    /// Project dir to keep a resumable checkpoint in while the run lasts.
+    pub checkpoint_dir: Option<&'a str>,
+    pub cleanup: CleanupRules,
+    /// Lines without translatable content are copied instead of requested.
+    pub trivial: TrivialRule,
+    /// URLs, paths, versions and IDs are copied instead of requested too.
+    pub nontext: NontextFilter,
+    /// Entries that already have a translation send it along to be improved
+    /// instead of being translated from scratch.
+    pub revise: bool,
+    /// Send each batch as one numbered-list request, falling back to one
+    /// request per entry for whatever that reply cannot cover.
+    pub batch_requests: bool,
+    /// Ask for batch replies as JSON `{id, translation}` objects where the
+    /// provider supports JSON mode; others keep numbered lines.
+    pub json_batches: bool,
+    /// Longest wait between two retries of one request.
+    pub max_backoff_ms: u64,
+    /// Cap on reply tokens; the provider's default when unset.
+    pub max_tokens: Option<u64>,
+    /// Translations longer than this many characters (or the entry's own
+    /// `max_chars`) are left `InProgress` instead of `Translated`.
+    pub max_chars: Option<usize>,
+    /// What happens to an entry the AI still failed after its retries.
+    pub on_failure: AiFailurePolicy,
+    /// Consecutive failed entries after which the rest of the run is
+    /// abandoned; 0 never gives up.
+    pub max_consecutive_failures: usize,
+    /// Behaviour of the `mock` provider; ignored by real ones.
+    pub mock: MockOptions,
+    /// Where to record every prompt and raw reply, if anywhere.
+    pub audit: Option<AuditLog>,
+    /// Sent with every request after the provider's own headers and auth,
+    /// replacing any of the same name; see `request_headers`.
+    pub headers: HeaderMap,
 }
 
+#[cfg(test)]
+impl AiConfig<'static> {
+    /// The `mock` provider from `ja` to `pt-BR`, with the payload defaults
+    /// and retries that do not wait.
+    pub(crate) fn mock() -> Self {
+        AiConfig {
+            provider: "mock",
+            api_key: "",
+            model: "mock",
+            source_lang: "ja",
+            target_lang: "pt-BR",
+            context_lines: DEFAULT_CONTEXT_LINES,
+            style_reference_lines: 0,
+            glossary: Vec::new(),
+            register: Register::Auto,
+            allow_break_reposition: false,
+            checkpoint_dir: None,
+            cleanup: CleanupRules::default(),
+            trivial: TrivialRule::default(),
+            nontext: NontextFilter::default(),
+            revise: false,
+            batch_requests: false,
+            json_batches: false,
+            max_backoff_ms: 1,
+            max_tokens: None,
+            max_chars: None,
+            on_failure: AiFailurePolicy::Leave,
+            max_consecutive_failures: DEFAULT_MAX_CONSECUTIVE_FAILURES,
+            mock: MockOptions::default(),
+            audit: None,
+            headers: HeaderMap::new(),
+        }
+    }
+}
+
+/// Fate of an entry whose request failed for good. Entries a run never got
+/// to (circuit open), and entries that already had a translation (a failed
+/// revision), are left alone whatever the policy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AiFailurePolicy {
+    /// Keep its translation and status as they were.
+    #[default]
+    Leave,
+    /// Copy the original in so the line still ships intact, as `InProgress`.
+    CopyOriginal,
+    /// Clear its translation and mark it `Untranslated`.
+    MarkUntranslated,
+}
+
+impl AiFailurePolicy {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "" | "leave" => Ok(AiFailurePolicy::Leave),
+            "copy_original" => Ok(AiFailurePolicy::CopyOriginal),
+            "mark_untranslated" => Ok(AiFailurePolicy::MarkUntranslated),
+            other => Err(format!("unknown on_ai_failure policy: {other}")),
+        }
+    }
+
+    fn apply(self, e: &mut CoreEntry) {
+        match self {
+            AiFailurePolicy::Leave => {}
+            AiFailurePolicy::CopyOriginal => {
+                e.set_translation(e.original.clone());
+                e.status = EntryStatus::InProgress;
+            }
+            AiFailurePolicy::MarkUntranslated => {
+                e.set_translation(String::new());
+                e.status = EntryStatus::Untranslated;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct MockOptions {
+    /// Simulated time per request.
+    pub latency_ms: u64,
+    /// Share of requests (0.0–1.0) answered with a retryable 503.
+    pub failure_rate: f64,
+}
+
+pub const DEFAULT_CONTEXT_LINES: usize = 2;
+/// A dead key or network fails every entry; this many in a row stops the run.
+pub const DEFAULT_MAX_CONSECUTIVE_FAILURES: usize = 10;
+
 const MAX_RETRIES: usize = 3;
 const BASE_DELAY_MS: u64 = 800;
+const MAX_JITTER_MS: u64 = 1_000;
+/// Ceiling on a single retry wait unless the payload sets another.
+pub const DEFAULT_MAX_BACKOFF_MS: u64 = 30_000;
 const TIMEOUT_SECS: u64 = 60;
+const POOL_MAX_IDLE_PER_HOST: usize = 2;
+const POOL_IDLE_TIMEOUT_SECS: u64 = 90;
 const BATCH_SIZE: usize = 5;
+/// Bytes of a non-JSON error body kept in the message.
+const MAX_ERROR_SNIPPET: usize = 400;
+/// Batches between checkpoint writes.
+const CHECKPOINT_EVERY: usize = 10;
 
-fn backoff(attempt: usize) -> Duration {
-    let jitter: u64 = thread_rng().gen_range(0..200);
-    let ms = BASE_DELAY_MS * (2_u64.pow(attempt as u32)) + jitter;
-    Duration::from_millis(ms)
+/// Exponential delay before retry `attempt + 1`, never above `max_ms`.
+/// Jitter is a tenth of the delay, at most `MAX_JITTER_MS`, and stays under
+/// the ceiling too.
+fn backoff(attempt: usize, max_ms: u64) -> Duration {
+    let factor = 1_u64.checked_shl(attempt as u32).unwrap_or(u64::MAX);
+    let delay = BASE_DELAY_MS.saturating_mul(factor).min(max_ms);
+    let jitter_range = (delay / 10).min(MAX_JITTER_MS);
+    let jitter: u64 = if jitter_range == 0 { 0 } else { thread_rng().gen_range(0..jitter_range) };
+    Duration::from_millis(delay.saturating_add(jitter).min(max_ms))
 }
 
-fn endpoint_for(provider: &str) -> Result<&'static str, String> {
-    match provider {
-        "openai" => Ok("https://api.openai.com/v1/chat/completions"),
-        "deepseek" => Ok("https://api.deepseek.com/v1/chat/completions"),
-        _ => Err("Unsupported provider".into()),
+/// One client for the whole process, so every run and batch reuses the same
+/// bounded connection pool instead of opening fresh sockets (on Windows,
+/// large runs otherwise risk exhausting ephemeral ports). Requests are issued
+/// one at a time, so at most `POOL_MAX_IDLE_PER_HOST` idle connections per
+/// host are kept, each for `POOL_IDLE_TIMEOUT_SECS`.
+fn http_client() -> Result<&'static Client, String> {
+    static CLIENT: OnceLock<Result<Client, String>> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| {
+            log_debug!("ai", "building http client");
+            Client::builder()
+                .timeout(Duration::from_secs(TIMEOUT_SECS))
+                .pool_max_idle_per_host(POOL_MAX_IDLE_PER_HOST)
+                .pool_idle_timeout(Duration::from_secs(POOL_IDLE_TIMEOUT_SECS))
+                .tcp_keepalive(Duration::from_secs(POOL_IDLE_TIMEOUT_SECS))
+                .build()
+                .map_err(|e| e.to_string())
+        })
+        .as_ref()
+        .map_err(Clone::clone)
+}
+
+fn endpoint_for(provider: &str) -> Result<&'static ProviderSpec, String> {
+    providers::resolve(provider)
+}
+
+/// Translates every translatable entry, or only those matching `filter`.
+pub fn translate_entries(
+    entries: &mut [CoreEntry],
+    cfg: AiConfig,
+    filter: Option<&EntryQuery>,
+) -> Result<AiRunReport, String> {
+    let selected: Option<HashSet<usize>> = filter.map(|q| query::matching_indices(entries, q).into_iter().collect());
+
+    let mut filtered_out = 0usize;
+    let translatable_indices: Vec<usize> = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.is_translatable)
+        .filter_map(|(i, _)| match &selected {
+            Some(set) if !set.contains(&i) => {
+                filtered_out += 1;
+                None
+            }
+            _ => Some(i),
+        })
+        .collect();
+
+    let mut trivial_indices: Vec<usize> = Vec::new();
+    let mut nontext_indices: Vec<usize> = Vec::new();
+    let mut ai_indices: Vec<usize> = Vec::new();
+    for i in translatable_indices {
+        let original = &entries[i].original;
+        if cfg.trivial.is_trivial(original) {
+            trivial_indices.push(i);
+        } else if cfg.nontext.is_nontext(original) {
+            nontext_indices.push(i);
+        } else {
+            ai_indices.push(i);
+        }
+    }
+
+    let max_chars = cfg.max_chars;
+    let mut report = translate_indices(entries, &ai_indices, cfg)?;
+    report.filtered_out = filtered_out;
+    let succeeded: Vec<usize> = report.items.iter().filter(|item| item.ok).map(|item| item.index).collect();
+    report.held_over_length = qa::hold_over_length(entries, succeeded, max_chars);
+
+    for &i in trivial_indices.iter().chain(&nontext_indices) {
+        trivial::copy_original(&mut entries[i]);
+        report.items.push(AiItemResult {
+            index: i,
+            entry_id: entries[i].entry_id.clone(),
+            ok: true,
+            error: None,
+        });
     }
+    report.trivial = trivial_indices.len();
+    report.skipped_nontext = nontext_indices.len();
+    report.items.sort_by_key(|item| item.index);
+
+    Ok(report)
+}
+
+/// What makes two entries' requests identical.
+#[derive(PartialEq, Eq, Hash)]
+struct DedupKey<'a> {
+    source_lang: &'a str,
+    target_lang: &'a str,
+    original: String,
+    draft: Option<&'a str>,
+    hint: Option<&'a str>,
+    /// The entry's own register, or the run's.
+    register: Register,
+    /// Speaker-scoped glossary terms the prompt carries, as sorted
+    /// (source, target) pairs; unscoped ones follow from the original.
+    scoped_terms: Vec<(&'a str, &'a str)>,
 }
 
-pub fn translate_entries(entries: &mut [CoreEntry], cfg: AiConfig) -> Result<AiRunReport, String> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(TIMEOUT_SECS))
-        .build()
-        .map_err(|e| e.to_string())?;
+/// Translates only `indices`, while still drawing prompt context from the
+/// whole `entries` slice so neighbours that are not sent still inform the model.
+pub fn translate_indices(
+    entries: &mut [CoreEntry],
+    indices: &[usize],
+    cfg: AiConfig,
+) -> Result<AiRunReport, String> {
+    translate_indices_with(entries, indices, cfg, |_, _| {})
+}
 
-    let endpoint = endpoint_for(cfg.provider)?;
+/// `translate_indices`, calling `on_batch` after every batch with the
+/// entries and the report so far. Items new since the previous call are at
+/// the end of `report.items`; results copied to duplicates only arrive in
+/// the returned report.
+pub fn translate_indices_with(
+    entries: &mut [CoreEntry],
+    indices: &[usize],
+    cfg: AiConfig,
+    mut on_batch: impl FnMut(&[CoreEntry], &AiRunReport),
+) -> Result<AiRunReport, String> {
+    let client = http_client()?;
+
+    let spec = endpoint_for(cfg.provider)?;
 
     let mut report = AiRunReport {
+        model: cfg.model.to_string(),
         succeeded: 0,
         failed: 0,
+        deduped: 0,
+        filtered_out: 0,
+        total_prompt_tokens: 0,
+        total_completion_tokens: 0,
+        total_ms: 0,
+        audit_file: cfg.audit.as_ref().map(|a| a.path().display().to_string()),
+        trivial: 0,
+        skipped_nontext: 0,
+        revised: 0,
+        held_over_length: 0,
+        aborted: None,
+        consecutive_failures: 0,
         items: Vec::new(),
     };
 
-    let translatable_indices: Vec<usize> = entries
-        .iter()
-        .enumerate()
-        .filter_map(|(i, e)| if e.is_translatable { Some(i) } else { None })
-        .collect();
+    // Identical originals within one run are requested once; the rest reuse
+    // the first result once it is known. When revising, the draft is part of
+    // the request, so only entries with the same draft are alike; the same
+    // goes for a prompt hint, the register and speaker-scoped glossary terms.
+    let mut first_by_key: HashMap<DedupKey, usize> = HashMap::new();
+    let mut unique: Vec<usize> = Vec::with_capacity(indices.len());
+    let mut duplicates: Vec<(usize, usize)> = Vec::new();
+
+    for &idx in indices {
+        let mut scoped_terms: Vec<(&str, &str)> = glossary::terms_for(&cfg.glossary, &entries[idx])
+            .into_iter()
+            .filter(|t| t.is_scoped())
+            .map(|t| (t.source.trim(), t.target.trim()))
+            .collect();
+        scoped_terms.sort_unstable();
+        let key = DedupKey {
+            source_lang: cfg.source_lang,
+            target_lang: cfg.target_lang,
+            original: normalize::normalize(&entries[idx].original),
+            draft: draft(&entries[idx], &cfg),
+            hint: prompt_hint(&entries[idx]),
+            register: entries[idx].register.unwrap_or(cfg.register),
+            scoped_terms,
+        };
+        match first_by_key.get(&key) {
+            Some(&first) => duplicates.push((idx, first)),
+            None => {
+                first_by_key.insert(key, idx);
+                unique.push(idx);
+            }
+        }
+    }
+
+    log_info!(
+        "ai",
+        "translating {} entries via {} ({} requests, {} deduped)",
+        indices.len(),
+        cfg.provider,
+        unique.len(),
+        duplicates.len()
+    );
 
     let mut batch: Vec<usize> = Vec::with_capacity(BATCH_SIZE);
+    let mut batches_done = 0usize;
 
-    for idx in translatable_indices {
+    for &idx in &unique {
+        if circuit_open(&cfg, &report) {
+            break;
+        }
         batch.push(idx);
 
         if batch.len() == BATCH_SIZE {
-            process_batch(&client, endpoint, entries, &batch, &cfg, &mut report);
+            process_batch(client, spec, entries, &batch, &cfg, &mut report);
+            on_batch(entries, &report);
             batch.clear();
+
+            batches_done += 1;
+            if let (Some(dir), 0) = (cfg.checkpoint_dir, batches_done % CHECKPOINT_EVERY) {
+                write_checkpoint(dir, entries, indices, &report);
+            }
         }
     }
 
-    if !batch.is_empty() {
-        process_batch(&client, endpoint, entries, &batch, &cfg, &mut report);
+    if !batch.is_empty() && !circuit_open(&cfg, &report) {
+        process_batch(client, spec, entries, &batch, &cfg, &mut report);
+        on_batch(entries, &report);
+    }
+
+    apply_duplicates(entries, &duplicates, &mut report);
+
+    if circuit_open(&cfg, &report) {
+        let skipped = indices.len() - report.items.len();
+        let message = format!(
+            "{} entries failed in a row; stopped with {} left untried",
+            report.consecutive_failures, skipped
+        );
+        log_warn!("ai", "circuit open: {message}");
+        report.aborted = Some(RunAbort {
+            code: "CIRCUIT_OPEN".into(),
+            message,
+            skipped,
+        });
     }
 
+    // An aborted run keeps its checkpoint so it can be resumed later.
+    if let Some(dir) = cfg.checkpoint_dir {
+        if report.aborted.is_some() {
+            write_checkpoint(dir, entries, indices, &report);
+        } else if let Err(e) = checkpoint::clear(dir) {
+            log_warn!("ai", "{e}");
+        }
+    }
+
+    // After the checkpoint, so a resumed run starts from the untouched entry.
+    // A failed request never writes the translation, so a non-empty one is
+    // what the entry had before the run.
+    for item in report.items.iter().filter(|item| !item.ok) {
+        let e = &mut entries[item.index];
+        if e.translation.trim().is_empty() {
+            cfg.on_failure.apply(e);
+        }
+    }
+
+    // Items are pushed in completion order; callers rely on input order.
+    report.items.sort_by_key(|item| item.index);
+
+    log_info!(
+        "ai",
+        "run finished: {} succeeded, {} failed, {} deduped, {}+{} tokens in {} ms",
+        report.succeeded,
+        report.failed,
+        report.deduped,
+        report.total_prompt_tokens,
+        report.total_completion_tokens,
+        report.total_ms
+    );
+
     Ok(report)
 }
 
+/// Everything in `indices` without a successful result yet stays pending, so
+/// failures are retried on resume too.
+fn write_checkpoint(dir: &str, entries: &[CoreEntry], indices: &[usize], report: &AiRunReport) {
+    let done: HashSet<usize> = report.items.iter().filter(|item| item.ok).map(|item| item.index).collect();
+    let cp = Checkpoint {
+        entries: entries.to_vec(),
+        pending: indices
+            .iter()
+            .filter(|i| !done.contains(i))
+            .map(|&i| entries[i].entry_id.clone())
+            .collect(),
+    };
+
+    match checkpoint::save(dir, &cp) {
+        Ok(()) => log_debug!("ai", "checkpoint: {} pending", cp.pending.len()),
+        Err(e) => log_warn!("ai", "checkpoint failed: {e}"),
+    }
+}
+
+fn circuit_open(cfg: &AiConfig, report: &AiRunReport) -> bool {
+    cfg.max_consecutive_failures > 0 && report.consecutive_failures >= cfg.max_consecutive_failures
+}
+
+/// The existing translation to improve, when `entry` is being revised.
+fn draft<'e>(entry: &'e CoreEntry, cfg: &AiConfig) -> Option<&'e str> {
+    let t = entry.translation.trim();
+    (cfg.revise && !t.is_empty()).then_some(t)
+}
+
+fn prompt_hint(entry: &CoreEntry) -> Option<&str> {
+    entry.prompt_hint.as_deref().map(str::trim).filter(|h| !h.is_empty())
+}
+
+fn apply_duplicates(entries: &mut [CoreEntry], duplicates: &[(usize, usize)], report: &mut AiRunReport) {
+    let outcome_by_index: HashMap<usize, (bool, Option<String>)> = report
+        .items
+        .iter()
+        .map(|item| (item.index, (item.ok, item.error.clone())))
+        .collect();
+
+    for &(idx, first) in duplicates {
+        // Only missing when an aborted run never reached `first`.
+        let Some((ok, error)) = outcome_by_index.get(&first).cloned() else {
+            continue;
+        };
+
+        if ok {
+            let (translation, status) = (entries[first].translation.clone(), entries[first].status);
+            let e = &mut entries[idx];
+            e.set_translation(translation);
+            e.status = status;
+            report.deduped += 1;
+        } else {
+            report.failed += 1;
+        }
+
+        report.items.push(AiItemResult {
+            index: idx,
+            entry_id: entries[idx].entry_id.clone(),
+            ok,
+            error,
+        });
+    }
+}
+
 fn process_batch(
     client: &Client,
-    endpoint: &str,
+    spec: &ProviderSpec,
     entries: &mut [CoreEntry],
     batch_idx: &[usize],
     cfg: &AiConfig,
     report: &mut AiRunReport,
 ) {
-    for &i in batch_idx {
-        let e = &mut entries[i];
+    let singles = if cfg.batch_requests && batch_idx.len() > 1 {
+        translate_joint(client, spec, entries, batch_idx, cfg, report)
+    } else {
+        batch_idx.to_vec()
+    };
+
+    for i in singles {
+        if circuit_open(cfg, report) {
+            break;
+        }
+        translate_single(client, spec, entries, i, cfg, report);
+    }
+}
+
+/// Sends the whole batch as one numbered list. Returns the indices that still
+/// need a request of their own: all of them if the reply cannot be used
+/// (request failure, unparsable or wrong line count), otherwise only the
+/// lines that came back unusable. Nothing is recorded for those here.
+fn translate_joint(
+    client: &Client,
+    spec: &ProviderSpec,
+    entries: &mut [CoreEntry],
+    batch_idx: &[usize],
+    cfg: &AiConfig,
+    report: &mut AiRunReport,
+) -> Vec<usize> {
+    // Multi-line originals would break the one-line-per-entry reply format,
+    // and a numbered list has no room for a draft to revise, a line's own
+    // prompt hint or register, or a term that only holds for one line's
+    // speaker.
+    let (joint, mut singles): (Vec<usize>, Vec<usize>) = batch_idx.iter().partition(|&&i| {
+        let e = &entries[i];
+        !e.original.trim().contains('\n')
+            && draft(e, cfg).is_none()
+            && prompt_hint(e).is_none()
+            && e.register.is_none_or(|r| r == cfg.register)
+            && !glossary::terms_for(&cfg.glossary, e).iter().any(|t| t.is_scoped())
+    });
+    if joint.len() < 2 {
+        return batch_idx.to_vec();
+    }
+
+    let protected: Vec<linebreaks::Protected> =
+        joint.iter().map(|&i| linebreaks::protect(entries[i].original.trim())).collect();
+    let json_reply = cfg.json_batches && spec.json_mode;
+    let lines: String = if json_reply {
+        let items: Vec<serde_json::Value> = protected
+            .iter()
+            .enumerate()
+            .map(|(n, p)| json!({ "id": n + 1, "text": p.text }))
+            .collect();
+        format!("{}\n", serde_json::Value::Array(items))
+    } else {
+        protected
+            .iter()
+            .enumerate()
+            .map(|(n, p)| format!("{}. {}\n", n + 1, p.text))
+            .collect()
+    };
+    let reference = style_reference(entries, joint[0], cfg.style_reference_lines);
+    // The listed lines are each other's context; what precedes the first one
+    // is given like a single request would get it.
+    let context = context_before(entries, joint[0], cfg.context_lines);
+    let mut terms: Vec<&GlossaryTerm> = Vec::new();
+    for &i in &joint {
+        for t in glossary::terms_for(&cfg.glossary, &entries[i]) {
+            if !terms.iter().any(|known| std::ptr::eq(*known, t)) {
+                terms.push(t);
+            }
+        }
+    }
+    let prompt = build_batch_prompt(&lines, json_reply, &protected, &reference, &context, &terms, cfg);
+    let label = format!("batch of {}", joint.len());
+    let ids: Vec<String> = joint.iter().map(|&i| entries[i].entry_id.clone()).collect();
+
+    let content = match request_reply(client, spec, &prompt, &lines, json_reply, cfg, &label, &ids, report) {
+        Ok(c) => c,
+        Err(e) => {
+            log_warn!("ai", "{label} failed, retrying entries one by one: {e}");
+            return batch_idx.to_vec();
+        }
+    };
 
+    // A JSON reply is usable even when some ids are missing; those lines
+    // are requested again one by one.
+    let replies: Option<Vec<Option<String>>> = if json_reply {
+        parse_json_batch(&content, joint.len())
+    } else {
+        parse_numbered(&content, joint.len()).map(|v| v.into_iter().map(Some).collect())
+    };
+    let Some(replies) = replies else {
+        if json_reply {
+            log_warn!("ai", "{label}: reply is not a JSON list of translations, retrying one by one");
+        } else {
+            log_warn!("ai", "{label}: reply does not have {} numbered lines, retrying one by one", joint.len());
+        }
+        return batch_idx.to_vec();
+    };
 
-        let prompt = build_prompt(e, cfg);
+    for ((&i, p), reply) in joint.iter().zip(&protected).zip(replies) {
+        let Some(line) = reply else {
+            singles.push(i);
+            continue;
+        };
+        let cleaned = cleanup::apply(&line, &p.text, cfg.cleanup);
+        match linebreaks::restore(&cleaned, &p.tokens, cfg.allow_break_reposition) {
+            Ok(t) if !t.trim().is_empty() => {
+                let e = &mut entries[i];
+                e.set_translation(t);
+                e.status = EntryStatus::Translated;
 
-        let body = json!({
+                report.succeeded += 1;
+                report.consecutive_failures = 0;
+                report.items.push(AiItemResult {
+                    index: i,
+                    entry_id: e.entry_id.clone(),
+                    ok: true,
+                    error: None,
+                });
+            }
+            _ => singles.push(i),
+        }
+    }
+
+    singles
+}
+
+/// One request with transport/HTTP retries; returns the reply content.
+#[allow(clippy::too_many_arguments)]
+fn request_reply(
+    client: &Client,
+    spec: &ProviderSpec,
+    prompt: &str,
+    mock_input: &str,
+    json_reply: bool,
+    cfg: &AiConfig,
+    label: &str,
+    entry_ids: &[String],
+    report: &mut AiRunReport,
+) -> Result<String, String> {
+    let body = request_body(spec, cfg, prompt, json_reply);
+    let mut last_err = String::new();
+
+    for attempt in 0..MAX_RETRIES {
+        if attempt > 0 {
+            log_warn!("ai", "retry {} for {}: {}", attempt, label, last_err);
+            thread::sleep(backoff(attempt - 1, cfg.max_backoff_ms));
+        }
+
+        let started = Instant::now();
+        let res = match spec.request_format {
+            RequestFormat::Openai => send(client, spec, &body, cfg, label),
+            RequestFormat::Mock => mock_response(mock_input, cfg),
+        };
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        report.total_ms += elapsed_ms;
+        audit(cfg, entry_ids, attempt, elapsed_ms, prompt, &res);
+
+        match res {
+            Ok((status, _, text)) if !status.is_success() => {
+                last_err = extract_error_message(status, &text);
+                if !should_retry_http(status) {
+                    break;
+                }
+            }
+            Ok((_, is_sse, text)) => {
+                let (content, usage) = parse_reply(spec, is_sse, &text)?;
+                add_usage(report, usage);
+                return Ok(content);
+            }
+            Err(err) => last_err = err,
+        }
+    }
+
+    Err(last_err)
+}
+
+fn request_body(spec: &ProviderSpec, cfg: &AiConfig, prompt: &str, json_reply: bool) -> serde_json::Value {
+    let mut body = match spec.request_format {
+        RequestFormat::Openai => json!({
             "model": cfg.model,
             "messages": [
                 { "role": "system", "content": "You are a professional visual novel translator." },
                 { "role": "user", "content": prompt }
             ],
             "temperature": 0.3
-        });
+        }),
+        RequestFormat::Mock => serde_json::Value::Null,
+    };
+    if let (Some(max), RequestFormat::Openai) = (cfg.max_tokens, spec.request_format) {
+        body["max_tokens"] = json!(max);
+    }
+    if spec.stream {
+        body["stream"] = json!(true);
+    }
+    if json_reply && spec.request_format == RequestFormat::Openai {
+        body["response_format"] = json!({ "type": "json_object" });
+    }
+    body
+}
 
-        let mut ok = false;
-        let mut last_err: Option<String> = None;
-
-        for attempt in 0..MAX_RETRIES {
-            let res = client
-                .post(endpoint)
-                .bearer_auth(cfg.api_key)
-                .json(&body)
-                .send();
-
-            match res {
-                Ok(resp) => {
-                    let status = resp.status();
-
-                    let text = match resp.text() {
-                        Ok(t) => t,
-                        Err(err) => {
-                            last_err = Some(err.to_string());
-                            thread::sleep(backoff(attempt));
-                            continue;
-                        }
-                    };
+fn parse_reply(spec: &ProviderSpec, is_sse: bool, text: &str) -> Result<(String, Option<providers::Usage>), String> {
+    if is_sse || spec.stream {
+        return providers::parse_sse(text);
+    }
+    match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(json) => providers::content(&json, &spec.response_path).map(|c| (c, providers::usage(&json))),
+        Err(_) => Err("Invalid JSON from AI".into()),
+    }
+}
 
-                    if !status.is_success() {
-                        last_err = Some(extract_error_message(status, &text));
-                        if should_retry_http(status) && attempt + 1 < MAX_RETRIES {
-                            thread::sleep(backoff(attempt));
-                            continue;
-                        } else {
-                            break;
-                        }
+fn add_usage(report: &mut AiRunReport, usage: Option<providers::Usage>) {
+    if let Some(usage) = usage {
+        report.total_prompt_tokens += usage.prompt_tokens;
+        report.total_completion_tokens += usage.completion_tokens;
+    }
+}
+
+/// Splits a `1. ...` / `2. ...` reply into exactly `count` texts, or `None`
+/// when numbering or line count do not match.
+fn parse_numbered(content: &str, count: usize) -> Option<Vec<String>> {
+    let lines: Vec<&str> = content.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    if lines.len() != count {
+        return None;
+    }
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(n, line)| {
+            let rest = line.strip_prefix(&format!("{}.", n + 1))?;
+            Some(rest.trim().to_string())
+        })
+        .collect()
+}
+
+/// Maps a JSON-mode batch reply to lines 1..=`count` by `id`. Accepts a bare
+/// array or an object holding one (`{"translations": [...]}`, as JSON mode
+/// requires an object); ids may be numbers or numeric strings. Lines the
+/// reply does not cover are `None`.
+fn parse_json_batch(content: &str, count: usize) -> Option<Vec<Option<String>>> {
+    let trimmed = content.trim();
+    let trimmed = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|s| s.strip_suffix("```"))
+        .unwrap_or(trimmed);
+
+    let value: serde_json::Value = serde_json::from_str(trimmed).ok()?;
+    let items = match &value {
+        serde_json::Value::Array(a) => a,
+        serde_json::Value::Object(o) => match o.get("translations").and_then(|v| v.as_array()) {
+            Some(a) => a,
+            None => o.values().find_map(|v| v.as_array())?,
+        },
+        _ => return None,
+    };
+
+    let mut out: Vec<Option<String>> = vec![None; count];
+    for item in items {
+        let id = match item.get("id") {
+            Some(serde_json::Value::Number(n)) => n.as_u64().map(|n| n as usize),
+            Some(serde_json::Value::String(s)) => s.trim().parse().ok(),
+            _ => None,
+        };
+        let translation = item.get("translation").and_then(|v| v.as_str());
+        if let (Some(id), Some(t)) = (id, translation) {
+            if (1..=count).contains(&id) {
+                out[id - 1] = Some(t.trim().to_string());
+            }
+        }
+    }
+    Some(out)
+}
+
+fn translate_single(
+    client: &Client,
+    spec: &ProviderSpec,
+    entries: &mut [CoreEntry],
+    i: usize,
+    cfg: &AiConfig,
+    report: &mut AiRunReport,
+) {
+    let protected = linebreaks::protect(entries[i].original.trim());
+    let prompt = build_prompt(entries, i, &protected, spec.context_format, cfg);
+    let revising = draft(&entries[i], cfg).is_some();
+
+    let e = &mut entries[i];
+    let body = request_body(spec, cfg, &prompt, false);
+
+    let mut ok = false;
+    let mut last_err: Option<String> = None;
+
+    for attempt in 0..MAX_RETRIES {
+        if attempt > 0 {
+            log_warn!(
+                "ai",
+                "retry {} for {}: {}",
+                attempt,
+                e.entry_id,
+                last_err.as_deref().unwrap_or("")
+            );
+        }
+        let started = Instant::now();
+        let res = match spec.request_format {
+            RequestFormat::Openai => send(client, spec, &body, cfg, &e.entry_id),
+            RequestFormat::Mock => mock_response(&protected.text, cfg),
+        };
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        report.total_ms += elapsed_ms;
+        audit(cfg, std::slice::from_ref(&e.entry_id), attempt, elapsed_ms, &prompt, &res);
+
+        match res {
+            Ok((status, is_sse, text)) => {
+                if !status.is_success() {
+                    last_err = Some(extract_error_message(status, &text));
+                    if should_retry_http(status) && attempt + 1 < MAX_RETRIES {
+                        thread::sleep(backoff(attempt, cfg.max_backoff_ms));
+                        continue;
+                    } else {
+                        break;
                     }
+                }
+
+                let parsed = parse_reply(spec, is_sse, &text);
 
-                    let v: Result<serde_json::Value, _> = serde_json::from_str(&text);
-                    match v {
-                        Ok(json) => {
-                            if let Some(t) = json
-                                .get("choices")
-                                .and_then(|c| c.get(0))
-                                .and_then(|c| c.get("message"))
-                                .and_then(|m| m.get("content"))
-                                .and_then(|c| c.as_str())
-                            {
-                                e.translation = t.trim().to_string();
-                                e.status = EntryStatus::Translated;
-
-                                report.succeeded += 1;
-                                report.items.push(AiItemResult {
-                                    entry_id: e.entry_id.clone(),
-                                    ok: true,
-                                    error: None,
-                                });
-
-                                ok = true;
-                                break;
-                            } else {
-                                last_err = Some(
-                                    "Invalid AI response: missing choices[0].message.content"
-                                        .into(),
-                                );
-                                if attempt + 1 < MAX_RETRIES {
-                                    thread::sleep(backoff(attempt));
-                                    continue;
-                                }
-                            }
+                // Billed even when the reply is later rejected.
+                if let Ok((_, usage)) = &parsed {
+                    add_usage(report, *usage);
+                }
+
+                let restored = parsed.and_then(|(t, _)| {
+                    let cleaned = cleanup::apply(&t, &protected.text, cfg.cleanup);
+                    linebreaks::restore(&cleaned, &protected.tokens, cfg.allow_break_reposition)
+                });
+
+                match restored {
+                    Ok(t) => {
+                        e.set_translation(t);
+                        e.status = EntryStatus::Translated;
+
+                        report.succeeded += 1;
+                        if revising {
+                            report.revised += 1;
                         }
-                        Err(_) => {
-                            last_err = Some("Invalid JSON from AI".into());
-                            if attempt + 1 < MAX_RETRIES {
-                                thread::sleep(backoff(attempt));
-                                continue;
-                            }
+                        report.consecutive_failures = 0;
+                        report.items.push(AiItemResult {
+                            index: i,
+                            entry_id: e.entry_id.clone(),
+                            ok: true,
+                            error: None,
+                        });
+
+                        ok = true;
+                        break;
+                    }
+                    Err(msg) => {
+                        // The same request would be cut off again.
+                        let truncated = msg.starts_with(providers::TRUNCATED);
+                        last_err = Some(msg);
+                        if truncated {
+                            break;
+                        }
+                        if attempt + 1 < MAX_RETRIES {
+                            thread::sleep(backoff(attempt, cfg.max_backoff_ms));
+                            continue;
                         }
                     }
                 }
-                Err(err) => {
-                    last_err = Some(err.to_string());
-                    if attempt + 1 < MAX_RETRIES {
-                        thread::sleep(backoff(attempt));
-                        continue;
-                    }
+            }
+            Err(err) => {
+                last_err = Some(err);
+                if attempt + 1 < MAX_RETRIES {
+                    thread::sleep(backoff(attempt, cfg.max_backoff_ms));
+                    continue;
                 }
             }
         }
+    }
 
-        if !ok {
-            report.failed += 1;
-            report.items.push(AiItemResult {
-                entry_id: e.entry_id.clone(),
-                ok: false,
-                error: last_err,
-            });
+    if !ok {
+        report.failed += 1;
+        report.consecutive_failures += 1;
+        report.items.push(AiItemResult {
+            index: i,
+            entry_id: e.entry_id.clone(),
+            ok: false,
+            error: last_err,
+        });
+    }
+}
+
+fn audit(
+    cfg: &AiConfig,
+    entry_ids: &[String],
+    attempt: usize,
+    elapsed_ms: u64,
+    prompt: &str,
+    result: &Result<(StatusCode, bool, String), String>,
+) {
+    if let Some(log) = &cfg.audit {
+        let exchange = Exchange {
+            provider: cfg.provider,
+            model: cfg.model,
+            entry_ids,
+            attempt,
+            elapsed_ms,
+            prompt,
+            result,
+        };
+        log.record(&exchange, cfg.api_key);
+    }
+}
+
+/// Headers that carry the API key.
+const AUTH_HEADERS: &[&str] = &["authorization", "x-api-key", "api-key"];
+
+/// Checks the payload's extra `headers` and turns them into a header map.
+/// Names and values must be valid HTTP; auth headers are refused unless
+/// `allow_auth_override`, so a stray entry cannot swap out the key.
+pub fn request_headers(headers: &BTreeMap<String, String>, allow_auth_override: bool) -> Result<HeaderMap, String> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let header = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| format!("invalid header name: {name:?}"))?;
+        if !allow_auth_override && AUTH_HEADERS.contains(&header.as_str()) {
+            return Err(format!("header {name} would replace the API key; set allow_auth_override to send it"));
         }
+        let value = HeaderValue::from_str(value).map_err(|_| format!("invalid value for header {name}"))?;
+        map.insert(header, value);
     }
+    Ok(map)
+}
+
+/// POSTs `body` and returns the status, whether the reply is an event
+/// stream, and the raw body.
+fn send(
+    client: &Client,
+    spec: &ProviderSpec,
+    body: &serde_json::Value,
+    cfg: &AiConfig,
+    entry_id: &str,
+) -> Result<(StatusCode, bool, String), String> {
+    log_debug!("ai", "POST {} for {}", spec.endpoint, entry_id);
+
+    let req = spec
+        .headers
+        .iter()
+        .fold(client.post(&spec.endpoint).json(body), |req, (name, value)| req.header(name, value));
+    let req = match spec.auth_style {
+        AuthStyle::Bearer => req.bearer_auth(cfg.api_key),
+        AuthStyle::XApiKey => req.header("x-api-key", cfg.api_key),
+        AuthStyle::None => req,
+    };
+    let req = req.headers(cfg.headers.clone());
+
+    let resp = req.send().map_err(|e| e.to_string())?;
+    let status = resp.status();
+    let is_sse = resp
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("text/event-stream"));
+    let text = resp.text().map_err(|e| e.to_string())?;
+
+    Ok((status, is_sse, text))
+}
+
+/// Stands in for `send` with the `mock` provider: answers `[target] text` per
+/// line in chat-completions shape after `latency_ms`, or a 503 at `failure_rate`.
+fn mock_response(text: &str, cfg: &AiConfig) -> Result<(StatusCode, bool, String), String> {
+    if cfg.mock.latency_ms > 0 {
+        thread::sleep(Duration::from_millis(cfg.mock.latency_ms));
+    }
+
+    if cfg.mock.failure_rate > 0.0 && thread_rng().gen::<f64>() < cfg.mock.failure_rate {
+        let body = json!({ "error": { "message": "mock failure" } });
+        return Ok((StatusCode::SERVICE_UNAVAILABLE, false, body.to_string()));
+    }
+
+    // JSON-mode batches (`[{id, text}]`) are answered as JSON; numbered
+    // lines (joint batches) keep their number in front.
+    let json_items = match serde_json::from_str::<serde_json::Value>(text.trim()) {
+        Ok(serde_json::Value::Array(items)) if !items.is_empty() && items.iter().all(|i| i.get("id").is_some()) => {
+            Some(items)
+        }
+        _ => None,
+    };
+    let content = match json_items {
+        Some(items) => {
+            let translations: Vec<serde_json::Value> = items
+                .iter()
+                .map(|item| {
+                    let source = item.get("text").and_then(|v| v.as_str()).unwrap_or("");
+                    json!({ "id": item.get("id"), "translation": format!("[{}] {}", cfg.target_lang, source) })
+                })
+                .collect();
+            json!({ "translations": translations }).to_string()
+        }
+        None => {
+            let lines: Vec<String> = text
+                .lines()
+                .map(|line| match line.split_once(". ") {
+                    Some((n, rest)) if n.chars().all(|c| c.is_ascii_digit()) => {
+                        format!("{n}. [{}] {rest}", cfg.target_lang)
+                    }
+                    _ => format!("[{}] {}", cfg.target_lang, line),
+                })
+                .collect();
+            lines.join("\n")
+        }
+    };
+    let body = json!({
+        "choices": [{ "message": { "content": content } }],
+        "usage": {
+            "prompt_tokens": tokens::heuristic(text),
+            "completion_tokens": tokens::heuristic(&content),
+        }
+    });
+    Ok((StatusCode::OK, false, body.to_string()))
 }
 
 fn should_retry_http(status: StatusCode) -> bool {
@@ -213,8 +1077,8 @@ fn extract_error_message(status: StatusCode, body_text: &str) -> String {
     }
 
     let trimmed = body_text.trim();
-    let snippet = if trimmed.len() > 400 {
-        format!("{}...", &trimmed[..400])
+    let snippet = if trimmed.len() > MAX_ERROR_SNIPPET {
+        format!("{}...", text::truncate_bytes(trimmed, MAX_ERROR_SNIPPET))
     } else {
         trimmed.to_string()
     };
@@ -222,7 +1086,61 @@ fn extract_error_message(status: StatusCode, body_text: &str) -> String {
     format!("HTTP {}: {}", status.as_u16(), snippet)
 }
 
-fn build_prompt(entry: &CoreEntry, cfg: &AiConfig) -> String {
+/// Collects up to `count` translatable originals preceding `idx`, oldest first.
+fn context_before(entries: &[CoreEntry], idx: usize, count: usize) -> Vec<&CoreEntry> {
+    let mut ctx: Vec<&CoreEntry> = entries[..idx]
+        .iter()
+        .rev()
+        .filter(|e| e.is_translatable && !e.original.trim().is_empty())
+        .take(count)
+        .collect();
+    ctx.reverse();
+    ctx
+}
+
+/// The last `count` translated entries before `idx`, oldest first.
+fn style_reference(entries: &[CoreEntry], idx: usize, count: usize) -> Vec<&CoreEntry> {
+    let mut refs: Vec<&CoreEntry> = entries[..idx]
+        .iter()
+        .rev()
+        .filter(|e| e.is_translatable && !e.translation.trim().is_empty())
+        .take(count)
+        .collect();
+    refs.reverse();
+    refs
+}
+
+fn push_style_reference(p: &mut String, reference: &[&CoreEntry]) {
+    if reference.is_empty() {
+        return;
+    }
+    p.push_str("Style reference (earlier lines already translated; keep tone and names consistent, do not translate):\n");
+    for r in reference {
+        p.push_str(&format!("{} => {}\n", r.original.trim(), r.translation.trim()));
+    }
+}
+
+fn push_glossary(p: &mut String, terms: &[&GlossaryTerm]) {
+    if terms.is_empty() {
+        return;
+    }
+    p.push_str("Glossary (always translate these terms this way):\n");
+    for t in terms {
+        match t.note.trim() {
+            "" => p.push_str(&format!("{} => {}\n", t.source.trim(), t.target.trim())),
+            note => p.push_str(&format!("{} => {} ({})\n", t.source.trim(), t.target.trim(), note)),
+        }
+    }
+}
+
+fn build_prompt(
+    entries: &[CoreEntry],
+    idx: usize,
+    protected: &linebreaks::Protected,
+    context_format: ContextFormat,
+    cfg: &AiConfig,
+) -> String {
+    let entry = &entries[idx];
     let mut p = String::new();
 
     p.push_str(&format!(
@@ -230,14 +1148,502 @@ fn build_prompt(entry: &CoreEntry, cfg: &AiConfig) -> String {
         cfg.source_lang, cfg.target_lang
     ));
 
-    if let Some(speaker) = &entry.speaker {
+    push_style_reference(&mut p, &style_reference(entries, idx, cfg.style_reference_lines));
+
+    let context = context_before(entries, idx, cfg.context_lines);
+    if !context.is_empty() {
+        p.push_str(match context_format {
+            ContextFormat::Labeled => "Context (previous lines, do not translate):\n",
+            ContextFormat::Xml => "Previous lines for context only, do not translate them:\n<context>\n",
+        });
+        push_context_lines(&mut p, &context);
+        if context_format == ContextFormat::Xml {
+            p.push_str("</context>\n");
+        }
+        p.push_str("Translate only the text below and reply with its translation alone.\n");
+    }
+
+    if entry.speakers.len() > 1 {
+        p.push_str(&format!("Speakers (together): {}\n", entry.speakers.join(", ")));
+    } else if let Some(speaker) = &entry.speaker {
         if !speaker.trim().is_empty() {
             p.push_str(&format!("Speaker: {}\n", speaker.trim()));
         }
     }
 
+    push_glossary(&mut p, &glossary::terms_for(&cfg.glossary, entry));
+
+    if let Some(instruction) = register_instruction(entry.register.unwrap_or(cfg.register), cfg.target_lang) {
+        p.push_str(instruction);
+        p.push('\n');
+    }
+
+    if let Some(instruction) = linebreaks::instruction(protected.tokens.len(), cfg.allow_break_reposition) {
+        p.push_str(&instruction);
+        p.push('\n');
+    }
+
+    if let Some(hint) = prompt_hint(entry) {
+        p.push_str(&format!("Note for this line: {hint}\n"));
+    }
+
     p.push_str("Text:\n");
-    p.push_str(entry.original.trim());
+    p.push_str(&protected.text);
+
+    if let Some(current) = draft(entry, cfg) {
+        p.push_str("\nCurrent translation (improve it rather than starting over; reply with the improved translation alone):\n");
+        p.push_str(&linebreaks::protect(current).text);
+    }
 
     p
 }
+
+/// One `speaker: original` (or bare original) line per context entry.
+fn push_context_lines(p: &mut String, context: &[&CoreEntry]) {
+    for c in context {
+        match c.speaker.as_deref().map(str::trim) {
+            Some(s) if !s.is_empty() => p.push_str(&format!("{}: {}\n", s, c.original.trim())),
+            _ => p.push_str(&format!("{}\n", c.original.trim())),
+        }
+    }
+}
+
+fn build_batch_prompt(
+    lines: &str,
+    json_reply: bool,
+    protected: &[linebreaks::Protected],
+    reference: &[&CoreEntry],
+    context: &[&CoreEntry],
+    terms: &[&GlossaryTerm],
+    cfg: &AiConfig,
+) -> String {
+    let mut p = String::new();
+    let count = protected.len();
+
+    if json_reply {
+        p.push_str(&format!(
+            "Translate the \"text\" of each item below from {} to {}.\n",
+            cfg.source_lang, cfg.target_lang
+        ));
+        p.push_str(&format!(
+            "Reply with a JSON object {{\"translations\": [{{\"id\": <id>, \"translation\": \"...\"}}]}} covering all {count} ids, and nothing else.\n"
+        ));
+    } else {
+        p.push_str(&format!(
+            "Translate each numbered line from {} to {}.\n",
+            cfg.source_lang, cfg.target_lang
+        ));
+        p.push_str(&format!(
+            "Reply with exactly {count} lines, each starting with its number as \"n. \", and nothing else.\n"
+        ));
+    }
+
+    push_style_reference(&mut p, reference);
+
+    if !context.is_empty() {
+        p.push_str("Context (lines before the first one, do not translate):\n");
+        push_context_lines(&mut p, context);
+    }
+
+    push_glossary(&mut p, terms);
+
+    if let Some(instruction) = register_instruction(cfg.register, cfg.target_lang) {
+        p.push_str(instruction);
+        p.push('\n');
+    }
+
+    let breaks: usize = protected.iter().map(|pr| pr.tokens.len()).sum();
+    if let Some(instruction) = linebreaks::instruction(breaks, cfg.allow_break_reposition) {
+        p.push_str(&instruction);
+        p.push_str(" Markers belong to their own line.\n");
+    }
+
+    p.push_str("Lines:\n");
+    p.push_str(lines);
+
+    p
+}
+
+fn register_instruction(register: Register, target_lang: &str) -> Option<&'static str> {
+    let pt_br = target_lang.eq_ignore_ascii_case("pt-BR") || target_lang.eq_ignore_ascii_case("pt_BR");
+
+    match (register, pt_br) {
+        (Register::Auto, _) => None,
+        (Register::Formal, true) => Some(
+            "Register: formal. Address people with \"o senhor\"/\"a senhora\" or a polite \"você\" and keep verb forms formal.",
+        ),
+        (Register::Casual, true) => Some(
+            "Register: casual. Use \"você\" or \"tu\" with relaxed, colloquial verb forms.",
+        ),
+        (Register::Formal, false) => Some("Register: formal. Keep the polite, respectful tone of the source."),
+        (Register::Casual, false) => Some("Register: casual. Use a relaxed, informal tone."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    fn line(entry_id: &str, original: &str) -> CoreEntry {
+        serde_json::from_value(json!({ "entry_id": entry_id, "original": original, "is_translatable": true })).unwrap()
+    }
+
+    fn prompt(entries: &[CoreEntry], idx: usize, cfg: &AiConfig) -> String {
+        let protected = linebreaks::protect(entries[idx].original.trim());
+        build_prompt(entries, idx, &protected, ContextFormat::Labeled, cfg)
+    }
+
+    #[test]
+    fn prompt_carries_the_preceding_translatable_lines() {
+        let mut command = line("3-raw", "");
+        command.is_translatable = false;
+        command.raw_line = Some("@wait time=10".into());
+        let mut spoken = line("4-text", "二");
+        spoken.speaker = Some("Ana".into());
+        let entries = vec![line("1-text", "零"), line("2-text", "一"), command, spoken, line("5-text", "三")];
+
+        let p = prompt(&entries, 4, &AiConfig::mock());
+        assert!(p.contains("Context (previous lines, do not translate):\n一\nAna: 二\n"), "{p}");
+        assert!(!p.contains("零") && !p.contains("@wait"));
+        assert!(p.ends_with("Text:\n三"));
+
+        let p = prompt(&entries, 4, &AiConfig { context_lines: 0, ..AiConfig::mock() });
+        assert!(!p.contains("Context"));
+    }
+
+    #[test]
+    fn only_the_requested_lines_are_translated() {
+        let mut entries = vec![line("1-text", "一"), line("2-text", "二"), line("3-text", "三")];
+        let report = translate_indices(&mut entries, &[1], AiConfig::mock()).unwrap();
+
+        assert_eq!(report.succeeded, 1);
+        let translations: Vec<&str> = entries.iter().map(|e| e.translation.as_str()).collect();
+        assert_eq!(translations, ["", "[pt-BR] 二", ""]);
+    }
+
+    #[test]
+    fn identical_originals_are_requested_once() {
+        let mut entries = vec![line("1-text", "おはよう"), line("2-text", " おはよう "), line("3-text", "おはよう")];
+        let report = translate_entries(&mut entries, AiConfig::mock(), None).unwrap();
+
+        assert_eq!((report.succeeded, report.deduped, report.failed), (1, 2, 0));
+        assert!(entries.iter().all(|e| e.translation == "[pt-BR] おはよう" && e.status == EntryStatus::Translated));
+    }
+
+    #[test]
+    fn items_follow_input_order() {
+        let mut entries = vec![line("1-text", "はい"), line("2-text", "!?"), line("3-text", "いいえ"), line("4-text", "はい")];
+        let report = translate_entries(&mut entries, AiConfig::mock(), None).unwrap();
+
+        let order: Vec<usize> = report.items.iter().map(|item| item.index).collect();
+        assert_eq!(order, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn register_comes_from_the_entry_before_the_run() {
+        assert_eq!(register_instruction(Register::Auto, "pt-BR"), None);
+        assert!(register_instruction(Register::Formal, "pt-BR").unwrap().contains("o senhor"));
+        assert!(register_instruction(Register::Casual, "en").unwrap().contains("informal"));
+
+        let mut entry = line("1-text", "行こう");
+        let formal = AiConfig { register: Register::Formal, ..AiConfig::mock() };
+        assert!(prompt(std::slice::from_ref(&entry), 0, &formal).contains("Register: formal"));
+
+        entry.register = Some(Register::Casual);
+        let p = prompt(std::slice::from_ref(&entry), 0, &formal);
+        assert!(p.contains("Register: casual") && !p.contains("Register: formal"));
+    }
+
+    #[test]
+    fn error_messages_are_read_or_cut_on_a_char_boundary() {
+        assert_eq!(
+            extract_error_message(StatusCode::TOO_MANY_REQUESTS, r#"{"error":{"message":"slow down"}}"#),
+            "HTTP 429: slow down"
+        );
+
+        let body = "エラー".repeat(100);
+        let msg = extract_error_message(StatusCode::BAD_GATEWAY, &body);
+        let snippet = msg.strip_prefix("HTTP 502: ").unwrap().strip_suffix("...").unwrap();
+        assert!(snippet.len() <= MAX_ERROR_SNIPPET && body.starts_with(snippet));
+    }
+
+    #[test]
+    fn filter_leaves_other_entries_alone() {
+        let mut spoken = line("2-text", "いいえ");
+        spoken.speaker = Some("Ana".into());
+        let mut entries = vec![line("1-text", "はい"), spoken];
+        let only_ana = EntryQuery { speaker: Some("Ana".into()), ..EntryQuery::default() };
+
+        let report = translate_entries(&mut entries, AiConfig::mock(), Some(&only_ana)).unwrap();
+        assert_eq!((report.succeeded, report.filtered_out), (1, 1));
+        assert_eq!(entries[0].translation, "");
+        assert_eq!(entries[1].translation, "[pt-BR] いいえ");
+    }
+
+    #[test]
+    fn numbered_replies_must_cover_every_line() {
+        assert_eq!(parse_numbered("1. um\n\n2. dois\n", 2), Some(vec!["um".to_string(), "dois".to_string()]));
+        assert_eq!(parse_numbered("1. um\n2. dois", 3), None);
+        assert_eq!(parse_numbered("1. um\n3. dois", 2), None);
+    }
+
+    #[test]
+    fn batched_runs_fall_back_to_single_requests() {
+        let mut hinted = line("3-text", "三");
+        hinted.prompt_hint = Some("shout it".into());
+        let mut entries = vec![line("1-text", "一"), line("2-text", "二"), hinted, line("4-text", "四\n五")];
+        let batched = AiConfig { batch_requests: true, ..AiConfig::mock() };
+
+        let report = translate_entries(&mut entries, batched, None).unwrap();
+        assert_eq!(report.succeeded, 4);
+        let translations: Vec<&str> = entries.iter().map(|e| e.translation.as_str()).collect();
+        assert_eq!(translations, ["[pt-BR] 一", "[pt-BR] 二", "[pt-BR] 三", "[pt-BR] 四\n[pt-BR] 五"]);
+    }
+
+    #[test]
+    fn batch_prompt_lists_every_line() {
+        let protected = vec![linebreaks::protect("一"), linebreaks::protect("二")];
+        let p = build_batch_prompt("1. 一\n2. 二\n", false, &protected, &[], &[], &[], &AiConfig::mock());
+        assert!(p.contains("Reply with exactly 2 lines"));
+        assert!(p.ends_with("Lines:\n1. 一\n2. 二\n"));
+    }
+
+    #[test]
+    fn every_caller_shares_one_client() {
+        let first = http_client().unwrap();
+        let second = thread::spawn(|| http_client().unwrap()).join().unwrap();
+        assert!(std::ptr::eq(first, second));
+    }
+
+    #[test]
+    fn consecutive_failures_open_the_circuit_and_keep_the_checkpoint() {
+        let dir = std::env::temp_dir().join(format!("sekai-core-ai-circuit-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir_str = dir.to_str().unwrap();
+        let mut entries: Vec<CoreEntry> = (1..=5).map(|n| line(&format!("{n}-text"), &format!("行{n}"))).collect();
+        let breaker = AiConfig {
+            max_consecutive_failures: 2,
+            checkpoint_dir: Some(dir_str),
+            mock: MockOptions { latency_ms: 0, failure_rate: 1.0 },
+            ..AiConfig::mock()
+        };
+
+        let report = translate_entries(&mut entries, breaker, None).unwrap();
+        let abort = report.aborted.unwrap();
+        assert_eq!(abort.code, "CIRCUIT_OPEN");
+        assert_eq!((report.failed, abort.skipped), (2, 3));
+        assert!(report.items.iter().all(|item| !item.ok));
+
+        let cp = checkpoint::load(dir_str).unwrap().unwrap();
+        assert_eq!(cp.pending.len(), 5);
+    }
+
+    #[test]
+    fn style_reference_shows_the_latest_translated_lines() {
+        let mut entries = vec![line("1-text", "一"), line("2-text", "二"), line("3-text", "三"), line("4-text", "四")];
+        entries[0].translation = "um".into();
+        entries[1].translation = "dois".into();
+
+        let p = prompt(&entries, 3, &AiConfig { style_reference_lines: 1, ..AiConfig::mock() });
+        assert!(p.contains("do not translate):\n二 => dois\n"), "{p}");
+        assert!(!p.contains("um"));
+        assert!(!prompt(&entries, 3, &AiConfig::mock()).contains("Style reference"));
+    }
+
+    #[test]
+    fn backoff_never_exceeds_the_ceiling() {
+        assert_eq!(backoff(100, 5_000), Duration::from_millis(5_000));
+        assert!(backoff(0, DEFAULT_MAX_BACKOFF_MS) >= Duration::from_millis(BASE_DELAY_MS));
+        assert!(backoff(0, DEFAULT_MAX_BACKOFF_MS) < Duration::from_millis(BASE_DELAY_MS + BASE_DELAY_MS / 10));
+        assert_eq!(backoff(3, 0), Duration::ZERO);
+    }
+
+    #[test]
+    fn context_block_follows_the_provider_format() {
+        let entries = vec![line("1-text", "一"), line("2-text", "二")];
+        let protected = linebreaks::protect("二");
+
+        let p = build_prompt(&entries, 1, &protected, ContextFormat::Xml, &AiConfig::mock());
+        assert!(p.contains("<context>\n一\n</context>\n"), "{p}");
+        assert!(!p.contains("Context (previous lines"));
+
+        let p = build_prompt(&entries, 1, &protected, ContextFormat::Labeled, &AiConfig::mock());
+        assert!(p.contains("Context (previous lines, do not translate):\n一\n") && !p.contains("<context>"));
+    }
+
+    #[test]
+    fn trivial_lines_are_copied_not_sent() {
+        let mut entries = vec![line("1-text", "……"), line("2-text", "はい")];
+        let report = translate_entries(&mut entries, AiConfig::mock(), None).unwrap();
+
+        assert_eq!((report.trivial, report.succeeded), (1, 1));
+        assert_eq!(entries[0].translation, "……");
+        assert_eq!(entries[1].translation, "[pt-BR] はい");
+        assert_eq!(report.total_prompt_tokens, tokens::heuristic("はい") as u64);
+    }
+
+    #[test]
+    fn revision_sends_the_draft_and_is_counted() {
+        let revise = AiConfig { revise: true, ..AiConfig::mock() };
+        let mut entries = vec![line("1-text", "ありがとう"), line("2-text", "ありがとう")];
+        entries[0].translation = "obrigada".into();
+
+        let p = prompt(&entries, 0, &revise);
+        assert!(p.contains("Current translation (improve it"));
+        assert!(p.ends_with("obrigada"));
+        assert!(!prompt(&entries, 0, &AiConfig::mock()).contains("obrigada"));
+
+        let report = translate_entries(&mut entries, revise, None).unwrap();
+        assert_eq!((report.succeeded, report.revised, report.deduped), (2, 1, 0));
+    }
+
+    #[test]
+    fn nontext_lines_are_copied_not_sent() {
+        let mut entries = vec![line("1-text", "https://example.com/a"), line("2-text", "はい")];
+        let report = translate_entries(&mut entries, AiConfig::mock(), None).unwrap();
+
+        assert_eq!((report.skipped_nontext, report.succeeded), (1, 1));
+        assert_eq!(entries[0].translation, "https://example.com/a");
+    }
+
+    #[test]
+    fn scoped_terms_split_duplicates() {
+        let mut by_ana = line("1-text", "先輩");
+        by_ana.speaker = Some("Ana".into());
+        let mut entries = vec![by_ana, line("2-text", "先輩")];
+        let term = GlossaryTerm {
+            source: "先輩".into(),
+            target: "veterana".into(),
+            note: String::new(),
+            speaker: Some("Ana".into()),
+        };
+        let glossary = AiConfig { glossary: vec![term], ..AiConfig::mock() };
+
+        assert!(prompt(&entries, 0, &glossary).contains("先輩 => veterana"));
+        assert!(!prompt(&entries, 1, &glossary).contains("veterana"));
+        let report = translate_entries(&mut entries, glossary, None).unwrap();
+        assert_eq!((report.succeeded, report.deduped), (2, 0));
+    }
+
+    #[test]
+    fn json_batch_replies_are_mapped_by_id() {
+        let reply = "```json\n{\"translations\": [{\"id\": \"2\", \"translation\": \" dois \"}, {\"id\": 9, \"translation\": \"x\"}]}\n```";
+        assert_eq!(parse_json_batch(reply, 2), Some(vec![None, Some("dois".to_string())]));
+        assert_eq!(parse_json_batch("[{\"id\": 1, \"translation\": \"um\"}]", 1), Some(vec![Some("um".to_string())]));
+        assert_eq!(parse_json_batch("not json", 1), None);
+
+        let protected = vec![linebreaks::protect("一"), linebreaks::protect("二")];
+        let p = build_batch_prompt("[]\n", true, &protected, &[], &[], &[], &AiConfig::mock());
+        assert!(p.contains("covering all 2 ids"));
+
+        let mut entries = vec![line("1-text", "一"), line("2-text", "二")];
+        let batched = AiConfig { batch_requests: true, json_batches: true, ..AiConfig::mock() };
+        let report = translate_entries(&mut entries, batched, None).unwrap();
+        assert_eq!(report.succeeded, 2);
+        assert_eq!(entries[1].translation, "[pt-BR] 二");
+    }
+
+    #[test]
+    fn hints_reach_only_their_line_and_split_duplicates() {
+        let mut hinted = line("1-text", "すごい");
+        hinted.prompt_hint = Some("sarcastic".into());
+        let mut entries = vec![hinted, line("2-text", "すごい")];
+
+        assert!(prompt(&entries, 0, &AiConfig::mock()).contains("Note for this line: sarcastic"));
+        assert!(!prompt(&entries, 1, &AiConfig::mock()).contains("sarcastic"));
+        let report = translate_entries(&mut entries, AiConfig::mock(), None).unwrap();
+        assert_eq!((report.succeeded, report.deduped), (2, 0));
+    }
+
+    #[test]
+    fn extra_headers_are_checked() {
+        let ok = BTreeMap::from([("X-Trace".to_string(), "abc".to_string())]);
+        assert_eq!(request_headers(&ok, false).unwrap()["x-trace"], "abc");
+
+        let auth = BTreeMap::from([("Authorization".to_string(), "Bearer other".to_string())]);
+        assert!(request_headers(&auth, false).is_err());
+        assert!(request_headers(&auth, true).is_ok());
+
+        let bad_name = BTreeMap::from([("bad header".to_string(), "x".to_string())]);
+        assert!(request_headers(&bad_name, false).is_err());
+        let bad_value = BTreeMap::from([("X-Trace".to_string(), "a\nb".to_string())]);
+        assert!(request_headers(&bad_value, false).is_err());
+    }
+
+    #[test]
+    fn extra_headers_override_the_provider_ones() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            let body = r#"{"choices":[{"message":{"content":"ok"}}]}"#;
+            let reply = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(reply.as_bytes()).unwrap();
+            String::from_utf8_lossy(&request).to_lowercase()
+        });
+
+        let spec = ProviderSpec {
+            endpoint: format!("http://{addr}/v1/chat/completions"),
+            auth_style: AuthStyle::Bearer,
+            request_format: RequestFormat::Openai,
+            response_path: "choices.0.message.content".into(),
+            stream: false,
+            default_model: None,
+            models: Vec::new(),
+            headers: BTreeMap::from([("X-Title".to_string(), "Sekai".to_string())]),
+            context_format: ContextFormat::Labeled,
+            json_mode: false,
+        };
+        let extra = BTreeMap::from([
+            ("x-title".to_string(), "Override".to_string()),
+            ("X-Trace".to_string(), "abc".to_string()),
+        ]);
+        let cfg = AiConfig { api_key: "sk-test", headers: request_headers(&extra, false).unwrap(), ..AiConfig::mock() };
+
+        let (status, is_sse, body) = send(http_client().unwrap(), &spec, &json!({}), &cfg, "1-text").unwrap();
+        assert!(status.is_success() && !is_sse);
+        assert!(body.contains("\"ok\""));
+
+        let request = server.join().unwrap();
+        assert!(request.contains("authorization: bearer sk-test"));
+        assert!(request.contains("x-title: override") && !request.contains("sekai"));
+        assert!(request.contains("x-trace: abc"));
+    }
+
+    #[test]
+    fn failure_policy_copies_the_original_but_spares_revisions() {
+        let mut draft = line("2-text", "二");
+        draft.translation = "dois".into();
+        let mut entries = vec![line("1-text", "一"), draft];
+        let policy = AiConfig {
+            on_failure: AiFailurePolicy::CopyOriginal,
+            revise: true,
+            mock: MockOptions { latency_ms: 0, failure_rate: 1.0 },
+            ..AiConfig::mock()
+        };
+
+        let report = translate_entries(&mut entries, policy, None).unwrap();
+        assert_eq!(report.failed, 2);
+        assert_eq!((entries[0].translation.as_str(), entries[0].status), ("一", EntryStatus::InProgress));
+        assert_eq!(entries[1].translation, "dois");
+
+        assert_eq!(AiFailurePolicy::parse("").unwrap(), AiFailurePolicy::Leave);
+        assert_eq!(AiFailurePolicy::parse("mark_untranslated").unwrap(), AiFailurePolicy::MarkUntranslated);
+        assert!(AiFailurePolicy::parse("retry").is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use reqwest::StatusCode;
+use serde::Serialize;
+
+use crate::log_warn;
+use crate::services::fsutil::append_line;
+
+const AUDIT_DIR: &str = "audit";
+const REDACTED: &str = "[redacted]";
+
+/// Per-run JSONL file under `<project>/audit/` recording every AI request
+/// prompt and the raw reply, one line per attempt.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    ts_ms: u64,
+    provider: &'a str,
+    model: &'a str,
+    entry_ids: &'a [String],
+    attempt: usize,
+    elapsed_ms: u64,
+    prompt: &'a str,
+    status: Option<u16>,
+    response: Option<&'a str>,
+    error: Option<&'a str>,
+}
+
+/// One request/reply pair as seen by the AI service.
+pub struct Exchange<'a> {
+    pub provider: &'a str,
+    pub model: &'a str,
+    pub entry_ids: &'a [String],
+    pub attempt: usize,
+    pub elapsed_ms: u64,
+    pub prompt: &'a str,
+    pub result: &'a Result<(StatusCode, bool, String), String>,
+}
+
+impl AuditLog {
+    /// Picks a fresh file for this run; nothing is written until the first
+    /// request.
+    pub fn for_run(project_path: &str) -> Result<Self, String> {
+        let dir = Path::new(project_path);
+        if !dir.is_dir() {
+            return Err("project directory not found".into());
+        }
+        let name = format!("ai-{}.jsonl", now_ms());
+        Ok(Self {
+            path: dir.join(AUDIT_DIR).join(name),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends `exchange`. `api_key` is scrubbed from the line in case a
+    /// provider echoes it back; failures are logged, never fatal to the run.
+    pub fn record(&self, exchange: &Exchange, api_key: &str) {
+        let (status, response, error) = match exchange.result {
+            Ok((status, _, text)) => (Some(status.as_u16()), Some(text.as_str()), None),
+            Err(e) => (None, None, Some(e.as_str())),
+        };
+        let record = Record {
+            ts_ms: now_ms(),
+            provider: exchange.provider,
+            model: exchange.model,
+            entry_ids: exchange.entry_ids,
+            attempt: exchange.attempt,
+            elapsed_ms: exchange.elapsed_ms,
+            prompt: exchange.prompt,
+            status,
+            response,
+            error,
+        };
+
+        let mut line = match serde_json::to_string(&record) {
+            Ok(l) => l,
+            Err(e) => {
+                log_warn!("audit", "failed to serialize record: {e}");
+                return;
+            }
+        };
+        if !api_key.is_empty() {
+            line = line.replace(api_key, REDACTED);
+        }
+
+        if let Err(e) = append_line(&self.path, &line) {
+            log_warn!("audit", "failed to write {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Whether `SEKAI_AI_AUDIT` turns auditing on for every run.
+pub fn enabled_by_env() -> bool {
+    matches!(
+        std::env::var("SEKAI_AI_AUDIT").unwrap_or_default().trim(),
+        "1" | "true" | "on"
+    )
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn records_are_appended_with_the_key_scrubbed() {
+        let dir = std::env::temp_dir().join(format!("sekai-core-audit-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        assert!(AuditLog::for_run(dir.join("missing").to_str().unwrap()).is_err());
+
+        let log = AuditLog::for_run(dir.to_str().unwrap()).unwrap();
+        assert!(!log.path().exists());
+
+        let ids = vec!["1-text".to_string()];
+        let replied = Ok((StatusCode::UNAUTHORIZED, false, "bad key sk-secret".to_string()));
+        let failed = Err("connection reset".to_string());
+        for (attempt, result) in [&replied, &failed].into_iter().enumerate() {
+            let exchange = Exchange {
+                provider: "openai",
+                model: "gpt-4o-mini",
+                entry_ids: &ids,
+                attempt,
+                elapsed_ms: 5,
+                prompt: "Text:\nはい",
+                result,
+            };
+            log.record(&exchange, "sk-secret");
+        }
+
+        let data = fs::read_to_string(log.path()).unwrap();
+        assert!(!data.contains("sk-secret") && data.contains(REDACTED));
+        let lines: Vec<serde_json::Value> = data.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[0]["status"].as_u64(), lines[0]["prompt"].as_str()), (Some(401), Some("Text:\nはい")));
+        assert_eq!((lines[1]["attempt"].as_u64(), lines[1]["error"].as_str()), (Some(1), Some("connection reset")));
+    }
+}
@@ -2,6 +2,9 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AiItemResult {
+    /// Position of the entry in the slice handed to the translator.
+    #[serde(skip)]
+    pub index: usize,
     pub entry_id: String,
     pub ok: bool,
     pub error: Option<String>,
@@ -9,7 +12,54 @@ pub struct AiItemResult {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AiRunReport {
+    /// Model actually requested (the provider default when none was given).
+    #[serde(default)]
+    pub model: String,
     pub succeeded: usize,
     pub failed: usize,
+    /// Entries that reused the result of an identical original in the same run.
+    pub deduped: usize,
+    /// Translatable entries left alone because they did not match the filter.
+    #[serde(default)]
+    pub filtered_out: usize,
+    /// Entries without translatable content, copied from the original.
+    #[serde(default)]
+    pub trivial: usize,
+    /// URLs, paths, version numbers and IDs, copied from the original.
+    #[serde(default)]
+    pub skipped_nontext: usize,
+    /// Successful entries whose existing translation was sent to be improved.
+    #[serde(default)]
+    pub revised: usize,
+    /// Successful entries over their `max_chars`, left `InProgress`.
+    #[serde(default)]
+    pub held_over_length: usize,
+    /// Summed from the provider's `usage`; requests without it add nothing.
+    #[serde(default)]
+    pub total_prompt_tokens: u64,
+    #[serde(default)]
+    pub total_completion_tokens: u64,
+    /// Wall time spent in requests, retries included.
+    #[serde(default)]
+    pub total_ms: u64,
+    /// Request/reply log written for this run, when auditing was on.
+    #[serde(default)]
+    pub audit_file: Option<String>,
+    /// Set when the run stopped early; entries it never reached keep their
+    /// previous state and have no item.
+    #[serde(default)]
+    pub aborted: Option<RunAbort>,
+    /// Entry failures since the last success; feeds the circuit breaker.
+    #[serde(skip)]
+    pub consecutive_failures: usize,
     pub items: Vec<AiItemResult>,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunAbort {
+    /// `CIRCUIT_OPEN` when too many entries failed in a row.
+    pub code: String,
+    pub message: String,
+    /// Entries left untried.
+    pub skipped: usize,
+}
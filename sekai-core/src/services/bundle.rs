@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::log_info;
+use crate::model::entry::CoreEntry;
+use crate::model::glossary::GlossaryTerm;
+use crate::model::project::ProjectInfo;
+use crate::services::translation_memory::model::{DedupStrategy, TMEntry};
+use crate::services::translation_memory::store;
+use crate::services::{glossary, project};
+
+pub const BUNDLE_FORMAT: u32 = 1;
+
+/// Everything a collaborator needs, minus the game files. Paths on the
+/// exporting machine are cleared; `project.json` holds no credentials.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProjectBundle {
+    pub format: u32,
+    pub project: ProjectInfo,
+    #[serde(default)]
+    pub glossary: Vec<GlossaryTerm>,
+    /// TM entries for the project's language pair (all of them when the
+    /// project does not name one).
+    #[serde(default)]
+    pub tm: Vec<TMEntry>,
+    /// Entries per script file, as handed to the export.
+    #[serde(default)]
+    pub files: BTreeMap<String, Vec<CoreEntry>>,
+}
+
+pub fn export_bundle(project_path: &str, files: BTreeMap<String, Vec<CoreEntry>>) -> Result<ProjectBundle, String> {
+    let mut info = project::open_project(project_path.to_string())?;
+    let glossary = glossary::load(project_path)?;
+
+    let (src, tgt) = (info.source_language.clone(), info.target_language.clone());
+    let tm: Vec<TMEntry> = store::peek()
+        .into_iter()
+        .filter(|e| (src.is_empty() || e.source_lang == src) && (tgt.is_empty() || e.target_lang == tgt))
+        .collect();
+
+    info.project_path = String::new();
+    info.root_path = String::new();
+
+    log_info!(
+        "bundle",
+        "exported {}: {} glossary terms, {} tm entries, {} files",
+        info.name,
+        glossary.len(),
+        tm.len(),
+        files.len()
+    );
+
+    Ok(ProjectBundle { format: BUNDLE_FORMAT, project: info, glossary, tm, files })
+}
+
+pub fn read_bundle(path: &Path) -> Result<ProjectBundle, String> {
+    let data = fs::read_to_string(path).map_err(|e| format!("failed to read bundle: {e}"))?;
+    serde_json::from_str(&data).map_err(|e| format!("invalid bundle: {e}"))
+}
+
+/// Creates a new local project from `bundle`, saves its glossary and merges
+/// its TM into the local one. Entries are returned for the caller to store.
+pub fn import_bundle(bundle: ProjectBundle, name: Option<String>, game_root: String) -> Result<ProjectInfo, String> {
+    if bundle.format > BUNDLE_FORMAT {
+        return Err(format!("unsupported bundle format: {}", bundle.format));
+    }
+
+    let mut info = bundle.project;
+    if let Some(n) = name.filter(|n| !n.trim().is_empty()) {
+        info.name = n;
+    }
+
+    let created = project::create_project(
+        info.name.clone(),
+        game_root,
+        info.encoding.clone(),
+        info.engine.clone(),
+        info.parser_id.clone(),
+        info.source_language.clone(),
+        info.target_language.clone(),
+    )?;
+
+    info.project_path = created.project_path;
+    info.root_path = created.root_path;
+    let info = project::save_project(info)?;
+
+    glossary::save(&info.project_path, &bundle.glossary)?;
+
+    if !bundle.tm.is_empty() {
+        store::update_with(DedupStrategy::default(), |tm| tm.extend(bundle.tm))?;
+    }
+
+    log_info!("bundle", "imported {} into {}", info.name, info.project_path);
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::translation_memory::model::TmSource;
+
+    fn tm(target_lang: &str, translation: &str) -> TMEntry {
+        TMEntry {
+            source_lang: "ja".into(),
+            target_lang: target_lang.into(),
+            original: "はい".into(),
+            translation: translation.into(),
+            normalized: String::new(),
+            hash: String::new(),
+            source: TmSource::Human,
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn export_then_import_carries_glossary_tm_and_files() {
+        store::with_temp_tm("bundle-tm", |cwd| {
+            project::with_temp_app_data("bundle-projects", |dir| {
+                let project = project::create_project(
+                    "Source".into(),
+                    dir.to_string_lossy().into(),
+                    "cp932".into(),
+                    "kirikiri".into(),
+                    "kirikiri".into(),
+                    "ja".into(),
+                    "pt".into(),
+                )
+                .unwrap();
+                let term = GlossaryTerm { source: "魔法".into(), target: "magia".into(), note: String::new(), speaker: None };
+                glossary::save(&project.project_path, &[term]).unwrap();
+                store::update_with(DedupStrategy::default(), |t| t.extend([tm("pt", "Sim"), tm("en", "Yes")])).unwrap();
+
+                let entry = serde_json::json!({ "entry_id": "1-text", "original": "はい", "translation": "Sim" });
+                let files = BTreeMap::from([("a.ks".to_string(), vec![serde_json::from_value(entry).unwrap()])]);
+                let bundle = export_bundle(&project.project_path, files).unwrap();
+                assert!(bundle.project.project_path.is_empty() && bundle.project.root_path.is_empty());
+                assert_eq!(bundle.tm.len(), 1);
+
+                let path = dir.join("a.bundle.json");
+                fs::write(&path, serde_json::to_string(&bundle).unwrap()).unwrap();
+                fs::remove_file(cwd.join("translation_memory.json")).unwrap();
+
+                let read = read_bundle(&path).unwrap();
+                assert_eq!(read.files["a.ks"][0].translation, "Sim");
+                let imported = import_bundle(read, Some("Copy".into()), "/games/copy".into()).unwrap();
+                assert_eq!((imported.name.as_str(), imported.encoding.as_str()), ("Copy", "cp932"));
+                assert_eq!(glossary::load(&imported.project_path).unwrap()[0].target, "magia");
+                assert_eq!(store::peek().len(), 1);
+            })
+        });
+    }
+
+    #[test]
+    fn newer_bundle_formats_are_refused() {
+        let bundle = ProjectBundle {
+            format: BUNDLE_FORMAT + 1,
+            project: ProjectInfo::default(),
+            glossary: Vec::new(),
+            tm: Vec::new(),
+            files: BTreeMap::new(),
+        };
+        assert!(import_bundle(bundle, None, String::new()).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::log_info;
+use crate::model::entry::CoreEntry;
+use crate::services::fsutil::write_atomic;
+
+const CHECKPOINT_FILE: &str = "translate_checkpoint.json";
+
+/// Snapshot of an interrupted AI run: every entry as it stood after the last
+/// finished batch, plus the ids that still need a translation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub entries: Vec<CoreEntry>,
+    pub pending: Vec<String>,
+}
+
+pub fn save(project_path: &str, checkpoint: &Checkpoint) -> Result<(), String> {
+    let dir = Path::new(project_path);
+    if !dir.is_dir() {
+        return Err("project directory not found".into());
+    }
+
+    let json = serde_json::to_string(checkpoint).map_err(|e| e.to_string())?;
+    write_atomic(&dir.join(CHECKPOINT_FILE), json.as_bytes())
+        .map_err(|e| format!("failed to write {CHECKPOINT_FILE}: {e}"))
+}
+
+pub fn load(project_path: &str) -> Result<Option<Checkpoint>, String> {
+    let path = Path::new(project_path).join(CHECKPOINT_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let data = fs::read_to_string(&path).map_err(|e| format!("failed to read {CHECKPOINT_FILE}: {e}"))?;
+    serde_json::from_str(&data)
+        .map(Some)
+        .map_err(|e| format!("invalid {CHECKPOINT_FILE}: {e}"))
+}
+
+/// Removes the checkpoint once a run has finished.
+pub fn clear(project_path: &str) -> Result<(), String> {
+    let path = Path::new(project_path).join(CHECKPOINT_FILE);
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| format!("failed to remove {CHECKPOINT_FILE}: {e}"))?;
+        log_info!("checkpoint", "cleared {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoint_round_trips_and_clears() {
+        let dir = std::env::temp_dir().join(format!("sekai-core-checkpoint-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let project = dir.to_str().unwrap();
+        assert!(load(project).unwrap().is_none());
+
+        let entry = serde_json::from_value(serde_json::json!({ "entry_id": "1-text", "original": "はい", "translation": "Sim" }));
+        let checkpoint = Checkpoint { entries: vec![entry.unwrap()], pending: vec!["2-text".into()] };
+        save(project, &checkpoint).unwrap();
+        let loaded = load(project).unwrap().unwrap();
+        assert_eq!(loaded.entries[0].translation, "Sim");
+        assert_eq!(loaded.pending, ["2-text"]);
+
+        clear(project).unwrap();
+        assert!(load(project).unwrap().is_none());
+        assert!(save(dir.join("missing").to_str().unwrap(), &checkpoint).is_err());
+    }
+}
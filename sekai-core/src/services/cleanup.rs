@@ -0,0 +1,109 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::Deserialize;
+
+/// Post-processing applied to raw model output before it becomes a
+/// translation. Each rule is conservative and can be switched off.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct CleanupRules {
+    /// Drop a leading `Translation:`-style label.
+    pub strip_label: bool,
+    /// Remove quotes wrapping the whole reply, unless the original was quoted.
+    pub strip_quotes: bool,
+    /// Drop a trailing `(...)` note, unless the original has parentheses.
+    pub strip_trailing_note: bool,
+}
+
+impl Default for CleanupRules {
+    fn default() -> Self {
+        CleanupRules { strip_label: true, strip_quotes: true, strip_trailing_note: true }
+    }
+}
+
+const QUOTE_PAIRS: &[(char, char)] = &[('"', '"'), ('\'', '\''), ('“', '”'), ('「', '」'), ('『', '』')];
+
+fn label_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^(?i)(?:translation|translated text|tradução|traducao|output)\s*[:：]\s*").unwrap()
+    })
+}
+
+fn trailing_note_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\s+[\(（][^\(\)（）]*[\)）]$").unwrap())
+}
+
+pub fn apply(output: &str, original: &str, rules: CleanupRules) -> String {
+    let mut t = output.trim().to_string();
+
+    if rules.strip_label {
+        t = strip_label(&t);
+    }
+    if rules.strip_trailing_note {
+        t = strip_trailing_note(&t, original);
+    }
+    if rules.strip_quotes {
+        t = strip_quotes(&t, original);
+    }
+
+    t
+}
+
+fn strip_label(text: &str) -> String {
+    label_re().replace(text, "").trim().to_string()
+}
+
+fn strip_quotes(text: &str, original: &str) -> String {
+    let original = original.trim();
+    if QUOTE_PAIRS.iter().any(|&(open, _)| original.starts_with(open)) {
+        return text.to_string();
+    }
+
+    for &(open, close) in QUOTE_PAIRS {
+        if let Some(inner) = text.strip_prefix(open).and_then(|r| r.strip_suffix(close)) {
+            // A lone quote char, or quotes inside, mean this is not a wrapper.
+            if !inner.is_empty() && !inner.contains(open) && !inner.contains(close) {
+                return inner.trim().to_string();
+            }
+        }
+    }
+
+    text.to_string()
+}
+
+fn strip_trailing_note(text: &str, original: &str) -> String {
+    if original.contains(['(', ')', '（', '）']) {
+        return text.to_string();
+    }
+
+    match trailing_note_re().find(text) {
+        Some(m) if m.start() > 0 => text[..m.start()].to_string(),
+        _ => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_quotes_and_trailing_note_are_stripped() {
+        assert_eq!(apply("Translation: \"Bom dia\" (literal: good morning)", "おはよう", CleanupRules::default()), "Bom dia");
+    }
+
+    #[test]
+    fn quotes_and_notes_the_original_had_are_kept() {
+        assert_eq!(apply("「Bom dia」", "「おはよう」", CleanupRules::default()), "「Bom dia」");
+        assert_eq!(apply("Bom dia (risos)", "おはよう（笑）", CleanupRules::default()), "Bom dia (risos)");
+        assert_eq!(apply("\"Sim\" e \"não\"", "はいといいえ", CleanupRules::default()), "\"Sim\" e \"não\"");
+    }
+
+    #[test]
+    fn rules_can_be_switched_off() {
+        let rules = CleanupRules { strip_label: false, strip_quotes: false, strip_trailing_note: false };
+        assert_eq!(apply("  Tradução: \"Oi\"  ", "やあ", rules), "Tradução: \"Oi\"");
+    }
+}
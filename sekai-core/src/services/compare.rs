@@ -0,0 +1,125 @@
+use serde::Serialize;
+
+use crate::model::entry::CoreEntry;
+use crate::services::ai::{self, AiConfig};
+use crate::services::ai_types::AiRunReport;
+
+#[derive(Debug, Serialize)]
+pub struct SideSummary {
+    pub provider: String,
+    pub model: String,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComparedLine {
+    pub entry_id: String,
+    pub original: String,
+    /// `None` when that side failed; see the matching `*_error`.
+    pub a: Option<String>,
+    pub b: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub a_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub b_error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    pub a: SideSummary,
+    pub b: SideSummary,
+    pub lines: Vec<ComparedLine>,
+}
+
+/// Translates the translatable `entries` once with each config, one request
+/// per entry, and lines the results up. `entries` is left as it was, and
+/// neither pass checkpoints or touches the TM.
+pub fn compare(entries: &[CoreEntry], a: AiConfig, b: AiConfig) -> Result<Comparison, String> {
+    let (a_entries, a_summary, a_report) = run(entries, a)?;
+    let (b_entries, b_summary, b_report) = run(entries, b)?;
+
+    let lines = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.is_translatable)
+        .map(|(i, e)| {
+            let (a, a_error) = outcome(&a_entries[i], &a_report, i);
+            let (b, b_error) = outcome(&b_entries[i], &b_report, i);
+            ComparedLine {
+                entry_id: e.entry_id.clone(),
+                original: e.original.clone(),
+                a,
+                b,
+                a_error,
+                b_error,
+            }
+        })
+        .collect();
+
+    Ok(Comparison {
+        a: a_summary,
+        b: b_summary,
+        lines,
+    })
+}
+
+fn run(entries: &[CoreEntry], mut cfg: AiConfig) -> Result<(Vec<CoreEntry>, SideSummary, AiRunReport), String> {
+    cfg.batch_requests = false;
+    cfg.checkpoint_dir = None;
+    let provider = cfg.provider.to_string();
+
+    // Every side starts from the same untranslated sample.
+    let mut copy: Vec<CoreEntry> = entries.to_vec();
+    for e in copy.iter_mut().filter(|e| e.is_translatable) {
+        e.translation.clear();
+    }
+
+    let report = ai::translate_entries(&mut copy, cfg, None)?;
+    let summary = SideSummary {
+        provider,
+        model: report.model.clone(),
+        succeeded: report.succeeded,
+        failed: report.failed,
+        prompt_tokens: report.total_prompt_tokens,
+        completion_tokens: report.total_completion_tokens,
+        total_ms: report.total_ms,
+    };
+    Ok((copy, summary, report))
+}
+
+fn outcome(e: &CoreEntry, report: &AiRunReport, index: usize) -> (Option<String>, Option<String>) {
+    match report.items.iter().find(|item| item.index == index) {
+        Some(item) if item.ok => (Some(e.translation.clone()), None),
+        Some(item) => (None, item.error.clone()),
+        None => (None, Some("not attempted".into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::ai::MockOptions;
+    use serde_json::json;
+
+    #[test]
+    fn both_sides_start_from_the_same_sample() {
+        let entries: Vec<CoreEntry> = serde_json::from_value(json!([
+            {"entry_id": "1-text", "original": "はい", "translation": "Sim", "is_translatable": true},
+            {"entry_id": "2-raw", "original": "@wait", "is_translatable": false},
+        ]))
+        .unwrap();
+        let english = AiConfig { target_lang: "en", ..AiConfig::mock() };
+        let broken = AiConfig { mock: MockOptions { latency_ms: 0, failure_rate: 1.0 }, ..AiConfig::mock() };
+
+        let cmp = compare(&entries, english, broken).unwrap();
+        assert_eq!((cmp.a.succeeded, cmp.b.failed), (1, 1));
+        assert_eq!(cmp.lines.len(), 1);
+        assert_eq!(cmp.lines[0].a.as_deref(), Some("[en] はい"));
+        assert_eq!((cmp.lines[0].b.as_deref(), cmp.lines[0].b_error.as_deref()), (None, Some("HTTP 503: mock failure")));
+        assert_eq!(entries[0].translation, "Sim");
+    }
+}
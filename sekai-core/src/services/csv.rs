@@ -0,0 +1,121 @@
+use crate::services::encoding;
+
+/// Minimal RFC 4180 reader: quoted fields, doubled quotes and embedded
+/// newlines. Each row is returned separately so one malformed row (stray or
+/// unterminated quote) can be skipped without aborting the rest.
+pub fn parse(text: &str) -> Vec<Result<Vec<String>, String>> {
+    let mut rows = Vec::new();
+
+    let mut fields: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut was_quoted = false;
+    let mut malformed = false;
+    let mut row_no = 1usize;
+
+    let mut chars = encoding::strip_bom(text).chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() && !was_quoted => {
+                in_quotes = true;
+                was_quoted = true;
+            }
+            '"' => malformed = true,
+            ',' => {
+                fields.push(std::mem::take(&mut field));
+                was_quoted = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                fields.push(std::mem::take(&mut field));
+                rows.push(finish_row(std::mem::take(&mut fields), malformed, row_no));
+                was_quoted = false;
+                malformed = false;
+                row_no += 1;
+            }
+            _ => {
+                if was_quoted {
+                    malformed = true;
+                }
+                field.push(c);
+            }
+        }
+    }
+
+    if in_quotes {
+        fields.push(field);
+        rows.push(Err(format!("row {row_no}: unterminated quoted field")));
+    } else if !field.is_empty() || !fields.is_empty() || was_quoted {
+        fields.push(field);
+        rows.push(finish_row(fields, malformed, row_no));
+    }
+
+    rows
+}
+
+fn finish_row(fields: Vec<String>, malformed: bool, row_no: usize) -> Result<Vec<String>, String> {
+    if malformed {
+        Err(format!("row {row_no}: stray quote"))
+    } else {
+        Ok(fields)
+    }
+}
+
+pub fn write_row(out: &mut String, fields: &[&str]) {
+    for (i, f) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if f.contains([',', '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&f.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(f);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_fields_keep_commas_quotes_and_newlines() {
+        let rows = parse("\u{feff}source,target\r\n\"a,b\",\"say \"\"hi\"\"\"\n\"two\nlines\",x");
+        let rows: Vec<Vec<String>> = rows.into_iter().map(Result::unwrap).collect();
+        assert_eq!(rows, [vec!["source", "target"], vec!["a,b", "say \"hi\""], vec!["two\nlines", "x"]]);
+    }
+
+    #[test]
+    fn malformed_rows_fail_alone() {
+        let rows = parse("ok,1\nbad\"quote,2\n\"x\"y,3\nfine,4\n\"open,5");
+        assert!(rows[0].is_ok());
+        assert_eq!(rows[1].as_ref().unwrap_err(), "row 2: stray quote");
+        assert!(rows[2].is_err());
+        assert!(rows[3].is_ok());
+        assert_eq!(rows[4].as_ref().unwrap_err(), "row 5: unterminated quoted field");
+    }
+
+    #[test]
+    fn written_rows_parse_back() {
+        let mut out = String::new();
+        write_row(&mut out, &["plain", "a,b", "say \"hi\""]);
+        assert_eq!(out, "plain,\"a,b\",\"say \"\"hi\"\"\"\r\n");
+        assert_eq!(parse(&out)[0].as_ref().unwrap(), &["plain", "a,b", "say \"hi\""]);
+    }
+}
@@ -1,9 +1,14 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 
 use chardetng::EncodingDetector;
 use encoding_rs::Encoding;
 use serde::Serialize;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::log_debug;
 
 #[derive(Debug, Serialize)]
 pub struct EncodingCandidate {
@@ -16,19 +21,58 @@ pub struct EncodingDetectionResult {
     pub best: String,
     pub confidence: f32,
     pub candidates: Vec<EncodingCandidate>,
+    /// Fewer than `MIN_RELIABLE_BYTES` of content: the guess is weak.
+    pub too_short: bool,
+    /// Why the result was decided without sniffing, if it was.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
 }
 
+/// Below this many bytes (BOM excluded) the detector has too little to go on.
+const MIN_RELIABLE_BYTES: usize = 32;
+
 pub fn detect_from_file(path: &Path) -> Result<EncodingDetectionResult, String> {
     let bytes = fs::read(path).map_err(|e| e.to_string())?;
 
-    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+    // Nothing to sniff; UTF-8 reads (and writes) an empty file faithfully.
+    if bytes.is_empty() {
+        return Ok(EncodingDetectionResult {
+            best: "utf-8".into(),
+            confidence: 1.0,
+            candidates: vec![EncodingCandidate {
+                name: "utf-8".into(),
+                confidence: 1.0,
+            }],
+            too_short: false,
+            note: Some("empty file".into()),
+        });
+    }
+
+    if let Some((enc, bom_len)) = Encoding::for_bom(&bytes) {
+        if bytes.len() == bom_len && enc != encoding_rs::UTF_8 {
+            let name = enc.name().to_lowercase();
+            return Ok(EncodingDetectionResult {
+                best: name.clone(),
+                confidence: 1.0,
+                candidates: vec![EncodingCandidate { name, confidence: 1.0 }],
+                too_short: false,
+                note: Some("file holds only a byte order mark".into()),
+            });
+        }
+    }
+
+    if bytes.starts_with(UTF8_BOM) {
+        let bom_only = bytes.len() == UTF8_BOM.len();
+        let confidence = if bom_only { 1.0 } else { 0.99 };
         return Ok(EncodingDetectionResult {
             best: "utf-8-sig".into(),
-            confidence: 0.99,
+            confidence,
+            too_short: false,
+            note: bom_only.then(|| "file holds only a byte order mark".into()),
             candidates: vec![
                 EncodingCandidate {
                     name: "utf-8-sig".into(),
-                    confidence: 0.99,
+                    confidence,
                 },
                 EncodingCandidate {
                     name: "utf-8".into(),
@@ -44,6 +88,7 @@ pub fn detect_from_file(path: &Path) -> Result<EncodingDetectionResult, String>
     let encoding = detector.guess(None, true);
     let best = encoding.name().to_lowercase();
     let confidence = estimate_confidence(&bytes, encoding);
+    log_debug!("encoding", "{}: guessed {} ({:.2})", path.display(), best, confidence);
 
     let mut candidates = Vec::new();
     candidates.push(EncodingCandidate {
@@ -82,6 +127,8 @@ pub fn detect_from_file(path: &Path) -> Result<EncodingDetectionResult, String>
         best,
         confidence,
         candidates,
+        too_short: bytes.len() < MIN_RELIABLE_BYTES,
+        note: None,
     })
 }
 
@@ -103,3 +150,490 @@ fn estimate_confidence(bytes: &[u8], encoding: &'static Encoding) -> f32 {
         0.90
     }
 }
+
+/// Guesses below this (decode errors, files under 64 bytes) do not count
+/// towards a folder's suggested encoding.
+const DIR_VOTE_MIN_CONFIDENCE: f32 = 0.70;
+
+#[derive(Debug, Serialize)]
+pub struct FileDetection {
+    /// Relative to the scanned folder, with `/` separators.
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<EncodingDetectionResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DirDetection {
+    pub files: Vec<FileDetection>,
+    /// Most common confident guess; ties go to the higher summed confidence.
+    /// Absent when no file was detected with confidence.
+    pub suggested: Option<String>,
+}
+
+/// Runs `detect_from_file` on every file in `dir` (and its subfolders when
+/// `recursive`) whose extension is one of `extensions`, compared without the
+/// dot and case-insensitively; an empty list takes every file. A file that
+/// cannot be read gets an `error` instead of stopping the scan.
+pub fn detect_dir(dir: &Path, extensions: &[String], recursive: bool) -> Result<DirDetection, String> {
+    let extensions: Vec<String> =
+        extensions.iter().map(|e| e.trim().trim_start_matches('.').to_lowercase()).filter(|e| !e.is_empty()).collect();
+
+    let mut paths = Vec::new();
+    collect_files(dir, recursive, &mut paths).map_err(|e| format!("failed to read {}: {e}", dir.display()))?;
+    paths.retain(|p| {
+        extensions.is_empty()
+            || p.extension().and_then(|e| e.to_str()).is_some_and(|e| extensions.contains(&e.to_lowercase()))
+    });
+    paths.sort();
+
+    // name -> (votes, summed confidence)
+    let mut votes: BTreeMap<String, (usize, f32)> = BTreeMap::new();
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        let rel = path.strip_prefix(dir).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+        match detect_from_file(&path) {
+            Ok(result) => {
+                // An empty or BOM-only file says nothing about the rest.
+                if result.confidence >= DIR_VOTE_MIN_CONFIDENCE && !result.too_short && result.note.is_none() {
+                    let vote = votes.entry(result.best.clone()).or_insert((0, 0.0));
+                    vote.0 += 1;
+                    vote.1 += result.confidence;
+                }
+                files.push(FileDetection { path: rel, result: Some(result), error: None });
+            }
+            Err(e) => files.push(FileDetection { path: rel, result: None, error: Some(e) }),
+        }
+    }
+
+    let suggested = votes
+        .into_iter()
+        .max_by(|(_, a), (_, b)| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)))
+        .map(|(name, _)| name);
+
+    Ok(DirDetection { files, suggested })
+}
+
+fn collect_files(dir: &Path, recursive: bool, out: &mut Vec<std::path::PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if recursive {
+                collect_files(&path, recursive, out)?;
+            }
+        } else {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Reads `path` and decodes it with the detected encoding, dropping any BOM.
+pub fn read_text(path: &Path) -> Result<String, String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    Ok(decode(&bytes))
+}
+
+/// Shared front end for every text importer: a UTF-8 or UTF-16 BOM decides
+/// the encoding outright (Excel writes both), otherwise the bytes are
+/// sniffed. The BOM itself never reaches the caller.
+pub fn decode(bytes: &[u8]) -> String {
+    let encoding = match Encoding::for_bom(bytes) {
+        Some((enc, _)) => enc,
+        None => {
+            let mut detector = EncodingDetector::new();
+            detector.feed(bytes, true);
+            detector.guess(None, true)
+        }
+    };
+
+    let (text, _, _) = encoding.decode(bytes);
+    strip_bom(&text).to_string()
+}
+
+/// Drops a leading U+FEFF from text that was decoded elsewhere (e.g. pasted
+/// into the UI), so it does not stick to the first field name.
+pub fn strip_bom(text: &str) -> &str {
+    text.strip_prefix('\u{feff}').unwrap_or(text)
+}
+
+/// Reads `path` as `encoding_name`, or as the detected encoding when none is
+/// given, also telling whether the file started with a BOM.
+pub fn read_text_with_bom(path: &Path, encoding_name: Option<&str>) -> Result<(String, bool), String> {
+    let enc = match encoding_name {
+        Some(name) => Some(resolve(name).ok_or_else(|| format!("unsupported encoding: {name}"))?),
+        None => None,
+    };
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let bom = Encoding::for_bom(&bytes).is_some();
+    let text = match enc {
+        Some(enc) => enc.decode(&bytes),
+        None => decode(&bytes),
+    };
+    Ok((text, bom))
+}
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// A project encoding name resolved to what `encoding_rs` should do with it,
+/// plus whether written files start with a BOM. `encoding_rs` has no
+/// `utf-8-sig`; it is UTF-8 with `bom` set. UTF-16 always gets its BOM.
+#[derive(Debug, Clone, Copy)]
+pub struct TextEncoding {
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+}
+
+impl TextEncoding {
+    /// Decodes `bytes`; a BOM matching the encoding is dropped whether or
+    /// not one was expected.
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let (text, _) = self.encoding.decode_with_bom_removal(bytes);
+        text.into_owned()
+    }
+
+    /// Like [`encode_lossy`], prefixed with the BOM when `bom` is set.
+    /// UTF-16 is encoded here, as `encoding_rs` only writes UTF-8 for it.
+    pub fn encode_lossy(self, text: &str) -> Vec<u8> {
+        let mut out = self.bom_bytes().to_vec();
+        if self.encoding == encoding_rs::UTF_16LE {
+            out.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        } else if self.encoding == encoding_rs::UTF_16BE {
+            out.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+        } else {
+            out.extend(encode_lossy(self.encoding, text));
+        }
+        out
+    }
+
+    fn bom_bytes(self) -> &'static [u8] {
+        match (self.bom, self.encoding) {
+            (false, _) => &[],
+            (true, enc) if enc == encoding_rs::UTF_16LE => UTF16LE_BOM,
+            (true, enc) if enc == encoding_rs::UTF_16BE => UTF16BE_BOM,
+            (true, _) => UTF8_BOM,
+        }
+    }
+}
+
+/// Maps the encoding names used by projects (including Python-style aliases
+/// such as `cp932` and `utf-8-sig`) to a [`TextEncoding`].
+pub fn resolve(name: &str) -> Option<TextEncoding> {
+    let n = name.trim().to_lowercase();
+    let (encoding, bom) = match n.as_str() {
+        "utf-8-sig" | "utf8-sig" => (encoding_rs::UTF_8, true),
+        "" | "utf8" => (encoding_rs::UTF_8, false),
+        "cp932" | "ms932" | "sjis" => (encoding_rs::SHIFT_JIS, false),
+        _ => {
+            let enc = Encoding::for_label(n.as_bytes())?;
+            (enc, enc == encoding_rs::UTF_16LE || enc == encoding_rs::UTF_16BE)
+        }
+    };
+    Some(TextEncoding { encoding, bom })
+}
+
+/// A file re-encoded in memory, not yet written back.
+pub struct Conversion {
+    pub bytes: Vec<u8>,
+    /// Characters `to` cannot represent, written as `?` in `bytes`.
+    pub unmappable: Vec<String>,
+}
+
+/// Decodes `path` as `from` and encodes the text as `to`.
+pub fn convert_file(path: &Path, from: TextEncoding, to: TextEncoding) -> Result<Conversion, String> {
+    let bytes = fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    let text = from.decode(&bytes);
+    Ok(Conversion {
+        unmappable: unmappable_chars(to.encoding, &text),
+        bytes: to.encode_lossy(&text),
+    })
+}
+
+/// Letters without a decomposition, and punctuation, mapped to ASCII.
+const TRANSLITERATIONS: &[(char, &str)] = &[
+    ('ß', "ss"),
+    ('æ', "ae"),
+    ('Æ', "AE"),
+    ('œ', "oe"),
+    ('Œ', "OE"),
+    ('ø', "o"),
+    ('Ø', "O"),
+    ('đ', "d"),
+    ('Đ', "D"),
+    ('ł', "l"),
+    ('Ł', "L"),
+    ('ı', "i"),
+    ('‘', "'"),
+    ('’', "'"),
+    ('‚', ","),
+    ('“', "\""),
+    ('”', "\""),
+    ('„', "\""),
+    ('«', "\""),
+    ('»', "\""),
+    ('‹', "'"),
+    ('›', "'"),
+    ('–', "-"),
+    ('—', "-"),
+    ('…', "..."),
+    ('¡', "!"),
+    ('¿', "?"),
+    ('\u{A0}', " "),
+];
+
+/// Nearest text `enc` can represent for the unmappable `ch`: the letter
+/// with its accents stripped (`ã` → `a`), or a table entry for the rest.
+/// `None` when neither gives something encodable.
+pub fn suggest_substitute(enc: &'static Encoding, ch: &str) -> Option<String> {
+    let c = ch.chars().next()?;
+    let candidate = match TRANSLITERATIONS.iter().find(|&&(from, _)| from == c) {
+        Some(&(_, to)) => to.to_string(),
+        None => ch.nfd().filter(|c| !is_combining_mark(*c)).collect(),
+    };
+
+    let (_, _, had_errors) = enc.encode(&candidate);
+    (!candidate.is_empty() && candidate != ch && !had_errors).then_some(candidate)
+}
+
+/// Distinct characters of `text` that `enc` cannot represent, in order of
+/// first appearance.
+pub fn unmappable_chars(enc: &'static Encoding, text: &str) -> Vec<String> {
+    let (_, _, had_errors) = enc.encode(text);
+    if !had_errors {
+        return Vec::new();
+    }
+
+    let mut out: Vec<String> = Vec::new();
+    let mut buf = [0u8; 4];
+    for ch in text.chars() {
+        let s = ch.encode_utf8(&mut buf);
+        let (_, _, err) = enc.encode(s);
+        if err && !out.iter().any(|c| c == s) {
+            out.push(s.to_string());
+        }
+    }
+    out
+}
+
+/// Encodes `text`, writing `?` for characters `enc` cannot represent instead
+/// of the HTML numeric references `encoding_rs` would emit.
+pub fn encode_lossy(enc: &'static Encoding, text: &str) -> Vec<u8> {
+    let (bytes, _, had_errors) = enc.encode(text);
+    if !had_errors {
+        return bytes.into_owned();
+    }
+
+    let mut out: Vec<u8> = Vec::with_capacity(text.len());
+    let mut buf = [0u8; 4];
+    for ch in text.chars() {
+        let (b, _, err) = enc.encode(ch.encode_utf8(&mut buf));
+        if err {
+            out.push(b'?');
+        } else {
+            out.extend_from_slice(&b);
+        }
+    }
+    out
+}
+
+const MAX_ROUNDTRIP_DIFFS: usize = 20;
+
+#[derive(Debug, Serialize)]
+pub struct RoundtripDiff {
+    pub index: usize,
+    pub expected: String,
+    pub actual: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoundtripResult {
+    pub encoding: String,
+    pub lossless: bool,
+    pub differences: Vec<RoundtripDiff>,
+}
+
+/// Encodes `text` to `encoding_name` and decodes it back, reporting the first
+/// characters (by char index) that did not survive.
+pub fn roundtrip_check(text: &str, encoding_name: &str) -> Result<RoundtripResult, String> {
+    let enc = resolve(encoding_name).ok_or_else(|| format!("unsupported encoding: {encoding_name}"))?;
+
+    // Only the BOM written here is dropped; one in `text` must survive.
+    let bytes = enc.encode_lossy(text);
+    let (decoded, _) = enc.encoding.decode_without_bom_handling(&bytes[enc.bom_bytes().len()..]);
+
+    let mut differences = Vec::new();
+    let mut expected = text.chars();
+    let mut actual = decoded.chars();
+    let mut index = 0usize;
+
+    loop {
+        let (e, a) = (expected.next(), actual.next());
+        if e.is_none() && a.is_none() {
+            break;
+        }
+        if e != a {
+            if differences.len() == MAX_ROUNDTRIP_DIFFS {
+                break;
+            }
+            differences.push(RoundtripDiff {
+                index,
+                expected: e.map(String::from).unwrap_or_default(),
+                actual: a.map(String::from).unwrap_or_default(),
+            });
+        }
+        index += 1;
+    }
+
+    Ok(RoundtripResult {
+        encoding: encoding_name.to_string(),
+        lossless: differences.is_empty(),
+        differences,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sekai-core-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    const JAPANESE: &str = "今日はいい天気ですね。明日も晴れるといいな。\n「そうだね、散歩に行こうか」\n";
+
+    fn sjis(text: &str) -> Vec<u8> {
+        encoding_rs::SHIFT_JIS.encode(text).0.into_owned()
+    }
+
+    #[test]
+    fn bom_decides_utf8_sig() {
+        let path = temp_dir("encoding-sig").join("a.ks");
+        fs::write(&path, [UTF8_BOM, "こんにちは".as_bytes()].concat()).unwrap();
+
+        let result = detect_from_file(&path).unwrap();
+        assert_eq!((result.best.as_str(), result.confidence), ("utf-8-sig", 0.99));
+        assert_eq!(result.candidates[1].name, "utf-8");
+        assert_eq!(read_text(&path).unwrap(), "こんにちは");
+    }
+
+    #[test]
+    fn utf8_sig_writes_and_strips_its_bom() {
+        let enc = resolve("utf-8-sig").unwrap();
+        let bytes = enc.encode_lossy("テスト");
+        assert!(bytes.starts_with(UTF8_BOM));
+        assert_eq!(enc.decode(&bytes), "テスト");
+        assert_eq!(decode(&bytes), "テスト");
+        assert!(!resolve("utf-8").unwrap().encode_lossy("テスト").starts_with(UTF8_BOM));
+        assert_eq!(resolve("cp932").unwrap().encoding, encoding_rs::SHIFT_JIS);
+        assert!(resolve("no-such-encoding").is_none());
+    }
+
+    #[test]
+    fn utf16_is_written_as_utf16_with_its_bom() {
+        let le = resolve("UTF-16LE").unwrap();
+        assert_eq!(le.encode_lossy("aあ"), [0xFF, 0xFE, 0x61, 0x00, 0x42, 0x30]);
+        assert_eq!(le.decode(&le.encode_lossy("「おはよう」[r]")), "「おはよう」[r]");
+        assert_eq!(decode(&le.encode_lossy("おはよう")), "おはよう");
+
+        let be = resolve("utf-16be").unwrap();
+        assert_eq!(be.encode_lossy("aあ"), [0xFE, 0xFF, 0x00, 0x61, 0x30, 0x42]);
+        assert_eq!(be.decode(&be.encode_lossy("😀")), "😀");
+    }
+
+    #[test]
+    fn roundtrip_reports_lost_characters() {
+        let result = roundtrip_check("Olá, 世界", "cp932").unwrap();
+        assert!(!result.lossless);
+        assert_eq!(result.differences.len(), 1);
+        assert_eq!((result.differences[0].index, result.differences[0].actual.as_str()), (2, "?"));
+        assert!(roundtrip_check("世界", "cp932").unwrap().lossless);
+        assert!(roundtrip_check("\u{feff}abc", "utf-8-sig").unwrap().lossless);
+        assert!(roundtrip_check("x", "nope").is_err());
+    }
+
+    #[test]
+    fn utf16_round_trips() {
+        for name in ["utf-16le", "utf-16be"] {
+            assert!(roundtrip_check("abc", name).unwrap().lossless);
+            assert!(roundtrip_check("「今日はいい天気ですね」[r]Olá", name).unwrap().lossless);
+        }
+    }
+
+    #[test]
+    fn empty_and_bom_only_files_are_decided_without_sniffing() {
+        let dir = temp_dir("encoding-edge");
+        let write = |name: &str, bytes: &[u8]| {
+            let path = dir.join(name);
+            fs::write(&path, bytes).unwrap();
+            detect_from_file(&path).unwrap()
+        };
+
+        let empty = write("empty.ks", b"");
+        assert_eq!((empty.best.as_str(), empty.note.as_deref()), ("utf-8", Some("empty file")));
+
+        let bom = write("bom.ks", UTF8_BOM);
+        assert_eq!(bom.best, "utf-8-sig");
+        assert_eq!(bom.confidence, 1.0);
+        assert!(bom.note.is_some());
+
+        let utf16 = write("utf16.ks", &[0xFF, 0xFE]);
+        assert_eq!(utf16.best, "utf-16le");
+
+        let short = write("short.ks", "あ".as_bytes());
+        assert!(short.too_short);
+    }
+
+    #[test]
+    fn shift_jis_is_detected_with_its_aliases() {
+        let path = temp_dir("encoding-sjis").join("a.ks");
+        fs::write(&path, sjis(&JAPANESE.repeat(3))).unwrap();
+
+        let result = detect_from_file(&path).unwrap();
+        assert_eq!(result.best, "shift_jis");
+        assert!(!result.too_short);
+        let names: Vec<&str> = result.candidates.iter().map(|c| c.name.as_str()).collect();
+        assert!(names.contains(&"cp932"));
+    }
+
+    #[test]
+    fn folder_suggestion_follows_the_most_common_encoding() {
+        let dir = temp_dir("encoding-dir");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.ks"), sjis(&JAPANESE.repeat(3))).unwrap();
+        fs::write(dir.join("b.ks"), JAPANESE.repeat(3)).unwrap();
+        fs::write(dir.join("c.ks"), sjis(&JAPANESE.repeat(3))).unwrap();
+        fs::write(dir.join("sub/d.KS"), JAPANESE.repeat(3)).unwrap();
+        fs::write(dir.join("notes.txt"), JAPANESE.repeat(3)).unwrap();
+
+        let flat = detect_dir(&dir, &[".ks".into()], false).unwrap();
+        let found: Vec<(&str, &str)> = flat
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.result.as_ref().unwrap().best.as_str()))
+            .collect();
+        assert_eq!(found, [("a.ks", "shift_jis"), ("b.ks", "utf-8"), ("c.ks", "shift_jis")]);
+        assert_eq!(flat.suggested.as_deref(), Some("shift_jis"));
+
+        let deep = detect_dir(&dir, &["KS".into()], true).unwrap();
+        assert_eq!(deep.files.len(), 4);
+        assert_eq!(deep.files[3].path, "sub/d.KS");
+    }
+
+    #[test]
+    fn substitutes_are_suggested_only_when_encodable() {
+        let sjis = encoding_rs::SHIFT_JIS;
+        assert_eq!(unmappable_chars(sjis, "coração"), ["ç", "ã"]);
+        assert_eq!(suggest_substitute(sjis, "ç").as_deref(), Some("c"));
+        assert_eq!(suggest_substitute(sjis, "ß").as_deref(), Some("ss"));
+        assert_eq!(suggest_substitute(sjis, "😀"), None);
+        assert_eq!(encode_lossy(sjis, "ção"), b"??o");
+    }
+}
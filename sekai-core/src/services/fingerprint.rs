@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::log_info;
+use crate::services::encoding;
+use crate::services::fsutil::write_atomic;
+use crate::services::project;
+use crate::services::translation_memory::store::unix_now;
+
+const FINGERPRINT_FILE: &str = "fingerprints.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// SHA-256 of the raw bytes.
+    pub bytes_hash: String,
+    /// SHA-256 of the decoded text with `\r\n`/`\r` folded to `\n`, so a
+    /// line-ending-only change can be told apart.
+    pub text_hash: String,
+    pub size: u64,
+    /// Unix seconds.
+    pub recorded_at: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileCheck {
+    pub path: String,
+    /// `unchanged`, `changed`, or `new` when nothing was recorded yet.
+    pub status: &'static str,
+    /// For `changed`: whether the text differs, not just its bytes.
+    pub text_changed: bool,
+}
+
+/// Records the current fingerprint of each of `files` (relative to the game
+/// root unless absolute) in the project, replacing earlier ones.
+pub fn record(project_path: &str, files: &[String]) -> Result<BTreeMap<String, Fingerprint>, String> {
+    let root = project::open_project(project_path.to_string())?.root_path;
+    let mut stored = load(project_path)?;
+
+    let mut recorded = BTreeMap::new();
+    for f in files {
+        let fp = compute(&Path::new(&root).join(f))?;
+        stored.insert(f.clone(), fp.clone());
+        recorded.insert(f.clone(), fp);
+    }
+
+    save(project_path, &stored)?;
+    log_info!("fingerprint", "recorded {} file(s) in {}", recorded.len(), project_path);
+    Ok(recorded)
+}
+
+/// Compares each of `files` against its recorded fingerprint without
+/// storing anything.
+pub fn check(project_path: &str, files: &[String]) -> Result<Vec<FileCheck>, String> {
+    let root = project::open_project(project_path.to_string())?.root_path;
+    let stored = load(project_path)?;
+
+    files
+        .iter()
+        .map(|f| {
+            let current = compute(&Path::new(&root).join(f))?;
+            let (status, text_changed) = match stored.get(f) {
+                None => ("new", false),
+                Some(old) if old.bytes_hash == current.bytes_hash => ("unchanged", false),
+                Some(old) => ("changed", old.text_hash != current.text_hash),
+            };
+            Ok(FileCheck {
+                path: f.clone(),
+                status,
+                text_changed,
+            })
+        })
+        .collect()
+}
+
+fn compute(path: &Path) -> Result<Fingerprint, String> {
+    let bytes = fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    let text = encoding::decode(&bytes).replace("\r\n", "\n").replace('\r', "\n");
+
+    Ok(Fingerprint {
+        bytes_hash: hex::encode(Sha256::digest(&bytes)),
+        text_hash: hex::encode(Sha256::digest(text.as_bytes())),
+        size: bytes.len() as u64,
+        recorded_at: unix_now(),
+    })
+}
+
+fn load(project_path: &str) -> Result<BTreeMap<String, Fingerprint>, String> {
+    let path = Path::new(project_path).join(FINGERPRINT_FILE);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let data = fs::read_to_string(&path).map_err(|e| format!("failed to read {FINGERPRINT_FILE}: {e}"))?;
+    serde_json::from_str(&data).map_err(|e| format!("invalid {FINGERPRINT_FILE}: {e}"))
+}
+
+fn save(project_path: &str, stored: &BTreeMap<String, Fingerprint>) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(stored).map_err(|e| e.to_string())?;
+    write_atomic(&Path::new(project_path).join(FINGERPRINT_FILE), json.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_are_told_apart_from_line_ending_changes() {
+        project::with_temp_app_data("fingerprint", |dir| {
+            let project = project::create_project(
+                "Fp".into(),
+                dir.to_string_lossy().into(),
+                "utf-8".into(),
+                String::new(),
+                String::new(),
+                "ja".into(),
+                "pt".into(),
+            )
+            .unwrap();
+            let path = &project.project_path;
+            let files: Vec<String> = ["a.ks", "b.ks", "c.ks"].map(String::from).to_vec();
+            for f in &files {
+                fs::write(dir.join(f), "一\n二\n").unwrap();
+            }
+            record(path, &files[..2]).unwrap();
+
+            fs::write(dir.join("a.ks"), "一\r\n二\r\n").unwrap();
+            let status = |checks: &[FileCheck]| checks.iter().map(|c| (c.status, c.text_changed)).collect::<Vec<_>>();
+            assert_eq!(status(&check(path, &files).unwrap()), [("changed", false), ("unchanged", false), ("new", false)]);
+
+            fs::write(dir.join("b.ks"), "一\n三\n").unwrap();
+            assert_eq!(status(&check(path, &files[1..2]).unwrap()), [("changed", true)]);
+        });
+    }
+}
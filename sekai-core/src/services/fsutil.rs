@@ -0,0 +1,155 @@
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+const LOCK_POLL_MS: u64 = 50;
+
+/// Writes to a sibling `.tmp` file first and renames it over `path`, so a
+/// crash never leaves a half-written file behind.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let tmp = tmp_path(path);
+
+    if let Some(parent) = tmp.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    fs::write(&tmp, bytes).map_err(|e| e.to_string())?;
+
+    if path.exists() {
+        fs::remove_file(path).map_err(|e| e.to_string())?;
+    }
+
+    fs::rename(&tmp, path).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Appends one line (newline added) to `path`, creating it and its parent
+/// directory on first use. For append-only logs.
+pub fn append_line(path: &Path, line: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    file.write_all(format!("{line}\n").as_bytes()).map_err(|e| e.to_string())
+}
+
+/// Held while a `<name>.lock` sidecar of the locked file exists; dropping it
+/// removes the sidecar.
+pub struct FileLock {
+    path: PathBuf,
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Advisory exclusive lock on `path` for processes that all go through
+/// here. Waits up to `timeout` for another holder to let go; a sidecar last
+/// touched more than `stale_after` ago is taken to be left by a crashed
+/// process and is removed.
+pub fn lock(path: &Path, timeout: Duration, stale_after: Duration) -> Result<FileLock, String> {
+    let lock_path = sibling(path, "lock");
+    if let Some(parent) = lock_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    let started = Instant::now();
+    loop {
+        match OpenOptions::new().write(true).create_new(true).open(&lock_path) {
+            Ok(mut file) => {
+                let _ = write!(file, "{}", std::process::id());
+                return Ok(FileLock { path: lock_path });
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(format!("failed to create {}: {e}", lock_path.display())),
+        }
+
+        let age = fs::metadata(&lock_path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| SystemTime::now().duration_since(t).ok());
+        if age.is_some_and(|a| a > stale_after) {
+            let _ = fs::remove_file(&lock_path);
+            continue;
+        }
+
+        if started.elapsed() >= timeout {
+            return Err(format!(
+                "{} is locked by another process ({} exists); gave up after {} ms",
+                path.display(),
+                lock_path.display(),
+                timeout.as_millis()
+            ));
+        }
+        thread::sleep(Duration::from_millis(LOCK_POLL_MS));
+    }
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    sibling(path, "tmp")
+}
+
+fn sibling(path: &Path, ext: &str) -> PathBuf {
+    let mut p = path.to_path_buf();
+    let file_name = match path.file_name().and_then(|s| s.to_str()) {
+        Some(n) => n.to_string(),
+        None => "tm".to_string(),
+    };
+    p.set_file_name(format!("{file_name}.{ext}"));
+    p
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sekai-core-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn lines_are_appended() {
+        let path = temp_dir("fsutil-append").join("log/review.jsonl");
+        append_line(&path, "a").unwrap();
+        append_line(&path, "b").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\nb\n");
+    }
+
+    #[test]
+    fn atomic_write_replaces_and_leaves_no_temp_file() {
+        let path = temp_dir("fsutil-write").join("nested/data.json");
+        write_atomic(&path, b"one").unwrap();
+        write_atomic(&path, b"two").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"two");
+        assert!(!tmp_path(&path).exists());
+    }
+
+    #[test]
+    fn lock_waits_then_gives_up_and_takes_over_stale_locks() {
+        let path = temp_dir("fsutil-lock").join("tm.json");
+        let held = lock(&path, Duration::from_secs(1), Duration::from_secs(60)).unwrap();
+        let Err(err) = lock(&path, Duration::from_millis(60), Duration::from_secs(60)) else {
+            panic!("lock taken twice");
+        };
+        assert!(err.contains("locked by another process"), "{err}");
+
+        // Seen as left behind by a crash.
+        let again = lock(&path, Duration::from_millis(60), Duration::ZERO);
+        assert!(again.is_ok());
+        drop(again);
+        drop(held);
+        assert!(!sibling(&path, "lock").exists());
+    }
+}
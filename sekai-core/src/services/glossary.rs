@@ -0,0 +1,220 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::log_info;
+use crate::model::entry::CoreEntry;
+use crate::model::glossary::GlossaryTerm;
+use crate::services::csv;
+use crate::services::fsutil::write_atomic;
+
+const GLOSSARY_FILE: &str = "glossary.json";
+
+#[derive(Debug, Serialize)]
+pub struct CsvImportReport {
+    pub imported: usize,
+    pub duplicates: usize,
+    pub skipped: usize,
+    pub total: usize,
+}
+
+/// Every `<name>` speaking `e`, or its lone `speaker`.
+pub fn entry_speakers(e: &CoreEntry) -> Vec<&str> {
+    if e.speakers.is_empty() {
+        e.speaker.as_deref().into_iter().collect()
+    } else {
+        e.speakers.iter().map(String::as_str).collect()
+    }
+}
+
+/// Terms with a target whose source occurs in `e.original`, as they apply to
+/// its speaker: a term scoped to that speaker takes the place of the
+/// unscoped one with the same source, and terms scoped to anyone else are
+/// left out.
+pub fn terms_for<'g>(glossary: &'g [GlossaryTerm], e: &CoreEntry) -> Vec<&'g GlossaryTerm> {
+    let speakers = entry_speakers(e);
+    let mut terms: Vec<&GlossaryTerm> = Vec::new();
+
+    for t in glossary {
+        let source = t.source.trim();
+        if source.is_empty() || t.target.trim().is_empty() || !t.applies_to(&speakers) || !e.original.contains(source) {
+            continue;
+        }
+        match terms.iter().position(|known| known.source.trim() == source) {
+            Some(i) if t.is_scoped() && !terms[i].is_scoped() => terms[i] = t,
+            Some(_) => {}
+            None => terms.push(t),
+        }
+    }
+
+    terms
+}
+
+pub fn load(project_path: &str) -> Result<Vec<GlossaryTerm>, String> {
+    let path = Path::new(project_path).join(GLOSSARY_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let data = fs::read_to_string(&path).map_err(|e| format!("failed to read {GLOSSARY_FILE}: {e}"))?;
+    serde_json::from_str(&data).map_err(|e| format!("invalid {GLOSSARY_FILE}: {e}"))
+}
+
+pub fn save(project_path: &str, terms: &[GlossaryTerm]) -> Result<(), String> {
+    let dir = Path::new(project_path);
+    if !dir.is_dir() {
+        return Err("project directory not found".into());
+    }
+
+    let json = serde_json::to_string_pretty(terms).map_err(|e| e.to_string())?;
+    write_atomic(&dir.join(GLOSSARY_FILE), json.as_bytes())?;
+
+    log_info!("glossary", "saved {} terms to {}", terms.len(), project_path);
+    Ok(())
+}
+
+/// Merges `source,target,note[,speaker]` rows into the project glossary.
+/// Sources are compared case-insensitively per speaker scope; the last
+/// occurrence wins, both within the CSV and over terms already stored.
+/// Malformed or empty rows are skipped.
+pub fn import_csv(project_path: &str, text: &str) -> Result<CsvImportReport, String> {
+    let mut terms = load(project_path)?;
+
+    let key_of = |source: &str, speaker: &str| (source.to_lowercase(), speaker.to_string());
+    let mut index: HashMap<(String, String), usize> = terms
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (key_of(t.source.trim(), t.speaker.as_deref().unwrap_or("").trim()), i))
+        .collect();
+
+    let mut imported = 0usize;
+    let mut duplicates = 0usize;
+    let mut skipped = 0usize;
+    let mut seen_in_csv: HashSet<(String, String)> = HashSet::new();
+
+    for (n, row) in csv::parse(text).into_iter().enumerate() {
+        let fields = match row {
+            Ok(f) => f,
+            Err(_) => {
+                skipped += 1;
+                continue;
+            }
+        };
+
+        let source = fields.first().map(|s| s.trim()).unwrap_or("");
+        let target = fields.get(1).map(|s| s.trim()).unwrap_or("");
+        let note = fields.get(2).map(|s| s.trim()).unwrap_or("");
+        let speaker = fields.get(3).map(|s| s.trim()).unwrap_or("");
+
+        if n == 0 && source.eq_ignore_ascii_case("source") {
+            continue;
+        }
+
+        if source.is_empty() || target.is_empty() {
+            skipped += 1;
+            continue;
+        }
+
+        let key = key_of(source, speaker);
+        if !seen_in_csv.insert(key.clone()) {
+            duplicates += 1;
+        } else {
+            imported += 1;
+        }
+
+        let term = GlossaryTerm {
+            source: source.to_string(),
+            target: target.to_string(),
+            note: note.to_string(),
+            speaker: (!speaker.is_empty()).then(|| speaker.to_string()),
+        };
+
+        match index.get(&key) {
+            Some(&i) => terms[i] = term,
+            None => {
+                index.insert(key, terms.len());
+                terms.push(term);
+            }
+        }
+    }
+
+    save(project_path, &terms)?;
+
+    Ok(CsvImportReport {
+        imported,
+        duplicates,
+        skipped,
+        total: terms.len(),
+    })
+}
+
+pub fn export_csv(project_path: &str) -> Result<String, String> {
+    let terms = load(project_path)?;
+
+    let mut out = String::new();
+    csv::write_row(&mut out, &["source", "target", "note", "speaker"]);
+    for t in &terms {
+        csv::write_row(&mut out, &[&t.source, &t.target, &t.note, t.speaker.as_deref().unwrap_or("")]);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sekai-core-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn term(source: &str, target: &str) -> GlossaryTerm {
+        GlossaryTerm { source: source.into(), target: target.into(), note: String::new(), speaker: None }
+    }
+
+    fn scoped(source: &str, target: &str, speaker: &str) -> GlossaryTerm {
+        GlossaryTerm { speaker: Some(speaker.into()), ..term(source, target) }
+    }
+
+    #[test]
+    fn csv_import_merges_quoted_rows_and_counts_duplicates() {
+        let dir = temp_dir("glossary-csv");
+        let project = dir.to_str().unwrap();
+        save(project, &[term("魔法", "mágica")]).unwrap();
+
+        let csv = "source,target,note\n\"魔法\",\"magia\",\"tom, leve\"\n剣,espada,\n剣,lâmina,\n\"bad\"x,y\n,vazio,";
+        let report = import_csv(project, csv).unwrap();
+        assert_eq!((report.imported, report.duplicates, report.skipped, report.total), (2, 1, 2, 2));
+
+        let terms = load(project).unwrap();
+        assert_eq!(terms[0].target, "magia");
+        assert_eq!(terms[0].note, "tom, leve");
+        assert_eq!(terms[1].target, "lâmina");
+
+        let exported = export_csv(project).unwrap();
+        assert!(exported.starts_with("source,target,note,speaker\r\n"));
+        assert!(exported.contains("魔法,magia,\"tom, leve\",\r\n"));
+    }
+
+    #[test]
+    fn scoped_term_replaces_the_unscoped_one_for_its_speaker() {
+        let glossary = [term("姉さん", "irmã"), scoped("姉さん", "mana", "B"), scoped("剣", "espada", "C")];
+        let entries: Vec<CoreEntry> = serde_json::from_value(serde_json::json!([
+            {"entry_id": "1-text", "original": "姉さんの剣", "speaker": "A"},
+            {"entry_id": "2-text", "original": "姉さんの剣", "speaker": "A", "speakers": ["A", "B"]},
+            {"entry_id": "3-text", "original": "姉さんの剣"},
+        ]))
+        .unwrap();
+        let targets = |e| terms_for(&glossary, e).iter().map(|t| t.target.as_str()).collect::<Vec<_>>();
+
+        assert_eq!(targets(&entries[0]), ["irmã"]);
+        assert_eq!(targets(&entries[1]), ["mana"]);
+        assert_eq!(targets(&entries[2]), ["irmã"]);
+    }
+}
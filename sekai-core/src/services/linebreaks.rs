@@ -0,0 +1,108 @@
+/// In-box line-break tokens (`[r]` and a literal `\n`) are swapped for numbered
+/// `{BRn}` markers before the text reaches the model and swapped back after,
+/// so the model cannot drop or rewrite them.
+const BREAK_TOKENS: &[&str] = &["[r]", "\\n"];
+
+pub struct Protected {
+    pub text: String,
+    pub tokens: Vec<&'static str>,
+}
+
+pub fn protect(text: &str) -> Protected {
+    let mut out = String::with_capacity(text.len());
+    let mut tokens: Vec<&'static str> = Vec::new();
+    let mut rest = text;
+
+    'outer: while !rest.is_empty() {
+        for &tok in BREAK_TOKENS {
+            if rest.starts_with(tok) {
+                tokens.push(tok);
+                out.push_str(&marker(tokens.len()));
+                rest = &rest[tok.len()..];
+                continue 'outer;
+            }
+        }
+        let ch = rest.chars().next().unwrap();
+        out.push(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+
+    Protected { text: out, tokens }
+}
+
+/// Puts the original tokens back. Every marker must appear exactly once; when
+/// `allow_reposition` is false they must also keep their original order.
+pub fn restore(translation: &str, tokens: &[&str], allow_reposition: bool) -> Result<String, String> {
+    if tokens.is_empty() {
+        return Ok(translation.to_string());
+    }
+
+    let mut positions: Vec<(usize, usize)> = Vec::with_capacity(tokens.len());
+    for n in 1..=tokens.len() {
+        let m = marker(n);
+        let found: Vec<usize> = translation.match_indices(&m).map(|(i, _)| i).collect();
+        if found.len() != 1 {
+            return Err(format!(
+                "line-break marker {m} appears {} times in the translation (expected once)",
+                found.len()
+            ));
+        }
+        positions.push((found[0], n));
+    }
+
+    if !allow_reposition && positions.windows(2).any(|w| w[0].0 > w[1].0) {
+        return Err("line-break markers were reordered".into());
+    }
+
+    let mut out = translation.to_string();
+    for (n, tok) in tokens.iter().enumerate() {
+        out = out.replacen(&marker(n + 1), tok, 1);
+    }
+    Ok(out)
+}
+
+pub fn instruction(count: usize, allow_reposition: bool) -> Option<String> {
+    if count == 0 {
+        return None;
+    }
+    let placement = if allow_reposition {
+        "You may move them to where the line should break in the target language."
+    } else {
+        "Keep them in the same order and at the equivalent place in the sentence."
+    };
+    Some(format!(
+        "The text contains {count} line-break marker(s) written as {{BRn}}. Keep every marker exactly once. {placement}"
+    ))
+}
+
+fn marker(n: usize) -> String {
+    format!("{{BR{n}}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn break_tokens_round_trip_through_markers() {
+        let p = protect("一行目[r]二行目\\n三行目");
+        assert_eq!(p.text, "一行目{BR1}二行目{BR2}三行目");
+        assert_eq!(p.tokens, ["[r]", "\\n"]);
+        assert_eq!(restore("Um{BR1}dois{BR2}três", &p.tokens, false).unwrap(), "Um[r]dois\\ntrês");
+    }
+
+    #[test]
+    fn dropped_or_reordered_markers_are_refused() {
+        let tokens = ["[r]", "[r]"];
+        assert!(restore("Um dois{BR2}", &tokens, true).is_err());
+        assert!(restore("Um{BR1}{BR1}{BR2}", &tokens, true).is_err());
+        assert!(restore("Um{BR2}dois{BR1}", &tokens, false).is_err());
+        assert_eq!(restore("Um{BR2}dois{BR1}", &tokens, true).unwrap(), "Um[r]dois[r]");
+    }
+
+    #[test]
+    fn instruction_only_when_there_are_markers() {
+        assert!(instruction(0, false).is_none());
+        assert!(instruction(2, true).unwrap().contains("You may move them"));
+    }
+}
@@ -1,8 +1,31 @@
 pub mod ai;
+pub mod ai_audit;
 pub mod ai_types;
+pub mod bundle;
+pub mod checkpoint;
+pub mod cleanup;
+pub mod compare;
+pub mod csv;
 pub mod encoding;
+pub mod fingerprint;
+pub mod fsutil;
+pub mod glossary;
+pub mod linebreaks;
+pub mod mojibake;
+pub mod nontext;
 pub mod pipeline;
 pub mod project;
+pub mod propagate;
+pub mod providers;
 pub mod qa;
+pub mod query;
 pub mod rebuild;
+pub mod review;
+pub mod rtl;
+pub mod segment;
+pub mod stats;
+pub mod substitutions;
+pub mod text;
+pub mod tokens;
 pub mod translation_memory;
+pub mod trivial;
@@ -0,0 +1,93 @@
+use serde::Serialize;
+
+use crate::model::entry::CoreEntry;
+
+pub const DEFAULT_THRESHOLD: f32 = 0.3;
+
+/// Characters Windows-1252 puts in 0x80–0x9F; Shift-JIS lead bytes decoded
+/// as cp1252 land here (`‚±‚ñ` is `こん`).
+const CP1252_HIGH: &str = "€‚ƒ„…†‡ˆ‰Š‹ŒŽ‘’“”•–—˜™š›œžŸ";
+
+/// Kanji that UTF-8 kana lead bytes (E3 81/82/83) become when read as
+/// Shift-JIS; they almost never occur in real text.
+const SJIS_SIGNATURE: &str = "縺繧繝";
+
+#[derive(Debug, Serialize)]
+pub struct MojibakeSuspect {
+    pub entry_id: String,
+    pub confidence: f32,
+}
+
+/// Heuristic share (0.0–1.0) of a string that looks like mis-decoded text.
+///
+/// C1 controls, cp1252 high punctuation, U+FFFD, `Ã`/`Â` followed by a
+/// continuation-range char and the Shift-JIS signature kanji count fully;
+/// other Latin-1 letters count half, so accented Latin text alone stays low.
+pub fn score(text: &str) -> f32 {
+    let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if chars.is_empty() {
+        return 0.0;
+    }
+
+    let mut weight = 0.0f32;
+    for (i, &c) in chars.iter().enumerate() {
+        let next_is_continuation = chars.get(i + 1).is_some_and(|&n| ('\u{80}'..='\u{BF}').contains(&n));
+
+        weight += if ('\u{80}'..='\u{9F}').contains(&c)
+            || c == '\u{FFFD}'
+            || CP1252_HIGH.contains(c)
+            || SJIS_SIGNATURE.contains(c)
+            || ((c == 'Ã' || c == 'Â') && next_is_continuation)
+        {
+            1.0
+        } else if ('\u{A0}'..='\u{FF}').contains(&c) {
+            0.5
+        } else {
+            0.0
+        };
+    }
+
+    (weight / chars.len() as f32).min(1.0)
+}
+
+/// Translatable entries whose `original` scores at or above `threshold`.
+pub fn detect(entries: &[CoreEntry], threshold: f32) -> Vec<MojibakeSuspect> {
+    entries
+        .iter()
+        .filter(|e| e.is_translatable)
+        .filter_map(|e| {
+            let confidence = score(&e.original);
+            (confidence >= threshold).then(|| MojibakeSuspect {
+                entry_id: e.entry_id.clone(),
+                confidence,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(entry_id: &str, original: &str) -> CoreEntry {
+        serde_json::from_value(serde_json::json!({ "entry_id": entry_id, "original": original, "is_translatable": true })).unwrap()
+    }
+
+    #[test]
+    fn misdecoded_text_scores_high_and_real_text_low() {
+        assert!(score("‚±‚ñ‚É‚¿‚Í") >= DEFAULT_THRESHOLD);
+        assert!(score("縺薙ｓ縺ｫ縺｡縺ｯ") >= DEFAULT_THRESHOLD);
+        assert!(score("coraÃ§Ã£o") >= DEFAULT_THRESHOLD);
+        assert!(score("Coração de estudante") < DEFAULT_THRESHOLD);
+        assert!(score("こんにちは") < DEFAULT_THRESHOLD);
+        assert_eq!(score("   "), 0.0);
+    }
+
+    #[test]
+    fn detect_lists_suspect_entries() {
+        let entries = [line("1-text", "こんにちは"), line("2-text", "‚±‚ñ‚É‚¿‚Í")];
+        let suspects = detect(&entries, DEFAULT_THRESHOLD);
+        assert_eq!(suspects.len(), 1);
+        assert_eq!(suspects[0].entry_id, "2-text");
+    }
+}
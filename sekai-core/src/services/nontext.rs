@@ -0,0 +1,93 @@
+use regex::Regex;
+use serde::Deserialize;
+
+/// Whole-line shapes of system strings that are never translated: URLs,
+/// Windows and relative paths, bare asset file names, version numbers and
+/// numeric IDs.
+pub const DEFAULT_PATTERNS: &[&str] = &[
+    r"^(?i:https?|ftp)://\S+$",
+    r"^[A-Za-z]:[\\/]\S*$",
+    r"^(?:\.{0,2}/)?(?:[A-Za-z0-9_.-]+[\\/])+[A-Za-z0-9_-]+\.[A-Za-z0-9]{2,4}$",
+    r"^[\w-]+\.(?i:png|jpe?g|bmp|gif|webp|ogg|wav|mp3|m4a|mp4|webm|ks|tjs|txt|json|csv|ttf|otf)$",
+    r"^v?\d+(?:\.\d+){1,3}(?:[-+][\w.]+)?$",
+    r"^#?\d+$",
+];
+
+/// Payload form of the non-text classifier; `patterns` replaces the
+/// defaults rather than adding to them.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NontextRule {
+    pub enabled: bool,
+    pub patterns: Vec<String>,
+}
+
+impl Default for NontextRule {
+    fn default() -> Self {
+        NontextRule {
+            enabled: true,
+            patterns: DEFAULT_PATTERNS.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+impl NontextRule {
+    pub fn compile(&self) -> Result<NontextFilter, String> {
+        if !self.enabled {
+            return Ok(NontextFilter { patterns: Vec::new() });
+        }
+        let patterns = self
+            .patterns
+            .iter()
+            .map(|p| Regex::new(p).map_err(|e| format!("invalid nontext pattern {p:?}: {e}")))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(NontextFilter { patterns })
+    }
+}
+
+/// Compiled classifier; with no patterns nothing is non-text.
+#[derive(Debug, Clone)]
+pub struct NontextFilter {
+    patterns: Vec<Regex>,
+}
+
+impl Default for NontextFilter {
+    fn default() -> Self {
+        NontextRule::default().compile().expect("default nontext patterns compile")
+    }
+}
+
+impl NontextFilter {
+    /// True when the trimmed `original` matches one of the patterns.
+    pub fn is_nontext(&self, original: &str) -> bool {
+        let text = original.trim();
+        !text.is_empty() && self.patterns.iter().any(|re| re.is_match(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_strings_are_nontext() {
+        let filter = NontextFilter::default();
+        for s in ["https://example.com/a", "C:\\Games\\save", "bgm/title.ogg", "bg01.png", "v1.2.3", "#1024"] {
+            assert!(filter.is_nontext(s), "{s}");
+        }
+        for s in ["こんにちは", "Version 2 is out", ""] {
+            assert!(!filter.is_nontext(s), "{s}");
+        }
+    }
+
+    #[test]
+    fn patterns_replace_the_defaults_and_can_be_disabled() {
+        let custom = NontextRule { enabled: true, patterns: vec![r"^SE_\d+$".into()] }.compile().unwrap();
+        assert!(custom.is_nontext("SE_01"));
+        assert!(!custom.is_nontext("bg01.png"));
+
+        let off = NontextRule { enabled: false, ..Default::default() }.compile().unwrap();
+        assert!(!off.is_nontext("bg01.png"));
+        assert!(NontextRule { enabled: true, patterns: vec!["(".into()] }.compile().is_err());
+    }
+}
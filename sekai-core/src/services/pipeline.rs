@@ -2,92 +2,272 @@ use crate::model::entry::{CoreEntry, EntryStatus};
 use crate::services::{
     ai,
     ai_types::AiRunReport,
-    translation_memory::{hash, matcher, model::TMEntry, normalize, store},
+    qa,
+    query::{self, EntryQuery},
+    trivial,
+    translation_memory::{
+        hash, matcher,
+        model::{DedupStrategy, TMEntry, TmSource},
+        normalize, store,
+    },
 };
 
-use std::collections::HashMap;
+use crate::{log_debug, log_info, log_warn};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+pub const DEFAULT_FUZZY_AUTO_THRESHOLD: f32 = 0.95;
+pub const DEFAULT_FUZZY_REVIEW_THRESHOLD: f32 = 0.85;
+pub const DEFAULT_TM_FLUSH_EVERY: usize = 50;
 
 pub struct PipelineConfig<'a> {
-    pub provider: &'a str,
-    pub api_key: &'a str,
-    pub model: &'a str,
-    pub source_lang: &'a str,
-    pub target_lang: &'a str,
+    pub ai: ai::AiConfig<'a>,
+    /// Fuzzy matches at or above this ratio are applied as `Translated`.
+    pub fuzzy_auto_threshold: f32,
+    /// Fuzzy matches at or above this ratio (but below auto) are applied as
+    /// `InProgress` for review; anything lower goes to the AI.
+    pub fuzzy_review_threshold: f32,
+    pub tm_dedup: DedupStrategy,
+    /// When false the TM is only read: AI results are not added and the
+    /// store file is left untouched.
+    pub write_tm: bool,
+    /// AI translations learned during the run are saved to the TM each time
+    /// this many are waiting, so a crash loses fewer; 0 saves only at the end.
+    pub tm_flush_every: usize,
+    /// Entries neither the TM nor the AI could translate lose whatever
+    /// translation they had and end up blank and `Untranslated`, so a
+    /// progress build shows exactly what is left.
+    pub blank_failures: bool,
+    /// Restricts both the TM and AI stages to matching entries.
+    pub filter: Option<EntryQuery>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchTier {
+    Exact,
+    FuzzyAuto,
+    FuzzyReview,
+    Ai,
+    /// No translatable content; the original was copied.
+    Trivial,
+    /// A URL, path, version or ID; the original was copied.
+    Nontext,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PipelineItem {
+    pub entry_id: String,
+    pub tier: MatchTier,
+    pub ratio: Option<f32>,
 }
 
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, Serialize)]
 pub struct PipelineReport {
     pub used_tm: usize,
+    pub used_fuzzy: usize,
     pub used_ai: usize,
+    /// Entries without translatable content, copied from the original.
+    pub trivial: usize,
+    /// URLs, paths, version numbers and IDs, copied from the original.
+    pub skipped_nontext: usize,
+    pub items: Vec<PipelineItem>,
     pub ai_report: Option<AiRunReport>,
+    /// AI translations that were not added to the TM because `write_tm` was off.
+    pub tm_writes_skipped: usize,
+    /// TM saves made while the AI stage was still running.
+    pub tm_flushes: usize,
+    /// Entries sent to the AI that came back without a translation, in
+    /// entry order; with `blank_failures` their translation is cleared.
+    pub needs_human: Vec<String>,
+    /// Translatable entries left alone because they did not match the filter.
+    pub filtered_out: usize,
+    /// Translations over their `max_chars`, left `InProgress` and kept out
+    /// of the TM.
+    pub held_over_length: usize,
 }
 
 pub fn run(entries: &mut [CoreEntry], cfg: PipelineConfig) -> Result<PipelineReport, String> {
-    let mut tm_entries = store::load();
+    let (source_lang, target_lang) = (cfg.ai.source_lang, cfg.ai.target_lang);
+    let max_chars = cfg.ai.max_chars;
+    // Without `write_tm` the store file is left exactly as it was.
+    let tm_entries = if cfg.write_tm { store::load_with(cfg.tm_dedup) } else { store::peek_with(cfg.tm_dedup) };
+    // AI translations to add to the TM once the run is over.
+    let mut learned: Vec<TMEntry> = Vec::new();
 
     let mut used_tm = 0usize;
+    let mut used_fuzzy = 0usize;
+    let mut trivial = 0usize;
+    let mut skipped_nontext = 0usize;
+    let mut items: Vec<PipelineItem> = Vec::new();
 
     let mut ai_needed: Vec<usize> = Vec::new();
 
+    let selected: Option<HashSet<usize>> = cfg
+        .filter
+        .as_ref()
+        .map(|q| query::matching_indices(entries, q).into_iter().collect());
+    let mut filtered_out = 0usize;
+
     for (i, e) in entries.iter_mut().enumerate() {
         if !e.is_translatable {
             continue;
         }
+        if selected.as_ref().is_some_and(|set| !set.contains(&i)) {
+            filtered_out += 1;
+            continue;
+        }
+
+        if cfg.ai.trivial.is_trivial(&e.original) {
+            trivial::copy_original(e);
+            trivial += 1;
+            items.push(PipelineItem {
+                entry_id: e.entry_id.clone(),
+                tier: MatchTier::Trivial,
+                ratio: None,
+            });
+            continue;
+        }
+
+        if cfg.ai.nontext.is_nontext(&e.original) {
+            trivial::copy_original(e);
+            skipped_nontext += 1;
+            items.push(PipelineItem {
+                entry_id: e.entry_id.clone(),
+                tier: MatchTier::Nontext,
+                ratio: None,
+            });
+            continue;
+        }
 
         if let Some(tm) =
-            matcher::exact_match(&tm_entries, cfg.source_lang, cfg.target_lang, &e.original)
+            matcher::exact_match(&tm_entries, source_lang, target_lang, &e.original)
         {
-            e.translation = tm.translation.clone();
+            e.set_translation(tm.translation.clone());
             e.status = EntryStatus::Translated;
             used_tm += 1;
-        } else {
-            ai_needed.push(i);
+            items.push(PipelineItem {
+                entry_id: e.entry_id.clone(),
+                tier: MatchTier::Exact,
+                ratio: None,
+            });
+            continue;
+        }
+
+        if let Some((tm, ratio)) = matcher::fuzzy_match(
+            &tm_entries,
+            source_lang,
+            target_lang,
+            &e.original,
+            cfg.fuzzy_review_threshold,
+        ) {
+            let tier = if ratio >= cfg.fuzzy_auto_threshold {
+                MatchTier::FuzzyAuto
+            } else {
+                MatchTier::FuzzyReview
+            };
+
+            e.set_translation(tm.translation.clone());
+            e.status = if tier == MatchTier::FuzzyAuto {
+                EntryStatus::Translated
+            } else {
+                EntryStatus::InProgress
+            };
+            used_fuzzy += 1;
+            items.push(PipelineItem {
+                entry_id: e.entry_id.clone(),
+                tier,
+                ratio: Some(ratio),
+            });
+            continue;
         }
+
+        ai_needed.push(i);
     }
 
+    log_info!(
+        "pipeline",
+        "tm exact hits: {}, fuzzy hits: {}, sent to ai: {}",
+        used_tm,
+        used_fuzzy,
+        ai_needed.len()
+    );
+
     let mut ai_report: Option<AiRunReport> = None;
     let mut used_ai = 0usize;
+    let mut tm_writes_skipped = 0usize;
+    let mut tm_flushes = 0usize;
+    let mut needs_human: Vec<String> = Vec::new();
 
     if !ai_needed.is_empty() {
-        let mut slice: Vec<CoreEntry> = ai_needed.iter().map(|&i| entries[i].clone()).collect();
+        let (write_tm, tm_dedup, flush_every) = (cfg.write_tm, cfg.tm_dedup, cfg.tm_flush_every);
+        // Entries already considered for a mid-run flush, and those learned
+        // but not yet saved.
+        let mut flushed: HashSet<usize> = HashSet::new();
+        let mut unflushed: Vec<TMEntry> = Vec::new();
+        let mut seen = 0usize;
 
-        let cfg_ai = ai::AiConfig {
-            provider: cfg.provider,
-            api_key: cfg.api_key,
-            model: cfg.model,
-            source_lang: cfg.source_lang,
-            target_lang: cfg.target_lang,
-        };
+        let report = ai::translate_indices_with(entries, &ai_needed, cfg.ai, |entries, report| {
+            if !write_tm || flush_every == 0 {
+                return;
+            }
+            for item in report.items[seen..].iter().filter(|item| item.ok) {
+                let e = &entries[item.index];
+                if !e.translation.trim().is_empty() && qa::over_length(e, max_chars).is_none() {
+                    unflushed.push(tm_entry(e, source_lang, target_lang));
+                }
+                flushed.insert(item.index);
+            }
+            seen = report.items.len();
 
-        let report = ai::translate_entries(&mut slice, cfg_ai)?;
+            if unflushed.len() >= flush_every {
+                match store::update_with(tm_dedup, |tm| tm.append(&mut unflushed)) {
+                    Ok(()) => tm_flushes += 1,
+                    Err(e) => log_warn!("pipeline", "tm flush failed, retrying at the end: {e}"),
+                }
+            }
+        })?;
+        learned.append(&mut unflushed);
 
-        let mut ok_by_id: HashMap<String, bool> = HashMap::new();
+        let mut ok_by_index: HashMap<usize, bool> = HashMap::new();
         for item in &report.items {
-            ok_by_id.insert(item.entry_id.clone(), item.ok);
+            ok_by_index.insert(item.index, item.ok);
         }
 
-        for (&idx, translated) in ai_needed.iter().zip(slice.into_iter()) {
+        for &idx in &ai_needed {
             let target = &mut entries[idx];
 
-            let ok = ok_by_id.get(&translated.entry_id).copied().unwrap_or(false);
+            items.push(PipelineItem {
+                entry_id: target.entry_id.clone(),
+                tier: MatchTier::Ai,
+                ratio: None,
+            });
 
-            if ok && !translated.translation.trim().is_empty() {
-                target.translation = translated.translation.clone();
+            let ok = ok_by_index.get(&idx).copied().unwrap_or(false);
+            if !ok {
+                log_debug!("pipeline", "ai failed for {}", target.entry_id);
+            }
+
+            if ok && !target.translation.trim().is_empty() {
                 target.status = EntryStatus::Translated;
                 used_ai += 1;
 
-                let norm = normalize::normalize(&target.original);
-                let h = hash::hash_norm(&norm);
-
-                tm_entries.push(TMEntry {
-                    source_lang: cfg.source_lang.to_string(),
-                    target_lang: cfg.target_lang.to_string(),
-                    original: target.original.clone(),
-                    translation: target.translation.clone(),
-                    normalized: norm,
-                    hash: h,
-                });
+                // Held for review below; not worth remembering as is.
+                if qa::over_length(target, max_chars).is_some() {
+                    continue;
+                }
+                if !cfg.write_tm {
+                    tm_writes_skipped += 1;
+                    continue;
+                }
+                if !flushed.contains(&idx) {
+                    learned.push(tm_entry(target, source_lang, target_lang));
+                }
             } else {
+                needs_human.push(target.entry_id.clone());
+                if cfg.blank_failures {
+                    target.set_translation(String::new());
+                }
                 if target.translation.trim().is_empty() {
                     target.status = EntryStatus::Untranslated;
                 } else {
@@ -99,11 +279,200 @@ pub fn run(entries: &mut [CoreEntry], cfg: PipelineConfig) -> Result<PipelineRep
         ai_report = Some(report);
     }
 
-    store::save(&tm_entries)?;
+    // TM hits can be too long for the box as well.
+    let handled: Vec<usize> = (0..entries.len())
+        .filter(|&i| entries[i].is_translatable && selected.as_ref().is_none_or(|set| set.contains(&i)))
+        .collect();
+    let held_over_length = qa::hold_over_length(entries, handled, max_chars);
+
+    // Merged into a fresh read, so entries other processes saved during the
+    // AI run are kept.
+    if cfg.write_tm {
+        store::update_with(cfg.tm_dedup, |tm| tm.extend(learned))?;
+    } else {
+        log_info!("pipeline", "write_tm off: {} tm write(s) skipped", tm_writes_skipped);
+    }
 
     Ok(PipelineReport {
         used_tm,
+        used_fuzzy,
         used_ai,
+        trivial,
+        skipped_nontext,
+        items,
         ai_report,
+        tm_writes_skipped,
+        tm_flushes,
+        needs_human,
+        filtered_out,
+        held_over_length,
     })
 }
+
+fn tm_entry(e: &CoreEntry, source_lang: &str, target_lang: &str) -> TMEntry {
+    let norm = normalize::normalize(&e.original);
+    let h = hash::hash_norm(&norm);
+
+    TMEntry {
+        source_lang: source_lang.to_string(),
+        target_lang: target_lang.to_string(),
+        original: e.original.clone(),
+        translation: e.translation.clone(),
+        normalized: norm,
+        hash: h,
+        source: TmSource::Ai,
+        created_at: store::unix_now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::translation_memory::seed;
+    use std::fs;
+
+    const SEEDED: &str = "abcdefghijklmnopqrst";
+
+    fn line(entry_id: &str, original: &str) -> CoreEntry {
+        serde_json::from_value(serde_json::json!({ "entry_id": entry_id, "original": original, "is_translatable": true }))
+            .unwrap()
+    }
+
+    fn config() -> PipelineConfig<'static> {
+        PipelineConfig {
+            ai: ai::AiConfig::mock(),
+            fuzzy_auto_threshold: 0.9,
+            fuzzy_review_threshold: 0.75,
+            tm_dedup: DedupStrategy::default(),
+            write_tm: true,
+            tm_flush_every: 0,
+            blank_failures: false,
+            filter: None,
+        }
+    }
+
+    fn seed_tm() {
+        let mut done = line("1-text", SEEDED);
+        done.translation = "vinte letras".into();
+        done.status = EntryStatus::Translated;
+        seed::seed_from_entries(&[done], "ja", "pt-BR", DedupStrategy::default()).unwrap();
+    }
+
+    #[test]
+    fn each_tier_starts_at_its_threshold() {
+        store::with_temp_tm("pipeline-tiers", |_| {
+            seed_tm();
+            // 20 characters: one edit is a 0.95 match, four are 0.8, ten 0.5.
+            let mut entries = vec![
+                line("1-text", SEEDED),
+                line("2-text", "abcdefghijklmnopqrsz"),
+                line("3-text", "abcdefghijklmnopwxyz"),
+                line("4-text", "abcdefghijuvwxyzuvwx"),
+            ];
+
+            let report = run(&mut entries, config()).unwrap();
+            let tiers: Vec<(MatchTier, Option<f32>)> = report.items.iter().map(|i| (i.tier, i.ratio)).collect();
+            assert_eq!(
+                tiers,
+                [
+                    (MatchTier::Exact, None),
+                    (MatchTier::FuzzyAuto, Some(0.95)),
+                    (MatchTier::FuzzyReview, Some(0.8)),
+                    (MatchTier::Ai, None),
+                ]
+            );
+            let statuses: Vec<EntryStatus> = entries.iter().map(|e| e.status).collect();
+            assert_eq!(
+                statuses,
+                [EntryStatus::Translated, EntryStatus::Translated, EntryStatus::InProgress, EntryStatus::Translated]
+            );
+            assert_eq!(entries[2].translation, "vinte letras");
+            assert_eq!((report.used_tm, report.used_fuzzy, report.used_ai), (1, 2, 1));
+        });
+    }
+
+    #[test]
+    fn read_only_tm_is_left_untouched() {
+        store::with_temp_tm("pipeline-read-only", |dir| {
+            seed_tm();
+            let before = fs::read(dir.join("translation_memory.json")).unwrap();
+            let mut entries = vec![line("1-text", SEEDED), line("2-text", "新しい行")];
+
+            let report = run(&mut entries, PipelineConfig { write_tm: false, ..config() }).unwrap();
+            assert_eq!((report.used_tm, report.used_ai, report.tm_writes_skipped), (1, 1, 1));
+            assert_eq!(fs::read(dir.join("translation_memory.json")).unwrap(), before);
+        });
+    }
+
+    #[test]
+    fn filter_covers_tm_and_ai_stages() {
+        store::with_temp_tm("pipeline-filter", |_| {
+            seed_tm();
+            let mut by_ana = line("2-text", "新しい行");
+            by_ana.speaker = Some("Ana".into());
+            let mut entries = vec![line("1-text", SEEDED), by_ana, line("3-text", "別の行")];
+            let only_ana = EntryQuery { speaker: Some("Ana".into()), ..EntryQuery::default() };
+
+            let report = run(&mut entries, PipelineConfig { filter: Some(only_ana), ..config() }).unwrap();
+            assert_eq!((report.used_tm, report.used_ai, report.filtered_out), (0, 1, 2));
+            assert_eq!(entries[0].translation, "");
+            assert_eq!(entries[1].translation, "[pt-BR] 新しい行");
+        });
+    }
+
+    #[test]
+    fn mock_translations_flow_into_the_tm() {
+        store::with_temp_tm("pipeline-mock", |_| {
+            let mut entries = vec![line("1-text", "新しい行")];
+            let report = run(&mut entries, config()).unwrap();
+            assert_eq!(report.used_ai, 1);
+            assert_eq!(entries[0].translation, "[pt-BR] 新しい行");
+
+            let learned = matcher::exact_match(&store::peek(), "ja", "pt-BR", "新しい行").cloned().unwrap();
+            assert_eq!((learned.translation.as_str(), learned.source), ("[pt-BR] 新しい行", TmSource::Ai));
+
+            let mut entries = vec![line("1-text", "新しい行")];
+            let report = run(&mut entries, config()).unwrap();
+            assert_eq!((report.used_tm, report.used_ai), (1, 0));
+        });
+    }
+
+    #[test]
+    fn learned_translations_are_flushed_during_the_run() {
+        store::with_temp_tm("pipeline-flush", |_| {
+            let mut entries: Vec<CoreEntry> =
+                (1..=10).map(|n| line(&format!("{n}-text"), &format!("台詞その{n}"))).collect();
+
+            let report = run(&mut entries, PipelineConfig { tm_flush_every: 5, ..config() }).unwrap();
+            assert_eq!((report.used_ai, report.tm_flushes), (10, 2));
+            assert_eq!(store::peek().len(), 10);
+        });
+    }
+
+    #[test]
+    fn ai_failures_need_a_human() {
+        store::with_temp_tm("pipeline-failures", |_| {
+            let failing = || PipelineConfig {
+                ai: ai::AiConfig { mock: ai::MockOptions { latency_ms: 0, failure_rate: 1.0 }, ..ai::AiConfig::mock() },
+                ..config()
+            };
+            let fresh = || {
+                let mut draft = line("1-text", "一");
+                draft.translation = "um?".into();
+                draft.status = EntryStatus::InProgress;
+                vec![draft, line("2-text", "二")]
+            };
+
+            let mut entries = fresh();
+            let report = run(&mut entries, failing()).unwrap();
+            assert_eq!(report.needs_human, ["1-text", "2-text"]);
+            assert_eq!((entries[0].translation.as_str(), entries[0].status), ("um?", EntryStatus::InProgress));
+            assert_eq!(entries[1].status, EntryStatus::Untranslated);
+
+            let mut entries = fresh();
+            run(&mut entries, PipelineConfig { blank_failures: true, ..failing() }).unwrap();
+            assert_eq!((entries[0].translation.as_str(), entries[0].status), ("", EntryStatus::Untranslated));
+            assert!(store::peek().is_empty());
+        });
+    }
+}
@@ -1,15 +1,46 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use serde::Serialize;
+
+use crate::log_info;
 use crate::model::project::ProjectInfo;
+use crate::services::encoding;
+use crate::services::fsutil::write_atomic;
 
-fn projects_base_dir() -> PathBuf {
+/// Per-user application directory; falls back to the working directory.
+pub fn app_data_dir() -> PathBuf {
     if let Ok(local) = std::env::var("LOCALAPPDATA") {
-        return PathBuf::from(local).join("SekaiTranslator").join("Projects");
+        return PathBuf::from(local).join("SekaiTranslator");
     }
-    std::env::current_dir()
-        .unwrap_or_else(|_| PathBuf::from("."))
-        .join("Projects")
+    std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
+}
+
+/// Runs `f` with `LOCALAPPDATA`, and so every project created, under a fresh
+/// temp dir; tests touching projects hold a shared lock, as the environment
+/// is process-wide.
+#[cfg(test)]
+pub(crate) fn with_temp_app_data<T>(name: &str, f: impl FnOnce(&Path) -> T) -> T {
+    use std::sync::Mutex;
+
+    static LOCK: Mutex<()> = Mutex::new(());
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = std::env::temp_dir().join(format!("sekai-core-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let previous = std::env::var_os("LOCALAPPDATA");
+    std::env::set_var("LOCALAPPDATA", &dir);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(&dir)));
+    match previous {
+        Some(v) => std::env::set_var("LOCALAPPDATA", v),
+        None => std::env::remove_var("LOCALAPPDATA"),
+    }
+    result.unwrap_or_else(|p| std::panic::resume_unwind(p))
+}
+
+fn projects_base_dir() -> PathBuf {
+    app_data_dir().join("Projects")
 }
 
 fn ensure_projects_dir() -> PathBuf {
@@ -108,6 +139,7 @@ pub fn create_project(
     let json = serde_json::to_string_pretty(&project).map_err(|_| "failed to serialize project")?;
 
     fs::write(project_dir.join("project.json"), json).map_err(|_| "failed to write project.json")?;
+    log_info!("project", "created {}", project.project_path);
 
     Ok(project)
 }
@@ -150,6 +182,209 @@ pub fn save_project(mut project: ProjectInfo) -> Result<ProjectInfo, String> {
 
     fs::write(project_dir.join("project.json"), json)
         .map_err(|e| format!("failed to write project.json: {e}"))?;
+    log_info!("project", "saved {}", project.project_path);
+
+    Ok(project)
+}
+
+/// Changes the display name in `project.json`.
+///
+/// The directory stays where it is unless `move_dir` is set; then it is
+/// renamed to the sanitized new name, which only happens for projects that
+/// live directly under the projects dir and never over an existing one.
+pub fn rename_project(project_path: String, new_name: String, move_dir: bool) -> Result<ProjectInfo, String> {
+    let new_name = new_name.trim().to_string();
+    if new_name.is_empty() {
+        return Err("project name must not be empty".into());
+    }
+
+    let mut project = open_project(project_path.clone())?;
+    let mut project_dir = PathBuf::from(&project_path);
+
+    if move_dir {
+        let base = ensure_projects_dir();
+        let base = base.canonicalize().map_err(|e| format!("failed to resolve projects dir: {e}"))?;
+        let current = project_dir
+            .canonicalize()
+            .map_err(|e| format!("failed to resolve project directory: {e}"))?;
+
+        if current.parent() != Some(base.as_path()) {
+            return Err("project is not inside the projects directory".into());
+        }
+
+        let target = base.join(safe_project_dir_name(&new_name));
+        if target != current {
+            if target.exists() {
+                return Err("a project with that name already exists".into());
+            }
+            fs::rename(&current, &target).map_err(|e| format!("failed to move project directory: {e}"))?;
+            log_info!("project", "moved {} -> {}", current.display(), target.display());
+        }
+        project_dir = target;
+    }
+
+    project.name = new_name;
+    project.project_path = project_dir.to_string_lossy().to_string();
+
+    let json = serde_json::to_string_pretty(&project).map_err(|e| format!("failed to serialize project: {e}"))?;
+    fs::write(project_dir.join("project.json"), json)
+        .map_err(|e| format!("failed to write project.json: {e}"))?;
+    log_info!("project", "renamed {} to {:?}", project.project_path, project.name);
 
     Ok(project)
 }
+
+#[derive(Debug, Serialize)]
+pub struct FileConversion {
+    pub path: String,
+    /// Characters the new encoding cannot represent; written as `?`.
+    pub unmappable: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EncodingChange {
+    pub project: ProjectInfo,
+    pub previous_encoding: String,
+    /// False when a lossy file blocked the change; then neither the project
+    /// nor any file was touched.
+    pub applied: bool,
+    pub files: Vec<FileConversion>,
+}
+
+/// Sets the project encoding, first re-encoding `files` (relative to the
+/// game root unless absolute) from the old encoding to the new one when
+/// given. Every file is converted in memory before any is written; if one
+/// would lose characters and `allow_lossy` is off, nothing changes and the
+/// report says which files and characters were at fault.
+pub fn set_encoding(
+    project_path: String,
+    new_encoding: &str,
+    files: &[String],
+    allow_lossy: bool,
+) -> Result<EncodingChange, String> {
+    let to = encoding::resolve(new_encoding).ok_or_else(|| format!("unsupported encoding: {new_encoding}"))?;
+    let mut project = open_project(project_path.clone())?;
+    let previous_encoding = project.encoding.clone();
+    let from = encoding::resolve(&previous_encoding)
+        .ok_or_else(|| format!("project encoding is unsupported: {previous_encoding}"))?;
+
+    let mut converted: Vec<(PathBuf, Vec<u8>)> = Vec::with_capacity(files.len());
+    let mut reports: Vec<FileConversion> = Vec::with_capacity(files.len());
+    for f in files {
+        let path = Path::new(&project.root_path).join(f);
+        let conversion = encoding::convert_file(&path, from, to)?;
+        reports.push(FileConversion {
+            path: f.clone(),
+            unmappable: conversion.unmappable,
+        });
+        converted.push((path, conversion.bytes));
+    }
+
+    if !allow_lossy && reports.iter().any(|r| !r.unmappable.is_empty()) {
+        return Ok(EncodingChange {
+            project,
+            previous_encoding,
+            applied: false,
+            files: reports,
+        });
+    }
+
+    for (path, bytes) in &converted {
+        write_atomic(path, bytes).map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+    }
+
+    project.encoding = new_encoding.trim().to_string();
+    let json = serde_json::to_string_pretty(&project).map_err(|e| format!("failed to serialize project: {e}"))?;
+    fs::write(Path::new(&project_path).join("project.json"), json)
+        .map_err(|e| format!("failed to write project.json: {e}"))?;
+    log_info!(
+        "project",
+        "encoding of {} changed from {:?} to {:?} ({} files converted)",
+        project_path,
+        previous_encoding,
+        project.encoding,
+        converted.len()
+    );
+
+    Ok(EncodingChange {
+        project,
+        previous_encoding,
+        applied: true,
+        files: reports,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create(name: &str, root: &Path, encoding: &str) -> ProjectInfo {
+        create_project(
+            name.into(),
+            root.to_string_lossy().into(),
+            encoding.into(),
+            "kirikiri".into(),
+            "kirikiri".into(),
+            "ja".into(),
+            "pt-BR".into(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn lossy_encoding_change_touches_nothing() {
+        with_temp_app_data("project-encoding", |dir| {
+            let project = create("Enc", dir, "utf-8");
+            fs::write(dir.join("a.ks"), "こんにちは").unwrap();
+            fs::write(dir.join("b.ks"), "coração").unwrap();
+            let files = vec!["a.ks".to_string(), "b.ks".to_string()];
+
+            let blocked = set_encoding(project.project_path.clone(), "cp932", &files, false).unwrap();
+            assert!(!blocked.applied);
+            assert_eq!(blocked.files[1].unmappable, ["ç", "ã"]);
+            assert_eq!(fs::read_to_string(dir.join("a.ks")).unwrap(), "こんにちは");
+            assert_eq!(open_project(project.project_path.clone()).unwrap().encoding, "utf-8");
+
+            let applied = set_encoding(project.project_path.clone(), "cp932", &files[..1], false).unwrap();
+            assert!(applied.applied);
+            assert_eq!(fs::read(dir.join("a.ks")).unwrap(), encoding_rs::SHIFT_JIS.encode("こんにちは").0.into_owned());
+            assert_eq!(open_project(project.project_path).unwrap().encoding, "cp932");
+        });
+    }
+
+    #[test]
+    fn reconverting_to_utf16_writes_utf16() {
+        with_temp_app_data("project-utf16", |dir| {
+            let project = create("Wide", dir, "utf-8");
+            fs::write(dir.join("a.ks"), "coração、こんにちは").unwrap();
+            let files = vec!["a.ks".to_string()];
+
+            let change = set_encoding(project.project_path.clone(), "utf-16le", &files, false).unwrap();
+            assert!(change.applied && change.files[0].unmappable.is_empty());
+            let bytes = fs::read(dir.join("a.ks")).unwrap();
+            let expected: Vec<u8> = "coração、こんにちは".encode_utf16().flat_map(u16::to_le_bytes).collect();
+            assert_eq!(bytes, [&[0xFF, 0xFE][..], &expected].concat());
+
+            set_encoding(project.project_path, "utf-8", &files, false).unwrap();
+            assert_eq!(fs::read_to_string(dir.join("a.ks")).unwrap(), "coração、こんにちは");
+        });
+    }
+
+    #[test]
+    fn rename_moves_the_directory_only_when_asked() {
+        with_temp_app_data("project-rename", |dir| {
+            let project = create("Old", dir, "utf-8");
+            let renamed = rename_project(project.project_path.clone(), "New name".into(), false).unwrap();
+            assert_eq!(renamed.project_path, project.project_path);
+            assert_eq!(open_project(project.project_path.clone()).unwrap().name, "New name");
+
+            create("Taken", dir, "utf-8");
+            assert!(rename_project(project.project_path.clone(), "Taken".into(), true).is_err());
+
+            let moved = rename_project(project.project_path.clone(), "Moved".into(), true).unwrap();
+            assert!(moved.project_path.ends_with("Moved"));
+            assert!(!Path::new(&project.project_path).exists());
+            assert!(rename_project(moved.project_path, " ".into(), false).is_err());
+        });
+    }
+}
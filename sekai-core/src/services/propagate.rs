@@ -0,0 +1,149 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde::Serialize;
+
+use crate::model::entry::{CoreEntry, EntryStatus};
+use crate::services::translation_memory::normalize;
+
+#[derive(Debug, Serialize)]
+pub struct FillReport {
+    /// Untranslated targets that received a translation.
+    pub matched: usize,
+    /// Untranslated targets with no counterpart in the source set.
+    pub unmatched: usize,
+}
+
+/// Copies translations from `source` into untranslated entries of `target`
+/// whose normalized original matches, marking them `InProgress` for review.
+///
+/// Both sets are expected to share a language pair. When a source original
+/// appears more than once, a `Reviewed` translation wins, then the first seen.
+/// Nothing is written to the persisted TM.
+pub fn fill_untranslated_from(source: &[CoreEntry], target: &mut [CoreEntry]) -> FillReport {
+    let mut by_norm: HashMap<String, &CoreEntry> = HashMap::new();
+    for e in source.iter().filter(|e| e.is_translatable && !e.translation.trim().is_empty()) {
+        let slot = by_norm.entry(normalize::normalize(&e.original)).or_insert(e);
+        if slot.status != EntryStatus::Reviewed && e.status == EntryStatus::Reviewed {
+            *slot = e;
+        }
+    }
+
+    let mut report = FillReport { matched: 0, unmatched: 0 };
+
+    for e in target.iter_mut() {
+        if !e.is_translatable || !e.translation.trim().is_empty() {
+            continue;
+        }
+
+        match by_norm.get(&normalize::normalize(&e.original)) {
+            Some(src) => {
+                e.set_translation(src.translation.clone());
+                e.status = EntryStatus::InProgress;
+                report.matched += 1;
+            }
+            None => report.unmatched += 1,
+        }
+    }
+
+    report
+}
+
+#[derive(Debug, Serialize)]
+pub struct MapImportReport {
+    /// Entries that received a translation.
+    pub applied: usize,
+    /// `by_id` keys naming no translatable entry.
+    pub unmatched_ids: Vec<String>,
+    /// `by_original` keys whose original appears on no translatable entry.
+    pub unmatched_originals: Vec<String>,
+}
+
+/// Applies externally produced translations, keyed by `entry_id` and/or by
+/// original (compared normalized, so every line with that original gets
+/// it). An ID match wins over an original match for the same entry. A blank
+/// translation marks the entry `Untranslated`, anything else `Translated`.
+pub fn apply_map(
+    entries: &mut [CoreEntry],
+    by_id: &HashMap<String, String>,
+    by_original: &HashMap<String, String>,
+) -> MapImportReport {
+    let by_norm: HashMap<String, (&str, &str)> = by_original
+        .iter()
+        .map(|(k, v)| (normalize::normalize(k), (k.as_str(), v.as_str())))
+        .collect();
+
+    let mut unmatched_ids: BTreeSet<&str> = by_id.keys().map(String::as_str).collect();
+    let mut unmatched_originals: BTreeSet<&str> = by_original.keys().map(String::as_str).collect();
+    let mut applied = 0usize;
+
+    for e in entries.iter_mut().filter(|e| e.is_translatable) {
+        let id_hit = by_id.get(&e.entry_id).map(String::as_str);
+        let original_hit = by_norm.get(&normalize::normalize(&e.original)).copied();
+
+        if id_hit.is_some() {
+            unmatched_ids.remove(e.entry_id.as_str());
+        }
+        if let Some((key, _)) = original_hit {
+            unmatched_originals.remove(key);
+        }
+        let Some(translation) = id_hit.or(original_hit.map(|(_, t)| t)) else { continue };
+
+        e.set_translation(translation.to_string());
+        e.status = if translation.trim().is_empty() { EntryStatus::Untranslated } else { EntryStatus::Translated };
+        applied += 1;
+    }
+
+    MapImportReport {
+        applied,
+        unmatched_ids: unmatched_ids.into_iter().map(str::to_string).collect(),
+        unmatched_originals: unmatched_originals.into_iter().map(str::to_string).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(entry_id: &str, original: &str, translation: &str) -> CoreEntry {
+        serde_json::from_value(serde_json::json!({
+            "entry_id": entry_id,
+            "original": original,
+            "translation": translation,
+            "is_translatable": true,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn fill_prefers_reviewed_and_marks_in_progress() {
+        let mut source = vec![entry("1-text", "はい", "Sim"), entry("2-text", "はい ", "Isso")];
+        source[1].status = EntryStatus::Reviewed;
+        let mut target = vec![entry("1-text", "はい", ""), entry("2-text", "いいえ", ""), entry("3-text", "はい", "Já feito")];
+
+        let report = fill_untranslated_from(&source, &mut target);
+        assert_eq!((report.matched, report.unmatched), (1, 1));
+        assert_eq!(target[0].translation, "Isso");
+        assert_eq!(target[0].status, EntryStatus::InProgress);
+        assert_eq!(target[2].translation, "Já feito");
+    }
+
+    #[test]
+    fn id_beats_original_and_unmatched_keys_are_listed() {
+        let mut entries = vec![entry("1-text", "はい", ""), entry("2-text", "はい", ""), entry("3-text", "いいえ", "Não")];
+        let by_id = HashMap::from([("1-text".to_string(), "Sim".to_string()), ("8-text".to_string(), "x".to_string())]);
+        let by_original = HashMap::from([
+            ("はい".to_string(), "Pois é".to_string()),
+            ("いいえ".to_string(), " ".to_string()),
+            ("ない".to_string(), "y".to_string()),
+        ]);
+
+        let report = apply_map(&mut entries, &by_id, &by_original);
+        assert_eq!(report.applied, 3);
+        assert_eq!(report.unmatched_ids, ["8-text"]);
+        assert_eq!(report.unmatched_originals, ["ない"]);
+        assert_eq!(entries[0].translation, "Sim");
+        assert_eq!(entries[1].translation, "Pois é");
+        assert_eq!(entries[2].status, EntryStatus::Untranslated);
+        assert_eq!(entries[2].history[0].translation, "Não");
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::services::project;
+use crate::{log_info, log_warn};
+
+const PROVIDERS_FILE: &str = "providers.json";
+const DEFAULT_RESPONSE_PATH: &str = "choices.0.message.content";
+const OPENROUTER_REFERER: &str = "https://github.com/Satonix/SekaiTranslatorV";
+const OPENROUTER_TITLE: &str = "SekaiTranslatorV";
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthStyle {
+    /// `Authorization: Bearer <key>`
+    #[default]
+    Bearer,
+    /// `x-api-key: <key>`
+    XApiKey,
+    /// No credentials are sent (local gateways).
+    None,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestFormat {
+    /// OpenAI chat-completions body.
+    #[default]
+    Openai,
+    /// Answered in-process without network; for UI tests and demos.
+    Mock,
+}
+
+/// How the "previous lines, do not translate" block is marked up; models
+/// differ in which they respect.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextFormat {
+    /// A `Context (...):` label followed by the lines (GPT-style models).
+    #[default]
+    Labeled,
+    /// Lines wrapped in `<context>...</context>` (Claude-style models).
+    Xml,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ProviderSpec {
+    pub endpoint: String,
+
+    #[serde(default)]
+    pub auth_style: AuthStyle,
+
+    #[serde(default)]
+    pub request_format: RequestFormat,
+
+    /// Dot-separated path to the translated text in the response JSON;
+    /// numeric segments index arrays.
+    #[serde(default = "default_response_path")]
+    pub response_path: String,
+
+    /// Ask for `stream: true`; for gateways that only answer with SSE.
+    #[serde(default)]
+    pub stream: bool,
+
+    /// Used when a request names no model.
+    #[serde(default)]
+    pub default_model: Option<String>,
+
+    /// Models the provider is known to serve; empty means any name is passed
+    /// through unchecked.
+    #[serde(default)]
+    pub models: Vec<String>,
+
+    /// Extra headers sent with every request, e.g. OpenRouter's app
+    /// attribution. Never put credentials here; `auth_style` carries the key.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub context_format: ContextFormat,
+
+    /// Accepts `response_format: {"type": "json_object"}`, so batches can be
+    /// answered as JSON instead of numbered lines.
+    #[serde(default)]
+    pub json_mode: bool,
+}
+
+impl ProviderSpec {
+    /// The model to request: `requested`, or the default when it is empty.
+    pub fn resolve_model<'a>(&'a self, provider: &str, requested: &'a str) -> Result<&'a str, String> {
+        let model = match requested.trim() {
+            "" => self
+                .default_model
+                .as_deref()
+                .ok_or_else(|| format!("payload.model is required for provider {provider}"))?,
+            m => m,
+        };
+
+        if !self.models.is_empty() && !self.models.iter().any(|m| m == model) {
+            return Err(format!(
+                "unknown model {model} for provider {provider}; expected one of: {}",
+                self.models.join(", ")
+            ));
+        }
+        Ok(model)
+    }
+}
+
+fn default_response_path() -> String {
+    DEFAULT_RESPONSE_PATH.to_string()
+}
+
+fn builtin(endpoint: &str, default_model: &str, models: &[&str]) -> ProviderSpec {
+    ProviderSpec {
+        endpoint: endpoint.to_string(),
+        auth_style: AuthStyle::Bearer,
+        request_format: RequestFormat::Openai,
+        response_path: default_response_path(),
+        stream: false,
+        default_model: Some(default_model.to_string()),
+        models: models.iter().map(|m| m.to_string()).collect(),
+        headers: BTreeMap::new(),
+        context_format: ContextFormat::Labeled,
+        json_mode: true,
+    }
+}
+
+/// Built-in providers overlaid with `providers.json` from the app data dir.
+/// Read once per process; entries in the file win over built-ins.
+fn registry() -> &'static HashMap<String, ProviderSpec> {
+    static REGISTRY: OnceLock<HashMap<String, ProviderSpec>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut map: HashMap<String, ProviderSpec> = HashMap::new();
+        // OpenAI's catalogue changes too often to check names against.
+        map.insert(
+            "openai".into(),
+            builtin("https://api.openai.com/v1/chat/completions", "gpt-4o-mini", &[]),
+        );
+        map.insert(
+            "deepseek".into(),
+            builtin(
+                "https://api.deepseek.com/v1/chat/completions",
+                "deepseek-chat",
+                &["deepseek-chat", "deepseek-reasoner"],
+            ),
+        );
+        // OpenAI-compatible; models are routed by `vendor/model` names. The
+        // app headers can be replaced by an `openrouter` entry in providers.json.
+        map.insert(
+            "openrouter".into(),
+            ProviderSpec {
+                headers: BTreeMap::from([
+                    ("HTTP-Referer".into(), OPENROUTER_REFERER.into()),
+                    ("X-Title".into(), OPENROUTER_TITLE.into()),
+                ]),
+                // Support depends on the routed model.
+                json_mode: false,
+                ..builtin("https://openrouter.ai/api/v1/chat/completions", "openai/gpt-4o-mini", &[])
+            },
+        );
+        map.insert(
+            "mock".into(),
+            ProviderSpec {
+                auth_style: AuthStyle::None,
+                request_format: RequestFormat::Mock,
+                ..builtin("mock://", "mock", &[])
+            },
+        );
+
+        let path = project::app_data_dir().join(PROVIDERS_FILE);
+        if path.exists() {
+            match fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|s| serde_json::from_str::<HashMap<String, ProviderSpec>>(&s).map_err(|e| e.to_string()))
+            {
+                Ok(custom) => {
+                    log_info!("providers", "loaded {} provider(s) from {}", custom.len(), path.display());
+                    map.extend(custom);
+                }
+                Err(e) => log_warn!("providers", "ignoring {}: {e}", path.display()),
+            }
+        }
+
+        map
+    })
+}
+
+pub fn resolve(provider: &str) -> Result<&'static ProviderSpec, String> {
+    registry()
+        .get(provider)
+        .ok_or_else(|| "Unsupported provider".to_string())
+}
+
+/// Follows a `response_path` such as `choices.0.message.content`.
+pub fn extract<'v>(value: &'v Value, path: &str) -> Option<&'v Value> {
+    path.split('.').filter(|s| !s.is_empty()).try_fold(value, |cur, seg| match seg.parse::<usize>() {
+        Ok(i) if cur.is_array() => cur.get(i),
+        _ => cur.get(seg),
+    })
+}
+
+/// Prefix of the error for replies cut off by the token limit; retrying the
+/// same request cannot help.
+pub const TRUNCATED: &str = "TRUNCATED";
+
+fn truncated_error() -> String {
+    format!("{TRUNCATED}: reply was cut off by the token limit; raise max_tokens")
+}
+
+/// Reads the reply text at `path`. Only the first choice is used when a
+/// provider returns several (`n > 1`). An empty `choices` array and a
+/// `finish_reason` of `length` get their own errors instead of the generic
+/// missing-content one.
+pub fn content(value: &Value, path: &str) -> Result<String, String> {
+    if let Some(choices) = value.get("choices").and_then(|c| c.as_array()) {
+        let Some(first) = choices.first() else {
+            return Err("Invalid AI response: empty choices".into());
+        };
+        if first.get("finish_reason").and_then(|r| r.as_str()) == Some("length") {
+            return Err(truncated_error());
+        }
+    }
+
+    extract(value, path)
+        .and_then(|c| c.as_str())
+        .map(str::to_string)
+        .ok_or_else(|| format!("Invalid AI response: missing {path}"))
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// Reads OpenAI-style `usage`; absent or malformed usage yields `None`.
+pub fn usage(value: &Value) -> Option<Usage> {
+    let u = value.get("usage")?;
+    let prompt_tokens = u.get("prompt_tokens").and_then(|v| v.as_u64());
+    let completion_tokens = u.get("completion_tokens").and_then(|v| v.as_u64());
+    if prompt_tokens.is_none() && completion_tokens.is_none() {
+        return None;
+    }
+    Some(Usage {
+        prompt_tokens: prompt_tokens.unwrap_or(0),
+        completion_tokens: completion_tokens.unwrap_or(0),
+    })
+}
+
+/// Reassembles a `text/event-stream` chat-completions body by concatenating
+/// every `choices.0.delta.content` up to the `[DONE]` sentinel. Usage, when
+/// the gateway reports it, comes on one of the chunks (usually the last).
+pub fn parse_sse(body: &str) -> Result<(String, Option<Usage>), String> {
+    let mut out = String::new();
+    let mut saw_chunk = false;
+    let mut total: Option<Usage> = None;
+    let mut truncated = false;
+
+    for line in body.lines() {
+        let Some(data) = line.strip_prefix("data:") else { continue };
+        let data = data.trim();
+        if data.is_empty() { continue; }
+        if data == "[DONE]" { break; }
+
+        let chunk: Value = serde_json::from_str(data).map_err(|_| "Invalid SSE chunk from AI".to_string())?;
+        if let Some(msg) = extract(&chunk, "error.message").and_then(|m| m.as_str()) {
+            return Err(msg.to_string());
+        }
+        saw_chunk = true;
+        if let Some(u) = usage(&chunk) {
+            total = Some(u);
+        }
+        if let Some(piece) = extract(&chunk, "choices.0.delta.content").and_then(|c| c.as_str()) {
+            out.push_str(piece);
+        }
+        if extract(&chunk, "choices.0.finish_reason").and_then(|r| r.as_str()) == Some("length") {
+            truncated = true;
+        }
+    }
+
+    if !saw_chunk {
+        return Err("Empty SSE stream from AI".into());
+    }
+    if truncated {
+        return Err(truncated_error());
+    }
+    Ok((out, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn custom_provider_fills_in_defaults_and_checks_its_models() {
+        let spec: ProviderSpec = serde_json::from_value(json!({
+            "endpoint": "http://localhost:8080/v1/generate",
+            "auth_style": "none",
+            "response_path": "output.text",
+            "models": ["local-7b"],
+        }))
+        .unwrap();
+        assert_eq!((spec.auth_style, spec.request_format), (AuthStyle::None, RequestFormat::Openai));
+        assert_eq!(spec.resolve_model("local", "local-7b").unwrap(), "local-7b");
+        assert!(spec.resolve_model("local", "gpt-4o").unwrap_err().contains("local-7b"));
+        assert!(spec.resolve_model("local", "").is_err());
+
+        let reply = json!({ "output": { "text": "Olá" }, "choices": [{ "message": { "content": "Oi" } }] });
+        assert_eq!(extract(&reply, &spec.response_path), Some(&json!("Olá")));
+        assert_eq!(extract(&reply, DEFAULT_RESPONSE_PATH), Some(&json!("Oi")));
+
+        assert!(resolve("openai").is_ok());
+        assert!(resolve("nope").is_err());
+    }
+
+    #[test]
+    fn sse_streams_are_reassembled() {
+        let body = concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Bom \"}}]}\n\n",
+            ": keep-alive\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"dia\"}}]}\n\n",
+            "data: [DONE]\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"!\"}}]}\n",
+        );
+        assert_eq!(parse_sse(body).unwrap().0, "Bom dia");
+
+        assert_eq!(parse_sse("data: {\"error\":{\"message\":\"quota\"}}\n").unwrap_err(), "quota");
+        assert_eq!(parse_sse("data: [DONE]\n").unwrap_err(), "Empty SSE stream from AI");
+    }
+
+    #[test]
+    fn usage_needs_at_least_one_count() {
+        let u = usage(&json!({ "usage": { "prompt_tokens": 7 } })).unwrap();
+        assert_eq!((u.prompt_tokens, u.completion_tokens), (7, 0));
+        assert!(usage(&json!({ "usage": {} })).is_none());
+
+        let body = "data: {\"choices\":[{\"delta\":{\"content\":\"Oi\"}}],\"usage\":{\"prompt_tokens\":3,\"completion_tokens\":2}}\n";
+        assert_eq!(parse_sse(body).unwrap().1.unwrap().completion_tokens, 2);
+    }
+
+    #[test]
+    fn builtin_providers_resolve_their_models() {
+        let openai = resolve("openai").unwrap();
+        assert_eq!(openai.resolve_model("openai", " ").unwrap(), "gpt-4o-mini");
+        assert_eq!(openai.resolve_model("openai", "any-model").unwrap(), "any-model");
+
+        let deepseek = resolve("deepseek").unwrap();
+        assert_eq!(deepseek.resolve_model("deepseek", "deepseek-reasoner").unwrap(), "deepseek-reasoner");
+        assert!(deepseek.resolve_model("deepseek", "gpt-4o").unwrap_err().contains("deepseek-chat"));
+
+        let unnamed = ProviderSpec { default_model: None, ..builtin("http://localhost", "", &[]) };
+        assert!(unnamed.resolve_model("local", "").is_err());
+    }
+
+    #[test]
+    fn openrouter_routes_any_vendor_model_with_app_headers() {
+        let openrouter = resolve("openrouter").unwrap();
+        assert_eq!(openrouter.resolve_model("openrouter", "").unwrap(), "openai/gpt-4o-mini");
+        assert_eq!(
+            openrouter.resolve_model("openrouter", "anthropic/claude-3.5-sonnet").unwrap(),
+            "anthropic/claude-3.5-sonnet"
+        );
+        assert_eq!(openrouter.headers["X-Title"], OPENROUTER_TITLE);
+        assert_eq!(openrouter.headers["HTTP-Referer"], OPENROUTER_REFERER);
+        assert!(!openrouter.json_mode);
+    }
+
+    #[test]
+    fn content_reads_the_first_choice_and_reports_cut_replies() {
+        let reply = json!({ "choices": [{ "message": { "content": "Olá" } }, { "message": { "content": "Oi" } }] });
+        assert_eq!(content(&reply, DEFAULT_RESPONSE_PATH).unwrap(), "Olá");
+        assert!(content(&json!({ "choices": [] }), DEFAULT_RESPONSE_PATH).unwrap_err().contains("empty choices"));
+        assert!(content(&json!({}), DEFAULT_RESPONSE_PATH).unwrap_err().contains("missing"));
+
+        let cut = json!({ "choices": [{ "message": { "content": "Ol" }, "finish_reason": "length" }] });
+        assert!(content(&cut, DEFAULT_RESPONSE_PATH).unwrap_err().starts_with(TRUNCATED));
+        let cut = "data: {\"choices\":[{\"delta\":{\"content\":\"Bo\"},\"finish_reason\":\"length\"}]}\n";
+        assert!(parse_sse(cut).unwrap_err().starts_with(TRUNCATED));
+    }
+}
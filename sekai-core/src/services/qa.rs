@@ -1,15 +1,93 @@
 use crate::model::entry::{CoreEntry, EntryStatus};
+use crate::model::glossary::GlossaryTerm;
+use crate::services::glossary;
+use crate::services::nontext::NontextFilter;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct QaIssue {
     pub entry_id: String,
     pub code: String,
+    pub severity: Severity,
     pub message: String,
 }
 
+#[derive(Debug, Serialize, Default)]
+pub struct QaSummary {
+    pub total: usize,
+    pub by_code: BTreeMap<String, usize>,
+    pub by_severity: BTreeMap<Severity, usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QaBatchReport {
+    pub files: BTreeMap<String, Vec<QaIssue>>,
+    pub summary: QaSummary,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct QaOptions {
+    /// Opening/closing characters checked by `UNBALANCED`.
+    pub bracket_pairs: Vec<(char, char)>,
+    /// Originals reported as `PROBABLY_NONTEXT`.
+    #[serde(skip)]
+    pub nontext: NontextFilter,
+    /// Terms checked by `GLOSSARY_MISS`, within their speaker scope.
+    pub glossary: Vec<GlossaryTerm>,
+    /// Length checked by `OVER_LENGTH` for entries without their own.
+    pub max_chars: Option<usize>,
+    /// `SOURCE_LANG_RESIDUE` only runs for targets outside `CJK_LANGS`.
+    pub target_lang: String,
+    /// Loanwords allowed to stay in kana/kanji (`さん`, `先輩`).
+    pub residue_whitelist: Vec<String>,
+    /// Leftover kana/kanji characters tolerated before flagging.
+    pub residue_max_chars: usize,
+}
+
+impl Default for QaOptions {
+    fn default() -> Self {
+        Self {
+            bracket_pairs: vec![('「', '」'), ('『', '』'), ('(', ')'), ('（', '）'), ('"', '"')],
+            nontext: NontextFilter::default(),
+            glossary: Vec::new(),
+            max_chars: None,
+            target_lang: "pt-BR".to_string(),
+            residue_whitelist: Vec::new(),
+            residue_max_chars: DEFAULT_RESIDUE_MAX_CHARS,
+        }
+    }
+}
+
+/// A lone kanji or kana (an emphasis mark, a name kept on purpose) passes.
+pub const DEFAULT_RESIDUE_MAX_CHARS: usize = 1;
+
+/// Primary language subtags whose text is expected to hold kana or Han.
+const CJK_LANGS: &[&str] = &["ja", "zh", "yue", "ko"];
+
 pub fn run(entries: &[CoreEntry]) -> Vec<QaIssue> {
+    run_with(entries, &QaOptions::default())
+}
+
+pub fn run_with(entries: &[CoreEntry], opts: &QaOptions) -> Vec<QaIssue> {
+    let mut issues = entry_issues(entries, opts);
+    issues.extend(glossary_inconsistencies(&[entries], &opts.glossary).into_iter().map(|(_, issue)| issue));
+    issues
+}
+
+/// Checks that look at one entry at a time.
+fn entry_issues(entries: &[CoreEntry], opts: &QaOptions) -> Vec<QaIssue> {
     let mut issues: Vec<QaIssue> = Vec::new();
+    let check_residue = !is_cjk(&opts.target_lang);
 
     for e in entries {
         if !e.is_translatable {
@@ -18,19 +96,110 @@ pub fn run(entries: &[CoreEntry]) -> Vec<QaIssue> {
 
         let original_trim = e.original.trim();
         let translation_trim = e.translation.trim();
+        let nontext = opts.nontext.is_nontext(&e.original);
 
-        if !translation_trim.is_empty() && translation_trim == original_trim {
+        if nontext {
+            issues.push(QaIssue {
+                entry_id: e.entry_id.clone(),
+                code: "PROBABLY_NONTEXT".to_string(),
+                severity: Severity::Info,
+                message: "Parece URL, caminho, versão ou ID; normalmente fica igual ao original".to_string(),
+            });
+        }
+
+        // Non-text lines are expected to stay as they are.
+        if !nontext && !translation_trim.is_empty() && translation_trim == original_trim {
             issues.push(QaIssue {
                 entry_id: e.entry_id.clone(),
                 code: "SAME_AS_ORIGINAL".to_string(),
+                severity: Severity::Warning,
                 message: "Tradução é idêntica ao texto original".to_string(),
             });
         }
 
+        if !translation_trim.is_empty() && edges(&e.translation) != edges(&e.original) {
+            issues.push(QaIssue {
+                entry_id: e.entry_id.clone(),
+                code: "EDGE_WHITESPACE".to_string(),
+                severity: Severity::Warning,
+                message: "Espaços no início/fim da tradução diferem do original; o rebuild usa os do original".to_string(),
+            });
+        }
+
+        // A pair the source leaves open itself (a quote spanning several
+        // lines) only has to stay open by as much in the translation.
+        if !translation_trim.is_empty() {
+            for (open, close) in unbalanced_pairs(&e.original, &e.translation, &opts.bracket_pairs) {
+                issues.push(QaIssue {
+                    entry_id: e.entry_id.clone(),
+                    code: "UNBALANCED".to_string(),
+                    severity: Severity::Warning,
+                    message: format!("Tradução com {open}{close} desbalanceado"),
+                });
+            }
+        }
+
+        if !translation_trim.is_empty() {
+            let (expected, found) = (digit_runs(&e.original), digit_runs(&e.translation));
+            if expected != found {
+                issues.push(QaIssue {
+                    entry_id: e.entry_id.clone(),
+                    code: "NUMBER_MISMATCH".to_string(),
+                    severity: Severity::Warning,
+                    message: format!("Números diferem do original: [{}] → [{}]", expected.join(", "), found.join(", ")),
+                });
+            }
+        }
+
+        if !translation_trim.is_empty() {
+            let translation_lower = translation_trim.to_lowercase();
+            for t in glossary::terms_for(&opts.glossary, e) {
+                if !translation_lower.contains(&t.target.trim().to_lowercase()) {
+                    issues.push(QaIssue {
+                        entry_id: e.entry_id.clone(),
+                        code: "GLOSSARY_MISS".to_string(),
+                        severity: Severity::Warning,
+                        message: format!("Termo \"{}\" deveria ser traduzido como \"{}\"", t.source.trim(), t.target.trim()),
+                    });
+                }
+            }
+        }
+
+        if check_residue && !nontext {
+            let residue = source_residue(&e.translation, &opts.residue_whitelist);
+            if residue.chars().count() > opts.residue_max_chars {
+                issues.push(QaIssue {
+                    entry_id: e.entry_id.clone(),
+                    code: "SOURCE_LANG_RESIDUE".to_string(),
+                    severity: Severity::Warning,
+                    message: format!("Tradução ainda contém caracteres do original: {residue}"),
+                });
+            }
+        }
+
+        if let Some((len, limit)) = over_length(e, opts.max_chars) {
+            issues.push(QaIssue {
+                entry_id: e.entry_id.clone(),
+                code: "OVER_LENGTH".to_string(),
+                severity: Severity::Error,
+                message: format!("Tradução com {len} caracteres excede o limite de {limit}"),
+            });
+        }
+
+        if let Some((found, expected)) = line_count_mismatch(e) {
+            issues.push(QaIssue {
+                entry_id: e.entry_id.clone(),
+                code: "LINE_COUNT_MISMATCH".to_string(),
+                severity: Severity::Error,
+                message: format!("Tradução com {found} linhas; o original ocupa {expected}"),
+            });
+        }
+
         if e.prefix.is_none() && e.suffix.is_none() {
             issues.push(QaIssue {
                 entry_id: e.entry_id.clone(),
                 code: "MISSING_CONTEXT".to_string(),
+                severity: Severity::Warning,
                 message: "Linha traduzível sem prefix/suffix".to_string(),
             });
         }
@@ -39,6 +208,7 @@ pub fn run(entries: &[CoreEntry]) -> Vec<QaIssue> {
             issues.push(QaIssue {
                 entry_id: e.entry_id.clone(),
                 code: "SPEAKER_WITHOUT_TEXT".to_string(),
+                severity: Severity::Warning,
                 message: "Speaker definido mas texto original vazio".to_string(),
             });
         }
@@ -49,6 +219,7 @@ pub fn run(entries: &[CoreEntry]) -> Vec<QaIssue> {
                     issues.push(QaIssue {
                         entry_id: e.entry_id.clone(),
                         code: "STATUS_TRANSLATED_BUT_EMPTY".to_string(),
+                        severity: Severity::Error,
                         message: "Status indica traduzido, mas tradução está vazia".to_string(),
                     });
                 }
@@ -58,6 +229,7 @@ pub fn run(entries: &[CoreEntry]) -> Vec<QaIssue> {
                     issues.push(QaIssue {
                         entry_id: e.entry_id.clone(),
                         code: "STATUS_IN_PROGRESS_BUT_EMPTY".to_string(),
+                        severity: Severity::Warning,
                         message: "Status IN_PROGRESS, mas tradução está vazia".to_string(),
                     });
                 }
@@ -69,3 +241,372 @@ pub fn run(entries: &[CoreEntry]) -> Vec<QaIssue> {
 
     issues
 }
+
+fn is_cjk(lang: &str) -> bool {
+    let primary = lang.trim().split(['-', '_']).next().unwrap_or("").to_lowercase();
+    CJK_LANGS.contains(&primary.as_str())
+}
+
+/// Hiragana, katakana and Han characters left in `translation` once the
+/// `whitelist` words are taken out.
+fn source_residue(translation: &str, whitelist: &[String]) -> String {
+    let mut rest = translation.to_string();
+    for word in whitelist.iter().map(|w| w.trim()).filter(|w| !w.is_empty()) {
+        rest = rest.replace(word, " ");
+    }
+    rest.chars()
+        .filter(|&c| {
+            matches!(c as u32,
+                0x3041..=0x309F | 0x30A1..=0x30FA | 0x30FD..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9D
+                | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x3005)
+        })
+        .collect()
+}
+
+/// What a translated line put where its original has a glossary term.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rendering {
+    /// The term's target.
+    Target,
+    /// The source term, left as it was.
+    Source,
+    /// Neither; some other wording.
+    Other,
+}
+
+fn rendering(translation: &str, term: &GlossaryTerm) -> Rendering {
+    if translation.to_lowercase().contains(&term.target.trim().to_lowercase()) {
+        Rendering::Target
+    } else if translation.contains(term.source.trim()) {
+        Rendering::Source
+    } else {
+        Rendering::Other
+    }
+}
+
+/// Cross-entry pass over every file in `files`: for each glossary term,
+/// what every translated line whose original holds it (within its speaker
+/// scope) put in its place. When those lines do not all agree, each of them
+/// is flagged, whichever side it is on, paired with the index of its file.
+fn glossary_inconsistencies(files: &[&[CoreEntry]], glossary: &[GlossaryTerm]) -> Vec<(usize, QaIssue)> {
+    // term index -> (file index, entry, what it used)
+    let mut uses: BTreeMap<usize, Vec<(usize, &CoreEntry, Rendering)>> = BTreeMap::new();
+    for (fi, entries) in files.iter().enumerate() {
+        for e in entries.iter().filter(|e| e.is_translatable && !e.translation.trim().is_empty()) {
+            for t in glossary::terms_for(glossary, e) {
+                let Some(ti) = glossary.iter().position(|g| std::ptr::eq(g, t)) else {
+                    continue;
+                };
+                uses.entry(ti).or_default().push((fi, e, rendering(&e.translation, t)));
+            }
+        }
+    }
+
+    let mut issues = Vec::new();
+    for (ti, lines) in uses {
+        if lines.iter().all(|&(_, _, r)| r == lines[0].2) {
+            continue;
+        }
+        let t = &glossary[ti];
+        let with_target = lines.iter().filter(|&&(_, _, r)| r == Rendering::Target).count();
+        for &(fi, e, r) in &lines {
+            let this_line = match r {
+                Rendering::Target => format!("usa \"{}\"", t.target.trim()),
+                Rendering::Source => format!("mantém \"{}\"", t.source.trim()),
+                Rendering::Other => "usa outra forma".to_string(),
+            };
+            issues.push((
+                fi,
+                QaIssue {
+                    entry_id: e.entry_id.clone(),
+                    code: "GLOSSARY_INCONSISTENT".to_string(),
+                    severity: Severity::Warning,
+                    message: format!(
+                        "Termo \"{}\" traduzido de formas diferentes: {} de {} linhas usam \"{}\"; esta linha {}",
+                        t.source.trim(),
+                        with_target,
+                        lines.len(),
+                        t.target.trim(),
+                        this_line
+                    ),
+                },
+            ));
+        }
+    }
+    issues
+}
+
+/// Character count of the translation and the limit it exceeds, if any. The
+/// entry's own `max_chars` wins over `default_max`.
+pub fn over_length(e: &CoreEntry, default_max: Option<usize>) -> Option<(usize, usize)> {
+    let limit = e.max_chars.or(default_max)?;
+    let len = e.translation.chars().count();
+    (len > limit).then_some((len, limit))
+}
+
+/// Lines in the translation and lines the entry spans in the source, when
+/// an entry merged from several lines is translated over a different count.
+pub fn line_count_mismatch(e: &CoreEntry) -> Option<(usize, usize)> {
+    let translation = e.translation.trim();
+    if e.joins.is_empty() || translation.is_empty() {
+        return None;
+    }
+    let (found, expected) = (translation.split('\n').count(), e.joins.len() + 1);
+    (found != expected).then_some((found, expected))
+}
+
+/// Moves `Translated` entries among `indices` that are over their length
+/// limit back to `InProgress`, so they get reviewed before shipping;
+/// `Reviewed` ones were already looked at. The text itself is never cut.
+/// Returns how many were held.
+pub fn hold_over_length(entries: &mut [CoreEntry], indices: impl IntoIterator<Item = usize>, default_max: Option<usize>) -> usize {
+    let mut held = 0;
+    for i in indices {
+        let e = &mut entries[i];
+        if e.status == EntryStatus::Translated && over_length(e, default_max).is_some() {
+            e.status = EntryStatus::InProgress;
+            held += 1;
+        }
+    }
+    held
+}
+
+/// Every run of consecutive digits in `s`, full-width ones folded to ASCII,
+/// sorted so order changes between languages do not count. Separators
+/// split runs, so `1,000` and `1.000` agree.
+fn digit_runs(s: &str) -> Vec<String> {
+    let mut runs: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for c in s.chars() {
+        let ascii = match c {
+            '0'..='9' => Some(c),
+            '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32),
+            _ => None,
+        };
+        match ascii {
+            Some(d) => current.push(d),
+            None if !current.is_empty() => runs.push(std::mem::take(&mut current)),
+            None => {}
+        }
+    }
+    if !current.is_empty() {
+        runs.push(current);
+    }
+    runs.sort();
+    runs
+}
+
+/// Leading and trailing whitespace of `s`.
+fn edges(s: &str) -> (&str, &str) {
+    (&s[..s.len() - s.trim_start().len()], &s[s.trim_end().len()..])
+}
+
+/// Pairs in `pairs` that `translation` balances differently from
+/// `original`. Where the original is balanced, the translation must be too:
+/// no closer without an opener, no opener left open. Otherwise only the
+/// difference between openers and closers has to match. Pairs whose two
+/// sides are the same character (`""`) compare the parity of their count.
+fn unbalanced_pairs(original: &str, translation: &str, pairs: &[(char, char)]) -> Vec<(char, char)> {
+    pairs
+        .iter()
+        .copied()
+        .filter(|&(open, close)| {
+            let (expected, found) = (balance(original, open, close), balance(translation, open, close));
+            if expected == (0, false) {
+                found != expected
+            } else {
+                found.0 != expected.0
+            }
+        })
+        .collect()
+}
+
+/// Openers minus closers in `text`, and whether a closer ever came before
+/// its opener.
+fn balance(text: &str, open: char, close: char) -> (i64, bool) {
+    if open == close {
+        return ((text.chars().filter(|&c| c == open).count() % 2) as i64, false);
+    }
+    let mut depth = 0i64;
+    let mut closes_early = false;
+    for c in text.chars() {
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            depth -= 1;
+            closes_early |= depth < 0;
+        }
+    }
+    (depth, closes_early)
+}
+
+/// Runs the per-entry checks per file and the glossary consistency pass
+/// across the whole project, and aggregates counts.
+pub fn run_batch(files: &BTreeMap<String, Vec<CoreEntry>>, opts: &QaOptions) -> QaBatchReport {
+    let mut summary = QaSummary::default();
+    let mut out: BTreeMap<String, Vec<QaIssue>> = BTreeMap::new();
+
+    let mut per_file: Vec<Vec<QaIssue>> = files.values().map(|entries| entry_issues(entries, opts)).collect();
+    let all: Vec<&[CoreEntry]> = files.values().map(Vec::as_slice).collect();
+    for (fi, issue) in glossary_inconsistencies(&all, &opts.glossary) {
+        per_file[fi].push(issue);
+    }
+
+    for (file, issues) in files.keys().zip(per_file) {
+        for issue in &issues {
+            summary.total += 1;
+            *summary.by_code.entry(issue.code.clone()).or_insert(0) += 1;
+            *summary.by_severity.entry(issue.severity).or_insert(0) += 1;
+        }
+        out.insert(file.clone(), issues);
+    }
+
+    QaBatchReport { files: out, summary }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(original: &str, translation: &str) -> CoreEntry {
+        serde_json::from_value(serde_json::json!({
+            "entry_id": "1-text",
+            "original": original,
+            "translation": translation,
+            "is_translatable": true,
+            "prefix": "",
+            "suffix": "",
+        }))
+        .unwrap()
+    }
+
+    fn codes(issues: &[QaIssue]) -> Vec<&str> {
+        issues.iter().map(|i| i.code.as_str()).collect()
+    }
+
+    fn unbalanced(original: &str, translation: &str) -> Vec<String> {
+        run(&[entry(original, translation)])
+            .into_iter()
+            .filter(|i| i.code == "UNBALANCED")
+            .map(|i| i.message)
+            .collect()
+    }
+
+    #[test]
+    fn unbalanced_brackets_in_the_translation_are_flagged() {
+        assert_eq!(unbalanced("「はい」", "「Sim"), ["Tradução com 「」 desbalanceado"]);
+        assert_eq!(unbalanced("(はい)", ")Sim("), ["Tradução com () desbalanceado"]);
+        assert_eq!(unbalanced("\"はい\"", "\"Sim"), ["Tradução com \"\" desbalanceado"]);
+        assert!(unbalanced("「はい」", "「Sim」").is_empty());
+    }
+
+    #[test]
+    fn a_pair_open_in_the_source_hides_no_other_pair() {
+        // A quote the source itself leaves open is not the translator's doing.
+        assert!(unbalanced("「はい（そう）", "「Sim (isso)").is_empty());
+        assert_eq!(unbalanced("「はい（そう）", "「Sim (isso"), ["Tradução com () desbalanceado"]);
+        assert_eq!(unbalanced("「はい『そう』", "Sim 『isso"), ["Tradução com 「」 desbalanceado", "Tradução com 『』 desbalanceado"]);
+    }
+
+    #[test]
+    fn batch_groups_issues_per_file_and_totals_them() {
+        let mut empty = entry("はい", "");
+        empty.status = EntryStatus::Translated;
+        let mut files = BTreeMap::new();
+        files.insert("a.ks".to_string(), vec![entry("はい", "Sim")]);
+        files.insert("b.ks".to_string(), vec![entry("「はい」", "「Sim"), empty]);
+
+        let report = run_batch(&files, &QaOptions::default());
+        assert!(report.files["a.ks"].is_empty());
+        assert_eq!(codes(&report.files["b.ks"]), ["UNBALANCED", "STATUS_TRANSLATED_BUT_EMPTY"]);
+        assert_eq!(report.summary.total, 2);
+        assert_eq!(report.summary.by_code["UNBALANCED"], 1);
+    }
+
+    #[test]
+    fn scoped_glossary_term_only_checks_its_speaker() {
+        let mut by_a = entry("姉さん", "Irmã");
+        by_a.speaker = Some("A".into());
+        let mut by_b = entry("姉さん", "Mana");
+        by_b.entry_id = "2-text".into();
+        by_b.speaker = Some("B".into());
+        let term = GlossaryTerm {
+            source: "姉さん".into(),
+            target: "Mana".into(),
+            note: String::new(),
+            speaker: Some("B".into()),
+        };
+        let opts = QaOptions { glossary: vec![term], ..Default::default() };
+
+        assert!(run_with(&[by_a, by_b], &opts).is_empty());
+    }
+
+    #[test]
+    fn numbers_are_compared_as_sorted_runs() {
+        assert!(!codes(&run(&[entry("３人で1,000円", "1.000 ienes para 3")])).contains(&"NUMBER_MISMATCH"));
+        assert!(codes(&run(&[entry("3人", "4 pessoas")])).contains(&"NUMBER_MISMATCH"));
+    }
+
+    #[test]
+    fn length_limit_of_the_entry_beats_the_default() {
+        let mut e = entry("はい", "Sim, claro");
+        assert_eq!(over_length(&e, Some(5)), Some((10, 5)));
+        e.max_chars = Some(20);
+        assert_eq!(over_length(&e, Some(5)), None);
+    }
+
+    #[test]
+    fn only_translated_over_length_entries_are_held() {
+        let mut entries = vec![entry("a", "longo demais"), entry("b", "longo demais")];
+        entries[0].status = EntryStatus::Translated;
+        entries[1].status = EntryStatus::Reviewed;
+        assert_eq!(hold_over_length(&mut entries, 0..2, Some(5)), 1);
+        assert_eq!(entries[0].status, EntryStatus::InProgress);
+        assert_eq!(entries[1].status, EntryStatus::Reviewed);
+    }
+
+    #[test]
+    fn kana_left_in_the_translation_is_residue() {
+        let entries = [entry("ありがとう、先輩", "Obrigado, 先輩")];
+        assert_eq!(codes(&run(&entries)), ["SOURCE_LANG_RESIDUE"]);
+
+        let opts = QaOptions { residue_whitelist: vec!["先輩".into()], ..Default::default() };
+        assert!(run_with(&entries, &opts).is_empty());
+        let opts = QaOptions { target_lang: "zh-CN".into(), ..Default::default() };
+        assert!(run_with(&entries, &opts).is_empty());
+    }
+
+    #[test]
+    fn glossary_inconsistency_flags_both_sides() {
+        let magia = || GlossaryTerm { source: "魔法".into(), target: "magia".into(), note: String::new(), speaker: None };
+        let mut entries = [entry("魔法だ", "É magia"), entry("魔法か", "É feitiço?"), entry("魔法!", "Magia!")];
+        for (n, e) in entries.iter_mut().enumerate() {
+            e.entry_id = format!("{}-text", n + 1);
+        }
+        let opts = QaOptions { glossary: vec![magia()], ..Default::default() };
+
+        let issues = run_with(&entries, &opts);
+        let inconsistent: Vec<&QaIssue> = issues.iter().filter(|i| i.code == "GLOSSARY_INCONSISTENT").collect();
+        assert_eq!(inconsistent.len(), 3);
+        assert!(inconsistent[1].message.contains("2 de 3 linhas"), "{}", inconsistent[1].message);
+        assert!(inconsistent[1].message.ends_with("usa outra forma"));
+
+        let mut files = BTreeMap::new();
+        files.insert("a.ks".to_string(), vec![entry("魔法", "magia")]);
+        files.insert("b.ks".to_string(), vec![entry("魔法", "feitiço")]);
+        let report = run_batch(&files, &QaOptions { glossary: vec![magia()], ..Default::default() });
+        assert_eq!(codes(&report.files["a.ks"]), ["GLOSSARY_INCONSISTENT"]);
+        assert_eq!(report.summary.by_code["GLOSSARY_INCONSISTENT"], 2);
+        assert_eq!(report.summary.by_code["GLOSSARY_MISS"], 1);
+    }
+
+    #[test]
+    fn merged_entry_over_other_line_count_is_an_error() {
+        let mut e = entry("一\n二", "Um, dois");
+        e.joins = vec!["\n".into()];
+        assert_eq!(line_count_mismatch(&e), Some((1, 2)));
+        let issues = run(&[e]);
+        assert_eq!(issues[0].code, "LINE_COUNT_MISMATCH");
+        assert_eq!(issues[0].severity, Severity::Error);
+    }
+}
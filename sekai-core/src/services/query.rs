@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+
+use serde::Deserialize;
+
+use crate::model::entry::{CoreEntry, EntryStatus};
+use crate::services::qa;
+
+/// Predicates over entries; every one that is set must hold (AND).
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct EntryQuery {
+    #[serde(default)]
+    pub status: Option<Vec<EntryStatus>>,
+
+    #[serde(default)]
+    pub has_speaker: Option<bool>,
+
+    /// Exact speaker name; on multi-speaker lines any of them matches.
+    #[serde(default)]
+    pub speaker: Option<String>,
+
+    #[serde(default)]
+    pub text_contains: Option<String>,
+
+    #[serde(default)]
+    pub is_translatable: Option<bool>,
+
+    #[serde(default)]
+    pub qa_code: Option<String>,
+
+    /// What `qa_code` is checked with; filled in by the caller from the
+    /// rest of its payload.
+    #[serde(skip)]
+    pub qa: qa::QaOptions,
+}
+
+/// Indices of the entries matching every predicate in `q`, in input order.
+pub fn matching_indices(entries: &[CoreEntry], q: &EntryQuery) -> Vec<usize> {
+    let needle = q
+        .text_contains
+        .as_deref()
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty());
+
+    let flagged: Option<HashSet<String>> = q.qa_code.as_deref().map(|code| {
+        qa::run_with(entries, &q.qa)
+            .into_iter()
+            .filter(|issue| issue.code == code)
+            .map(|issue| issue.entry_id)
+            .collect()
+    });
+
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| {
+            if let Some(statuses) = &q.status {
+                if !statuses.contains(&e.status) {
+                    return false;
+                }
+            }
+
+            if let Some(want) = q.has_speaker {
+                let has = e.speaker.as_deref().is_some_and(|s| !s.trim().is_empty());
+                if has != want {
+                    return false;
+                }
+            }
+
+            if let Some(want) = q.speaker.as_deref().map(str::trim) {
+                let matches = e.speaker.as_deref().is_some_and(|s| s.trim() == want)
+                    || e.speakers.iter().any(|s| s.trim() == want);
+                if !matches {
+                    return false;
+                }
+            }
+
+            if let Some(want) = q.is_translatable {
+                if e.is_translatable != want {
+                    return false;
+                }
+            }
+
+            if let Some(n) = &needle {
+                if !e.original.to_lowercase().contains(n) && !e.translation.to_lowercase().contains(n) {
+                    return false;
+                }
+            }
+
+            if let Some(ids) = &flagged {
+                if !ids.contains(&e.entry_id) {
+                    return false;
+                }
+            }
+
+            true
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(entry_id: &str, original: &str, translation: &str) -> CoreEntry {
+        serde_json::from_value(serde_json::json!({
+            "entry_id": entry_id,
+            "original": original,
+            "translation": translation,
+            "is_translatable": true,
+        }))
+        .unwrap()
+    }
+
+    fn sample() -> Vec<CoreEntry> {
+        let mut entries = vec![
+            entry("1-text", "魔法の杖", "Varinha mágica"),
+            entry("2-text", "魔法", ""),
+            entry("3-text", "剣", "Espada muito longa"),
+        ];
+        entries[0].status = EntryStatus::Translated;
+        entries[0].speakers = vec!["A".into(), "B".into()];
+        entries[0].speaker = Some("A".into());
+        entries[2].status = EntryStatus::Translated;
+        entries
+    }
+
+    #[test]
+    fn predicates_are_combined() {
+        let q = EntryQuery {
+            status: Some(vec![EntryStatus::Translated]),
+            text_contains: Some(" MÁGICA ".into()),
+            ..Default::default()
+        };
+        assert_eq!(matching_indices(&sample(), &q), [0]);
+
+        let q = EntryQuery { text_contains: Some("魔法".into()), ..Default::default() };
+        assert_eq!(matching_indices(&sample(), &q), [0, 1]);
+    }
+
+    #[test]
+    fn qa_code_uses_the_callers_options() {
+        let mut q = EntryQuery { qa_code: Some("OVER_LENGTH".into()), ..Default::default() };
+        assert!(matching_indices(&sample(), &q).is_empty());
+        q.qa.max_chars = Some(15);
+        assert_eq!(matching_indices(&sample(), &q), [2]);
+    }
+
+    #[test]
+    fn any_speaker_of_a_shared_line_matches() {
+        let q = EntryQuery { speaker: Some("B".into()), ..Default::default() };
+        assert_eq!(matching_indices(&sample(), &q), [0]);
+        let q = EntryQuery { has_speaker: Some(false), ..Default::default() };
+        assert_eq!(matching_indices(&sample(), &q), [1, 2]);
+    }
+}
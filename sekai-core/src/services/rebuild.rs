@@ -1,30 +1,715 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
 use crate::model::entry::CoreEntry;
+use crate::model::glossary::GlossaryTerm;
+use crate::services::encoding;
+use crate::services::fsutil::write_atomic;
+use crate::services::glossary;
+use crate::services::qa::{self, QaIssue, Severity};
+use crate::services::rtl::RtlOptions;
+use crate::services::substitutions::{self, Substitution};
+use crate::{log_info, log_warn};
 
-pub fn rebuild(entries: &[CoreEntry]) -> String {
-    let mut out: Vec<String> = Vec::with_capacity(entries.len());
+pub const DEFAULT_UNTRANSLATED_MARKER: &str = "[TODO]";
 
-    for e in entries {
-        if !e.is_translatable {
-            out.push(e.raw_line.clone().unwrap_or_default());
-            continue;
+/// What to emit for a translatable line whose translation is blank.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum UntranslatedPolicy {
+    #[default]
+    Original,
+    Empty,
+    Marker(String),
+}
+
+impl UntranslatedPolicy {
+    pub fn parse(name: &str, marker: &str) -> Result<Self, String> {
+        match name {
+            "" | "original" => Ok(UntranslatedPolicy::Original),
+            "empty" => Ok(UntranslatedPolicy::Empty),
+            "marker" => Ok(UntranslatedPolicy::Marker(marker.to_string())),
+            other => Err(format!("unknown on_untranslated policy: {other}")),
         }
+    }
+}
 
-        let translation_trimmed_empty = e.translation.trim().is_empty();
-        let text = if !translation_trimmed_empty {
-            e.translation.as_str()
-        } else {
-            e.original.as_str()
-        };
+/// Unicode normalization form translations are written in. Independent of
+/// the NFKC folding the TM uses for matching.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputNormalization {
+    #[default]
+    Nfc,
+    Nfd,
+    None,
+}
+
+impl OutputNormalization {
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            OutputNormalization::Nfc => text.nfc().collect(),
+            OutputNormalization::Nfd => text.nfd().collect(),
+            OutputNormalization::None => text.to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RebuildConfig {
+    /// Speakers matching a glossary `source` are rewritten to its `target`.
+    pub glossary: Vec<GlossaryTerm>,
+    pub on_untranslated: UntranslatedPolicy,
+    /// Applied to translations only; originals and markers pass through.
+    pub substitutions: Vec<Substitution>,
+    pub normalization: OutputNormalization,
+    /// Set when the target is written right to left; applied to
+    /// translations only, after every other rewrite.
+    pub rtl: Option<RtlOptions>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnmappableLine {
+    pub entry_id: String,
+    pub line_number: usize,
+    pub chars: Vec<String>,
+    /// Encodable stand-in per character of `chars` that has one, when
+    /// suggestions were asked for.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub suggestions: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RebuildFileReport {
+    pub written: bool,
+    pub encoding: String,
+    pub unmappable: Vec<UnmappableLine>,
+}
+
+pub fn rebuild(entries: &[CoreEntry], cfg: &RebuildConfig) -> String {
+    rebuild_lines(entries, cfg).join("\n")
+}
+
+/// Output lines in entry order. Consecutive entries sharing a `group` (the
+/// pieces of a split entry) are joined back onto a single line.
+pub fn rebuild_lines(entries: &[CoreEntry], cfg: &RebuildConfig) -> Vec<String> {
+    let inconsistent = inconsistencies(entries);
+    if !inconsistent.is_empty() {
+        log_warn!("rebuild", "{} inconsistent entries; is_translatable decides which fields are written", inconsistent.len());
+    }
+
+    join_lines(entries, |e| render_entry(e, cfg))
+        .into_iter()
+        .map(|(_, line)| line)
+        .collect()
+}
 
-        let line = format!(
+/// Renders every entry and joins group pieces, keeping the index of the first
+/// entry of each line.
+fn join_lines(entries: &[CoreEntry], render: impl Fn(&CoreEntry) -> String) -> Vec<(usize, String)> {
+    let mut out: Vec<(usize, String)> = Vec::with_capacity(entries.len());
+    let mut prev_group: Option<&str> = None;
+
+    for (i, e) in entries.iter().enumerate() {
+        let rendered = render(e);
+        let group = e.group.as_deref();
+
+        match (out.last_mut(), group) {
+            (Some((_, last)), Some(g)) if prev_group == Some(g) => last.push_str(&rendered),
+            _ => out.push((i, rendered)),
+        }
+
+        prev_group = group;
+    }
+
+    out
+}
+
+#[derive(Debug, Serialize)]
+pub struct LineChange {
+    pub line_number: usize,
+    pub before: String,
+    pub after: String,
+}
+
+/// Lines whose rebuilt text differs from the source line the entries were
+/// parsed from (`raw_line`, or prefix + original + suffix).
+pub fn patch(entries: &[CoreEntry], cfg: &RebuildConfig) -> Vec<LineChange> {
+    let source = join_lines(entries, |e| match &e.raw_line {
+        Some(raw) if !e.is_translatable => raw.clone(),
+        _ => format!(
             "{}{}{}",
             e.prefix.as_deref().unwrap_or(""),
-            text,
+            interleave(&e.original, &e.joins),
             e.suffix.as_deref().unwrap_or("")
+        ),
+    });
+    let rebuilt = join_lines(entries, |e| render_entry(e, cfg));
+
+    source
+        .into_iter()
+        .zip(rebuilt)
+        .filter(|((_, before), (_, after))| before != after)
+        .map(|((i, before), (_, after))| LineChange {
+            line_number: entries[i].line_number,
+            before,
+            after,
+        })
+        .collect()
+}
+
+/// The text a single entry contributes to the output. `is_translatable`
+/// alone decides which fields count: prefix + text + suffix when set (any
+/// `raw_line` is ignored), `raw_line` otherwise (prefix, suffix and
+/// original are ignored). `inconsistencies` reports entries that carry
+/// the fields the other side uses.
+pub fn render_entry(e: &CoreEntry, cfg: &RebuildConfig) -> String {
+    if !e.is_translatable {
+        return e.raw_line.clone().unwrap_or_default();
+    }
+
+    let translation_trimmed_empty = e.translation.trim().is_empty();
+    let text = if !translation_trimmed_empty {
+        let fitted = fit_edges(&e.translation, &e.original);
+        let text = cfg.normalization.apply(&substitutions::apply(&fitted, &cfg.substitutions));
+        match &cfg.rtl {
+            Some(rtl) => rtl.apply(&text),
+            None => text,
+        }
+    } else {
+        match &cfg.on_untranslated {
+            UntranslatedPolicy::Original => e.original.clone(),
+            UntranslatedPolicy::Empty => String::new(),
+            UntranslatedPolicy::Marker(m) => m.clone(),
+        }
+    };
+
+    format!(
+        "{}{}{}",
+        localized_prefix(e, &cfg.glossary),
+        interleave(&text, &e.joins),
+        e.suffix.as_deref().unwrap_or("")
+    )
+}
+
+/// Puts line n of `text` in front of `joins[n]` for a merged entry, so it
+/// spans as many lines as it was merged from. Surplus lines go onto the last
+/// one, separated by a space; missing ones stay empty (`check_line_counts`
+/// keeps such translations from being written). Without joins `text` is
+/// returned as is.
+fn interleave(text: &str, joins: &[String]) -> String {
+    if joins.is_empty() {
+        return text.to_string();
+    }
+
+    let mut parts: Vec<String> = text.split('\n').map(str::to_string).collect();
+    if parts.len() > joins.len() + 1 {
+        let rest = parts.split_off(joins.len()).join(" ");
+        parts.push(rest);
+    }
+    parts.resize(joins.len() + 1, String::new());
+
+    let mut out = parts[0].clone();
+    for (join, part) in joins.iter().zip(&parts[1..]) {
+        out.push_str(join);
+        out.push_str(part);
+    }
+    out
+}
+
+/// Refuses a rebuild in which an entry spanning several source lines has a
+/// translation over a different number of lines.
+pub fn check_line_counts(entries: &[CoreEntry]) -> Result<(), String> {
+    for e in entries.iter().filter(|e| e.is_translatable) {
+        if let Some((found, expected)) = qa::line_count_mismatch(e) {
+            return Err(format!(
+                "translation of {} has {found} line(s), the source spans {expected}",
+                e.entry_id
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Entries breaking the invariant behind `render_entry`: a translatable
+/// entry has no `raw_line`; a structural one has a `raw_line` and no
+/// prefix, suffix or original.
+pub fn inconsistencies(entries: &[CoreEntry]) -> Vec<QaIssue> {
+    entries
+        .iter()
+        .filter_map(|e| {
+            let problem = if e.is_translatable {
+                e.raw_line.is_some().then_some("Linha traduzível com raw_line; o rebuild usa prefix + texto + suffix")
+            } else if e.raw_line.is_none() {
+                Some("Linha estrutural sem raw_line; o rebuild escreve uma linha vazia")
+            } else if e.prefix.is_some() || e.suffix.is_some() || !e.original.is_empty() {
+                Some("Linha estrutural com prefix/suffix/original; o rebuild usa apenas raw_line")
+            } else {
+                None
+            };
+            problem.map(|message| QaIssue {
+                entry_id: e.entry_id.clone(),
+                code: "INCONSISTENT_ENTRY".to_string(),
+                severity: Severity::Error,
+                message: message.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Prefix and suffix own the whitespace around the text, so the translation's
+/// own edge whitespace is replaced by whatever the original had at its edges
+/// (nothing for narration, which is stored trimmed). Otherwise a translator's
+/// leading spaces would double the indentation.
+fn fit_edges(translation: &str, original: &str) -> String {
+    let lead = &original[..original.len() - original.trim_start().len()];
+    let trail = &original[original.trim_end().len()..];
+    format!("{lead}{}{trail}", translation.trim())
+}
+
+/// Rebuilds `entries` and writes them to `path` in `encoding_name`.
+///
+/// Every line is checked for characters the encoding cannot represent. When
+/// any are found nothing is written unless `allow_lossy` is set, in which case
+/// they are replaced with `?`. With `check_only` the file is never touched.
+/// With `suggest_substitutes` each reported character comes with the nearest
+/// one the encoding has, where there is one; nothing is replaced for you.
+/// Fails on a multi-line entry translated over a different line count.
+pub fn rebuild_file(
+    entries: &[CoreEntry],
+    cfg: &RebuildConfig,
+    path: &Path,
+    encoding_name: &str,
+    check_only: bool,
+    allow_lossy: bool,
+    suggest_substitutes: bool,
+) -> Result<RebuildFileReport, String> {
+    let enc = encoding::resolve(encoding_name)
+        .ok_or_else(|| format!("unsupported encoding: {encoding_name}"))?;
+    check_line_counts(entries)?;
+
+    let unmappable: Vec<UnmappableLine> = entries
+        .iter()
+        .filter_map(|e| {
+            let chars = encoding::unmappable_chars(enc.encoding, &render_entry(e, cfg));
+            if chars.is_empty() {
+                None
+            } else {
+                let suggestions = if suggest_substitutes {
+                    chars
+                        .iter()
+                        .filter_map(|c| encoding::suggest_substitute(enc.encoding, c).map(|s| (c.clone(), s)))
+                        .collect()
+                } else {
+                    BTreeMap::new()
+                };
+                Some(UnmappableLine {
+                    entry_id: e.entry_id.clone(),
+                    line_number: e.line_number,
+                    chars,
+                    suggestions,
+                })
+            }
+        })
+        .collect();
+
+    let written = !check_only && (unmappable.is_empty() || allow_lossy);
+
+    if written {
+        let bytes = enc.encode_lossy(&rebuild(entries, cfg));
+        write_atomic(path, &bytes).map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+        log_info!("rebuild", "wrote {} ({})", path.display(), encoding_name);
+    }
+
+    Ok(RebuildFileReport {
+        written,
+        encoding: encoding_name.to_string(),
+        unmappable,
+    })
+}
+
+/// Swaps each speaker's name in the prefix, inside its `<speaker>` tag or
+/// the inline bracket it was read from (`【speaker】`), for its glossary
+/// target; the brackets stay. Speakers are never sent to the AI, so names
+/// unknown to the glossary stay as they are.
+fn localized_prefix(e: &CoreEntry, glossary: &[GlossaryTerm]) -> String {
+    let mut prefix = e.prefix.as_deref().unwrap_or("").to_string();
+
+    let speakers = glossary::entry_speakers(e);
+
+    // Substitute left to right so each name is replaced once, even when two
+    // speakers share a name or a target equals another source.
+    let mut from = 0usize;
+    for &speaker in speakers.iter().filter(|s| !s.trim().is_empty()) {
+        let (pos, len) = match prefix[from..].find(&format!("<{}>", speaker)) {
+            Some(p) => (from + p + 1, speaker.len()),
+            // Inline speakers are stored trimmed, whatever the spacing
+            // inside their bracket.
+            None => match prefix[from..].find(speaker.trim()) {
+                Some(p) => (from + p, speaker.trim().len()),
+                None => continue,
+            },
+        };
+
+        // A term scoped to this line's speakers beats an unscoped one.
+        let names: Vec<&GlossaryTerm> = glossary
+            .iter()
+            .filter(|t| t.source.trim() == speaker.trim() && !t.target.trim().is_empty() && t.applies_to(&speakers))
+            .collect();
+        let replacement = match names.iter().find(|t| t.is_scoped()).or(names.first()) {
+            Some(t) => t.target.trim().to_string(),
+            None => prefix[pos..pos + len].to_string(),
+        };
+
+        prefix.replace_range(pos..pos + len, &replacement);
+        from = pos + replacement.len();
+    }
+
+    prefix
+}
+
+#[derive(Debug, Serialize)]
+pub struct Discrepancy {
+    /// Line in the original file.
+    pub line_number: usize,
+    /// That line as it is on disk.
+    pub expected: String,
+    /// What the rebuild writes in its place; absent when nothing does.
+    pub found: Option<String>,
+}
+
+/// Rebuilds `entries` and compares the output, line by line, against
+/// `original_text`, the file they came from (`original` being its parse).
+/// A structural line of the file that the output drops or alters at its
+/// position, a structural entry landing where the file has other text, and
+/// an output with a different line count (reported at the first line past
+/// the shorter side) are discrepancies.
+pub fn verify_against(
+    entries: &[CoreEntry],
+    cfg: &RebuildConfig,
+    original_text: &str,
+    original: &[CoreEntry],
+) -> Vec<Discrepancy> {
+    let source_lines: Vec<&str> = original_text.lines().map(|l| l.trim_end_matches('\r')).collect();
+    let source_line = |n: usize| n.checked_sub(1).and_then(|i| source_lines.get(i)).copied().unwrap_or("").to_string();
+
+    // Output lines, and the 1-based positions structural entries land on.
+    let mut rebuilt: Vec<String> = Vec::new();
+    let mut structural_at: BTreeSet<usize> = BTreeSet::new();
+    for (i, chunk) in join_lines(entries, |e| render_entry(e, cfg)) {
+        if !entries[i].is_translatable {
+            structural_at.insert(rebuilt.len() + 1);
+        }
+        rebuilt.extend(chunk.split('\n').map(str::to_string));
+    }
+    let rebuilt_line = |n: usize| n.checked_sub(1).and_then(|i| rebuilt.get(i)).cloned();
+
+    let structural: BTreeSet<usize> = original.iter().filter(|e| !e.is_translatable).map(|e| e.line_number).collect();
+
+    let mut out: BTreeMap<usize, Discrepancy> = BTreeMap::new();
+    for &n in structural.union(&structural_at) {
+        let expected = source_line(n);
+        let found = rebuilt_line(n);
+        if found.as_deref() != Some(expected.as_str()) || n > source_lines.len() {
+            out.insert(n, Discrepancy { line_number: n, expected, found });
+        }
+    }
+    if rebuilt.len() != source_lines.len() {
+        let n = rebuilt.len().min(source_lines.len()) + 1;
+        out.entry(n).or_insert_with(|| Discrepancy { line_number: n, expected: source_line(n), found: rebuilt_line(n) });
+    }
+    out.into_values().collect()
+}
+
+/// Confirms every structural (non-translatable) line of `original` is still
+/// present, unchanged and in order in `entries` before they are written back.
+pub fn verify_structure(entries: &[CoreEntry], original: &[CoreEntry]) -> Result<(), String> {
+    let expected: Vec<&CoreEntry> = original.iter().filter(|e| !e.is_translatable).collect();
+    let actual: Vec<&CoreEntry> = entries.iter().filter(|e| !e.is_translatable).collect();
+
+    for (pos, exp) in expected.iter().enumerate() {
+        let exp_line = exp.raw_line.as_deref().unwrap_or("");
+
+        match actual.get(pos) {
+            None => {
+                return Err(format!(
+                    "structural entry missing: line {} ({})",
+                    exp.line_number, exp.entry_id
+                ));
+            }
+            Some(act) => {
+                if act.raw_line.as_deref().unwrap_or("") != exp_line {
+                    return Err(format!(
+                        "structural entry mismatch at line {}: expected {:?}, found {} ({:?})",
+                        exp.line_number,
+                        exp_line,
+                        act.entry_id,
+                        act.raw_line.as_deref().unwrap_or("")
+                    ));
+                }
+            }
+        }
+    }
+
+    if actual.len() > expected.len() {
+        let extra = actual[expected.len()];
+        return Err(format!(
+            "unexpected structural entry: {} (line {})",
+            extra.entry_id, extra.line_number
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::kirikiri::{self, ParseOptions};
+    use std::fs;
+    use std::path::PathBuf;
+
+    const SCRIPT: &str = "[cm]\n<A>\"おはよう\"\n@wait time=10\nいい天気だ";
+
+    fn parsed() -> Vec<CoreEntry> {
+        kirikiri::parse(SCRIPT, &ParseOptions::default())
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sekai-core-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn unmappable_characters_are_reported_per_line() {
+        let path = temp_dir("rebuild-unmappable").join("out.ks");
+        let mut entries = parsed();
+        entries[3].translation = "coração".into();
+
+        let report = rebuild_file(&entries, &RebuildConfig::default(), &path, "cp932", false, false, false).unwrap();
+        assert!(!report.written);
+        assert!(!path.exists());
+        assert_eq!(report.unmappable.len(), 1);
+        assert_eq!(report.unmappable[0].entry_id, entries[3].entry_id);
+        assert_eq!(report.unmappable[0].line_number, 4);
+        assert_eq!(report.unmappable[0].chars, ["ç", "ã"]);
+
+        let report = rebuild_file(&entries, &RebuildConfig::default(), &path, "cp932", true, true, false).unwrap();
+        assert!(!report.written && !path.exists());
+
+        let report = rebuild_file(&entries, &RebuildConfig::default(), &path, "cp932", false, true, false).unwrap();
+        assert!(report.written);
+        assert!(encoding::decode(&fs::read(&path).unwrap()).ends_with("cora??o"));
+    }
+
+    #[test]
+    fn unmappable_characters_come_with_suggestions_when_asked() {
+        let path = temp_dir("rebuild-suggestions").join("out.ks");
+        let mut entries = parsed();
+        entries[3].translation = "coração".into();
+
+        let report = rebuild_file(&entries, &RebuildConfig::default(), &path, "cp932", false, false, true).unwrap();
+        assert!(!report.written);
+        assert_eq!(report.unmappable[0].suggestions["ç"], "c");
+        assert_eq!(report.unmappable[0].suggestions["ã"], "a");
+
+        let report = rebuild_file(&entries, &RebuildConfig::default(), &path, "cp932", false, false, false).unwrap();
+        assert!(report.unmappable[0].suggestions.is_empty());
+    }
+
+    #[test]
+    fn utf16_projects_are_written_as_utf16() {
+        let path = temp_dir("rebuild-utf16").join("out.ks");
+        let mut entries = parsed();
+        entries[3].translation = "coração".into();
+
+        let report = rebuild_file(&entries, &RebuildConfig::default(), &path, "utf-16le", false, false, false).unwrap();
+        assert!(report.written && report.unmappable.is_empty());
+        let bytes = fs::read(&path).unwrap();
+        assert!(bytes.starts_with(&[0xFF, 0xFE]));
+        assert_eq!(encoding::resolve("utf-16le").unwrap().decode(&bytes), rebuild(&entries, &RebuildConfig::default()));
+    }
+
+    #[test]
+    fn structure_check_catches_a_missing_or_altered_raw_line() {
+        let original = parsed();
+        let mut translated = parsed();
+        translated[1].translation = "Bom dia".into();
+        assert!(verify_structure(&translated, &original).is_ok());
+
+        let mut missing = translated.clone();
+        missing.remove(2);
+        let err = verify_structure(&missing, &original).unwrap_err();
+        assert!(err.contains("missing: line 3"), "{err}");
+
+        let mut altered = translated;
+        altered[2].raw_line = Some("@wait time=99".into());
+        assert!(verify_structure(&altered, &original).unwrap_err().contains("mismatch at line 3"));
+    }
+
+    #[test]
+    fn verification_flags_only_structural_lines_that_moved_or_changed() {
+        let original = parsed();
+        let mut translated = parsed();
+        translated[1].translation = "Bom dia".into();
+        assert!(verify_against(&translated, &RebuildConfig::default(), SCRIPT, &original).is_empty());
+
+        let mut altered = translated.clone();
+        altered[2].raw_line = Some("@wait time=99".into());
+        let found = verify_against(&altered, &RebuildConfig::default(), SCRIPT, &original);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].line_number, found[0].found.as_deref()), (3, Some("@wait time=99")));
+
+        let mut missing = translated.clone();
+        missing.remove(2);
+        let found = verify_against(&missing, &RebuildConfig::default(), SCRIPT, &original);
+        assert!(found.iter().any(|d| d.line_number == 3 && d.expected == "@wait time=10"));
+
+        let mut shifted = translated;
+        shifted[1].translation = "Bom\ndia".into();
+        let found = verify_against(&shifted, &RebuildConfig::default(), SCRIPT, &original);
+        assert!(found.iter().any(|d| d.line_number == 3));
+        assert!(found.iter().any(|d| d.line_number == 5 && d.found.is_some()));
+    }
+
+    #[test]
+    fn known_speakers_are_localized_and_unknown_ones_kept() {
+        let term = |source: &str, target: &str| GlossaryTerm {
+            source: source.into(),
+            target: target.into(),
+            note: String::new(),
+            speaker: None,
+        };
+        let mut entries = kirikiri::parse("<A>\"おはよう\"\n<B>\"やあ\"", &ParseOptions::default());
+        entries[0].translation = "Bom dia".into();
+        entries[1].translation = "Oi".into();
+
+        let cfg = RebuildConfig { glossary: vec![term("A", "Ana")], ..Default::default() };
+        assert_eq!(rebuild(&entries, &cfg), "<Ana>\"Bom dia\"\n<B>\"Oi\"");
+        assert_eq!(rebuild(&entries, &RebuildConfig::default()), "<A>\"Bom dia\"\n<B>\"Oi\"");
+    }
+
+    #[test]
+    fn untranslated_policies() {
+        let entries = parsed();
+        let with = |on_untranslated| rebuild(&entries, &RebuildConfig { on_untranslated, ..Default::default() });
+
+        assert_eq!(with(UntranslatedPolicy::Original), SCRIPT);
+        assert_eq!(with(UntranslatedPolicy::Empty), "[cm]\n<A>\"\"\n@wait time=10\n");
+        assert_eq!(
+            with(UntranslatedPolicy::Marker("[TODO]".into())),
+            "[cm]\n<A>\"[TODO]\"\n@wait time=10\n[TODO]"
         );
+        assert!(UntranslatedPolicy::parse("skip", "").is_err());
+    }
+
+    #[test]
+    fn normalization_touches_translations_only() {
+        let entries: Vec<CoreEntry> = serde_json::from_value(serde_json::json!([{
+            "entry_id": "1-text",
+            "original": "カフェ",
+            "translation": "cafe\u{301}",
+            "is_translatable": true,
+            "prefix": "e\u{301} ",
+            "suffix": "",
+        }]))
+        .unwrap();
+        let with = |normalization| rebuild(&entries, &RebuildConfig { normalization, ..Default::default() });
 
-        out.push(line);
+        assert_eq!(with(OutputNormalization::Nfc), "e\u{301} caf\u{e9}");
+        assert_eq!(with(OutputNormalization::Nfd), "e\u{301} cafe\u{301}");
+        assert_eq!(with(OutputNormalization::None), "e\u{301} cafe\u{301}");
     }
 
-    out.join("\n")
+    #[test]
+    fn comments_are_never_written() {
+        let mut entries = parsed();
+        entries[1].translation = "Bom dia".into();
+        entries[1].comment = Some("revisar".into());
+        let out = rebuild(&entries, &RebuildConfig::default());
+        assert!(!out.contains("revisar"));
+
+        let reloaded: Vec<CoreEntry> = serde_json::from_str(&serde_json::to_string(&entries).unwrap()).unwrap();
+        assert_eq!(reloaded[1].comment.as_deref(), Some("revisar"));
+    }
+
+    #[test]
+    fn hints_are_never_written() {
+        let mut entries = parsed();
+        entries[1].translation = "Bom dia".into();
+        entries[1].prompt_hint = Some("tom irônico".into());
+        assert!(!rebuild(&entries, &RebuildConfig::default()).contains("irônico"));
+    }
+
+    #[test]
+    fn inconsistent_entries_are_reported_and_rebuilt_by_is_translatable() {
+        let entries: Vec<CoreEntry> = serde_json::from_value(serde_json::json!([
+            {"entry_id": "1-text", "original": "a", "translation": "A", "is_translatable": true, "raw_line": "x"},
+            {"entry_id": "2-raw", "is_translatable": false},
+            {"entry_id": "3-raw", "original": "b", "is_translatable": false, "raw_line": "@b"},
+            {"entry_id": "4-raw", "is_translatable": false, "raw_line": "@wait"},
+        ]))
+        .unwrap();
+        let flagged: Vec<String> = inconsistencies(&entries).into_iter().map(|i| i.entry_id).collect();
+        assert_eq!(flagged, ["1-text", "2-raw", "3-raw"]);
+        assert_eq!(rebuild(&entries, &RebuildConfig::default()), "A\n\n@b\n@wait");
+    }
+
+    #[test]
+    fn patch_lists_only_changed_lines() {
+        let mut entries = parsed();
+        entries[1].translation = "Bom dia".into();
+        entries[3].translation = "Que dia bonito".into();
+
+        let changes = patch(&entries, &RebuildConfig::default());
+        let lines: Vec<(usize, &str)> = changes.iter().map(|c| (c.line_number, c.after.as_str())).collect();
+        assert_eq!(lines, [(2, "<A>\"Bom dia\""), (4, "Que dia bonito")]);
+        assert_eq!(changes[0].before, "<A>\"おはよう\"");
+    }
+
+    #[test]
+    fn indentation_comes_from_the_source_line() {
+        let mut entries = kirikiri::parse("  こんにちは", &ParseOptions::default());
+        entries[0].translation = "    Olá ".into();
+        assert_eq!(rebuild(&entries, &RebuildConfig::default()), "  Olá");
+
+        let issues = qa::run(&entries);
+        assert!(issues.iter().any(|i| i.code == "EDGE_WHITESPACE"));
+    }
+
+    #[test]
+    fn line_count_mismatch_blocks_rebuild() {
+        let opts = ParseOptions { block_terminators: vec!["p".into()], ..Default::default() };
+        let mut entries = kirikiri::parse("一行目\n二行目[p]", &opts);
+        entries[0].translation = "Tudo numa linha[p]".into();
+        let err = check_line_counts(&entries).unwrap_err();
+        assert!(err.contains("1 line(s), the source spans 2"), "{err}");
+
+        entries[0].translation = "Linha um\nlinha dois[p]".into();
+        assert!(check_line_counts(&entries).is_ok());
+    }
+
+    #[test]
+    fn rtl_markers_wrap_translations_only() {
+        let mut entries = kirikiri::parse("<A>\"やあ\"", &ParseOptions::default());
+        entries[0].translation = "(שלום)".into();
+        let cfg = RebuildConfig {
+            rtl: Some(RtlOptions { enabled: Some(true), markers: true, mirror_brackets: true }),
+            ..Default::default()
+        };
+        assert_eq!(rebuild(&entries, &cfg), "<A>\"\u{202B})שלום(\u{202C}\"");
+    }
+
+    #[test]
+    fn inline_speaker_is_localized_through_the_glossary() {
+        let mut entries = kirikiri::parse("【明日香】おはよう", &ParseOptions::default());
+        entries[0].translation = "Bom dia".into();
+        let term = GlossaryTerm { source: "明日香".into(), target: "Asuka".into(), note: String::new(), speaker: None };
+        let cfg = RebuildConfig { glossary: vec![term], ..Default::default() };
+        assert_eq!(rebuild(&entries, &cfg), "【Asuka】Bom dia");
+    }
 }
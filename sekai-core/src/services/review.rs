@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::log_info;
+use crate::model::entry::{CoreEntry, EntryStatus};
+use crate::services::fsutil::append_line;
+use crate::services::qa::{self, QaOptions, Severity};
+use crate::services::translation_memory::store::unix_now;
+
+const REVIEW_LOG: &str = "review_log.jsonl";
+
+/// One sign-off, as appended to the project's review log.
+#[derive(Debug, Serialize)]
+struct ReviewRecord<'a> {
+    entry_id: &'a str,
+    reviewer: &'a str,
+    /// Unix seconds.
+    reviewed_at: u64,
+    translation: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Rejection {
+    pub entry_id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReviewReport {
+    pub reviewed: Vec<String>,
+    pub rejected: Vec<Rejection>,
+}
+
+/// Marks `ids` as `Reviewed` by `reviewer`, logging each sign-off to
+/// `review_log.jsonl` in the project. An entry is refused while its
+/// translation is empty or QA, run with `qa_opts`, reports an error for it;
+/// the others still go through.
+pub fn review(
+    project_path: &str,
+    entries: &mut [CoreEntry],
+    ids: &[String],
+    reviewer: &str,
+    qa_opts: &QaOptions,
+) -> Result<ReviewReport, String> {
+    let dir = Path::new(project_path);
+    if !dir.is_dir() {
+        return Err("project directory not found".into());
+    }
+    let log_path = dir.join(REVIEW_LOG);
+
+    let qa_errors: HashSet<String> = qa::run_with(entries, qa_opts)
+        .into_iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .map(|issue| issue.entry_id)
+        .collect();
+
+    let mut report = ReviewReport {
+        reviewed: Vec::new(),
+        rejected: Vec::new(),
+    };
+    let now = unix_now();
+
+    for id in ids {
+        let reject = |reason: &str| Rejection {
+            entry_id: id.clone(),
+            reason: reason.to_string(),
+        };
+
+        let Some(e) = entries.iter_mut().find(|e| &e.entry_id == id) else {
+            report.rejected.push(reject("entry not found"));
+            continue;
+        };
+        if e.translation.trim().is_empty() {
+            report.rejected.push(reject("translation is empty"));
+            continue;
+        }
+        if qa_errors.contains(id) {
+            report.rejected.push(reject("open QA errors"));
+            continue;
+        }
+
+        let record = ReviewRecord {
+            entry_id: id,
+            reviewer,
+            reviewed_at: now,
+            translation: &e.translation,
+        };
+        let line = serde_json::to_string(&record).map_err(|e| e.to_string())?;
+        append_line(&log_path, &line).map_err(|e| format!("failed to write {REVIEW_LOG}: {e}"))?;
+
+        e.status = EntryStatus::Reviewed;
+        report.reviewed.push(id.clone());
+    }
+
+    log_info!(
+        "review",
+        "{reviewer}: {} reviewed, {} rejected",
+        report.reviewed.len(),
+        report.rejected.len()
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sekai-core-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entry(entry_id: &str, original: &str, translation: &str) -> CoreEntry {
+        serde_json::from_value(serde_json::json!({
+            "entry_id": entry_id,
+            "original": original,
+            "translation": translation,
+            "is_translatable": true,
+            "prefix": "",
+            "suffix": "",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn empty_and_qa_failing_entries_are_refused() {
+        let dir = temp_dir("review");
+        let project = dir.to_str().unwrap();
+        let mut block = entry("3-text", "一\n二", "Um, dois");
+        block.joins = vec!["\n".into()];
+        let mut entries = vec![entry("1-text", "はい", "Sim"), entry("2-text", "いいえ", ""), block];
+        let ids: Vec<String> = ["1-text", "2-text", "3-text", "9-text"].map(String::from).to_vec();
+
+        let report = review(project, &mut entries, &ids, "ana", &QaOptions::default()).unwrap();
+        assert_eq!(report.reviewed, ["1-text"]);
+        let reasons: Vec<&str> = report.rejected.iter().map(|r| r.reason.as_str()).collect();
+        assert_eq!(reasons, ["translation is empty", "open QA errors", "entry not found"]);
+        assert_eq!(entries[0].status, EntryStatus::Reviewed);
+        assert_eq!(entries[2].status, EntryStatus::Untranslated);
+
+        let log = fs::read_to_string(dir.join(REVIEW_LOG)).unwrap();
+        assert_eq!(log.lines().count(), 1);
+        assert!(log.contains("\"reviewer\":\"ana\""));
+    }
+
+    #[test]
+    fn missing_project_is_an_error() {
+        let missing = temp_dir("review-missing").join("nope");
+        let result = review(missing.to_str().unwrap(), &mut [], &[], "ana", &QaOptions::default());
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use serde::Deserialize;
+
+/// RIGHT-TO-LEFT EMBEDDING and POP DIRECTIONAL FORMATTING.
+const RLE: char = '\u{202B}';
+const PDF: char = '\u{202C}';
+
+/// Primary language subtags written right to left.
+const RTL_LANGS: &[&str] = &["ar", "arc", "ckb", "dv", "fa", "he", "iw", "ps", "sd", "ug", "ur", "yi"];
+
+/// Bracket pairs swapped by `mirror_brackets`. `[]` and `<>` are left alone
+/// because they are script markup, not prose.
+const MIRRORED: &[(char, char)] = &[('(', ')'), ('（', '）'), ('«', '»'), ('‹', '›'), ('{', '}')];
+
+/// How translated text is prepared for an engine rendering a right-to-left
+/// target. Applied only when the target is RTL (detected from its language
+/// code unless `enabled` says otherwise).
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct RtlOptions {
+    /// Forces RTL handling on or off regardless of the language code.
+    pub enabled: Option<bool>,
+    /// Wrap each translated span in U+202B … U+202C.
+    pub markers: bool,
+    /// Swap opening and closing brackets, for engines that lay glyphs out
+    /// left to right without bidi mirroring.
+    pub mirror_brackets: bool,
+}
+
+impl Default for RtlOptions {
+    fn default() -> Self {
+        RtlOptions { enabled: None, markers: true, mirror_brackets: false }
+    }
+}
+
+impl RtlOptions {
+    /// Whether `target_lang` gets RTL handling under these options.
+    pub fn active_for(&self, target_lang: &str) -> bool {
+        self.enabled.unwrap_or_else(|| is_rtl(target_lang))
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        let text: String = if self.mirror_brackets { text.chars().map(mirror).collect() } else { text.to_string() };
+        if self.markers && !text.is_empty() {
+            format!("{RLE}{text}{PDF}")
+        } else {
+            text
+        }
+    }
+}
+
+/// True for codes such as `ar`, `he-IL` or `fa_IR`.
+pub fn is_rtl(lang: &str) -> bool {
+    let primary = lang.trim().split(['-', '_']).next().unwrap_or("").to_lowercase();
+    RTL_LANGS.contains(&primary.as_str())
+}
+
+fn mirror(c: char) -> char {
+    MIRRORED
+        .iter()
+        .find_map(|&(open, close)| match c {
+            _ if c == open => Some(close),
+            _ if c == close => Some(open),
+            _ => None,
+        })
+        .unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rtl_is_read_from_the_primary_subtag() {
+        assert!(is_rtl("ar") && is_rtl("he-IL") && is_rtl("fa_IR"));
+        assert!(!is_rtl("pt-BR") && !is_rtl("arn"));
+        assert!(RtlOptions { enabled: Some(true), ..Default::default() }.active_for("en"));
+        assert!(!RtlOptions { enabled: Some(false), ..Default::default() }.active_for("ar"));
+    }
+
+    #[test]
+    fn markers_wrap_and_brackets_mirror() {
+        let opts = RtlOptions { enabled: None, markers: true, mirror_brackets: true };
+        assert_eq!(opts.apply("(مرحبا) [r]"), "\u{202B})مرحبا( [r]\u{202C}");
+        assert_eq!(opts.apply(""), "");
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::model::entry::{CoreEntry, EntryStatus};
+
+pub enum SplitPoints<'a> {
+    /// Character (not byte) offsets into `original`.
+    Offsets(Vec<usize>),
+    /// Split after every occurrence of the delimiter, which stays with the
+    /// preceding piece.
+    Delimiter(&'a str),
+}
+
+/// Replaces the entry `entry_id` with one entry per piece of its original.
+///
+/// Only the first piece keeps the prefix and only the last keeps the suffix,
+/// so the pieces tile back to the original line. All pieces share a `group`
+/// and rebuild joins them onto a single line.
+pub fn split(entries: &[CoreEntry], entry_id: &str, points: SplitPoints) -> Result<Vec<CoreEntry>, String> {
+    let pos = entries
+        .iter()
+        .position(|e| e.entry_id == entry_id)
+        .ok_or_else(|| format!("entry not found: {entry_id}"))?;
+
+    let target = &entries[pos];
+    if !target.is_translatable {
+        return Err(format!("entry is not translatable: {entry_id}"));
+    }
+    if !target.joins.is_empty() || target.original.contains('\n') {
+        return Err(format!("entry spans several lines: {entry_id}"));
+    }
+
+    let pieces = split_text(&target.original, &points)?;
+    if pieces.len() < 2 {
+        return Err("split points produce a single piece".into());
+    }
+
+    let group = target.group.clone().unwrap_or_else(|| target.entry_id.clone());
+    let last = pieces.len() - 1;
+
+    let mut out: Vec<CoreEntry> = Vec::with_capacity(entries.len() + last);
+    out.extend_from_slice(&entries[..pos]);
+
+    for (n, piece) in pieces.into_iter().enumerate() {
+        let mut e = target.clone();
+        e.entry_id = format!("{}.{}", target.entry_id, n + 1);
+        e.original = piece.to_string();
+        e.translation = String::new();
+        e.history.clear();
+        e.status = EntryStatus::Untranslated;
+        e.group = Some(group.clone());
+        if n > 0 {
+            e.prefix = Some(String::new());
+        }
+        if n < last {
+            e.suffix = Some(String::new());
+        }
+        out.push(e);
+    }
+
+    out.extend_from_slice(&entries[pos + 1..]);
+    Ok(out)
+}
+
+/// Replaces the adjacent translatable entries `entry_ids` (in order, nothing
+/// between them) with one entry covering their lines, the inverse of a
+/// parser splitting one sentence over several lines.
+///
+/// The merged entry keeps the first's ID, prefix and speaker and the last's
+/// suffix; its original is the pieces joined by `\n`, with what separated
+/// them in the source kept in `joins` so rebuild writes the same number of
+/// lines back. It is translated when every piece was, one line per piece.
+pub fn merge(entries: &[CoreEntry], entry_ids: &[String]) -> Result<Vec<CoreEntry>, String> {
+    if entry_ids.len() < 2 {
+        return Err("at least two entry_ids are required".into());
+    }
+    let pos = entries
+        .iter()
+        .position(|e| e.entry_id == entry_ids[0])
+        .ok_or_else(|| format!("entry not found: {}", entry_ids[0]))?;
+    let pieces = entries
+        .get(pos..pos + entry_ids.len())
+        .filter(|run| run.iter().zip(entry_ids).all(|(e, id)| e.entry_id == *id))
+        .ok_or("entry_ids must name adjacent entries in order")?;
+
+    for e in pieces {
+        if !e.is_translatable {
+            return Err(format!("entry is not translatable: {}", e.entry_id));
+        }
+        if e.group.is_some() || !e.joins.is_empty() || e.original.contains('\n') {
+            return Err(format!("entry already spans or shares a line: {}", e.entry_id));
+        }
+    }
+
+    let first = &pieces[0];
+    let last = &pieces[pieces.len() - 1];
+    let all_translated = pieces.iter().all(|e| !e.translation.trim().is_empty());
+
+    let mut merged = first.clone();
+    merged.original = pieces.iter().map(|e| e.original.as_str()).collect::<Vec<_>>().join("\n");
+    merged.suffix = last.suffix.clone();
+    merged.history.clear();
+    merged.joins = pieces
+        .windows(2)
+        .map(|w| format!("{}\n{}", w[0].suffix.as_deref().unwrap_or(""), w[1].prefix.as_deref().unwrap_or("")))
+        .collect();
+    if all_translated {
+        merged.translation = pieces.iter().map(|e| e.translation.trim()).collect::<Vec<_>>().join("\n");
+        merged.status = pieces.iter().map(|e| e.status).min().unwrap_or_default();
+    } else {
+        merged.translation = String::new();
+        merged.status = EntryStatus::Untranslated;
+    }
+
+    let mut out: Vec<CoreEntry> = Vec::with_capacity(entries.len() - pieces.len() + 1);
+    out.extend_from_slice(&entries[..pos]);
+    out.push(merged);
+    out.extend_from_slice(&entries[pos + pieces.len()..]);
+    Ok(out)
+}
+
+#[derive(Debug, Serialize)]
+pub struct Renumbered {
+    pub entries: Vec<CoreEntry>,
+    /// Old `entry_id` to new, for every entry.
+    pub id_map: BTreeMap<String, String>,
+}
+
+/// Recomputes `line_number` and IDs in the parser's scheme (`{line}-text`,
+/// `{line}-raw`) from the entries' order, as rebuild would lay them out.
+///
+/// Consecutive entries of one `group` share a line and get `.{n}` suffixes;
+/// the group itself is renamed after the line. A merged entry takes its first
+/// line's number and the lines it spans are skipped. Translation and status
+/// are kept.
+pub fn renumber(entries: &[CoreEntry]) -> Renumbered {
+    let mut out: Vec<CoreEntry> = Vec::with_capacity(entries.len());
+    let mut id_map: BTreeMap<String, String> = BTreeMap::new();
+
+    let mut i = 0;
+    let mut line = 0;
+    while i < entries.len() {
+        line += 1;
+
+        let run = match entries[i].group.as_deref() {
+            Some(g) => entries[i..].iter().take_while(|e| e.group.as_deref() == Some(g)).count(),
+            None => 1,
+        };
+        let new_group = entries[i].group.as_ref().map(|_| format!("{line}-text"));
+
+        for (n, old) in entries[i..i + run].iter().enumerate() {
+            let kind = if old.is_translatable { "text" } else { "raw" };
+            let new_id = if run > 1 {
+                format!("{line}-{kind}.{}", n + 1)
+            } else {
+                format!("{line}-{kind}")
+            };
+
+            let mut e = old.clone();
+            e.entry_id = new_id.clone();
+            e.line_number = line;
+            e.group = new_group.clone();
+            id_map.insert(old.entry_id.clone(), new_id);
+            out.push(e);
+        }
+
+        // A merged entry spans one more line per join.
+        line += entries[i..i + run].iter().map(|e| e.joins.len()).sum::<usize>();
+        i += run;
+    }
+
+    Renumbered { entries: out, id_map }
+}
+
+fn split_text<'t>(text: &'t str, points: &SplitPoints) -> Result<Vec<&'t str>, String> {
+    match points {
+        SplitPoints::Delimiter(d) => {
+            if d.is_empty() {
+                return Err("delimiter must not be empty".into());
+            }
+            let mut pieces = Vec::new();
+            let mut rest = text;
+            while let Some(i) = rest.find(d) {
+                let cut = i + d.len();
+                if cut == rest.len() {
+                    break;
+                }
+                pieces.push(&rest[..cut]);
+                rest = &rest[cut..];
+            }
+            pieces.push(rest);
+            Ok(pieces)
+        }
+        SplitPoints::Offsets(offsets) => {
+            let char_count = text.chars().count();
+            let mut pieces = Vec::new();
+            let mut start_byte = 0usize;
+            let mut prev = 0usize;
+
+            for &off in offsets {
+                if off <= prev || off >= char_count {
+                    return Err(format!("invalid split offset: {off}"));
+                }
+                let byte = text.char_indices().nth(off).map(|(b, _)| b).unwrap_or(text.len());
+                pieces.push(&text[start_byte..byte]);
+                start_byte = byte;
+                prev = off;
+            }
+
+            pieces.push(&text[start_byte..]);
+            Ok(pieces)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::kirikiri::{self, ParseOptions};
+    use crate::services::rebuild::{self, RebuildConfig};
+
+    fn ids(entries: &[CoreEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.entry_id.as_str()).collect()
+    }
+
+    #[test]
+    fn split_then_rebuild_gives_back_the_line() {
+        let text = "<A>\"一つ目。二つ目。三つ目\"";
+        let entries = kirikiri::parse(text, &ParseOptions::default());
+        let pieces = split(&entries, "1-text", SplitPoints::Delimiter("。")).unwrap();
+
+        assert_eq!(ids(&pieces), ["1-text.1", "1-text.2", "1-text.3"]);
+        assert_eq!(pieces[1].original, "二つ目。");
+        assert_eq!(rebuild::rebuild(&pieces, &RebuildConfig::default()), text);
+    }
+
+    #[test]
+    fn split_offsets_count_characters() {
+        let entries = kirikiri::parse("あいうえ", &ParseOptions::default());
+        let pieces = split(&entries, "1-text", SplitPoints::Offsets(vec![1, 3])).unwrap();
+        let originals: Vec<&str> = pieces.iter().map(|e| e.original.as_str()).collect();
+        assert_eq!(originals, ["あ", "いう", "え"]);
+        assert!(split(&entries, "1-text", SplitPoints::Offsets(vec![4])).is_err());
+    }
+
+    #[test]
+    fn renumber_follows_a_split_and_a_removed_line() {
+        let entries = kirikiri::parse("一。二。\n@wait time=10\n三", &ParseOptions::default());
+        let mut entries = split(&entries, "1-text", SplitPoints::Delimiter("。")).unwrap();
+        entries.retain(|e| e.entry_id != "2-raw");
+
+        let renumbered = renumber(&entries);
+        assert_eq!(ids(&renumbered.entries), ["1-text.1", "1-text.2", "2-text"]);
+        assert_eq!(renumbered.entries[0].group.as_deref(), Some("1-text"));
+        assert_eq!((renumbered.id_map["3-text"].as_str(), renumbered.entries[2].line_number), ("2-text", 2));
+        assert_eq!(rebuild::rebuild(&renumbered.entries, &RebuildConfig::default()), "一。二。\n三");
+    }
+
+    #[test]
+    fn merge_then_rebuild_gives_back_the_lines() {
+        let text = "*label\n  前半の文が\n後半に続く。\n@wait time=10";
+        let entries = kirikiri::parse(text, &ParseOptions::default());
+        let merged = merge(&entries, &["2-text".into(), "3-text".into()]).unwrap();
+
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[1].original, "前半の文が\n後半に続く。");
+        assert_eq!(rebuild::rebuild(&merged, &RebuildConfig::default()), text);
+    }
+
+    #[test]
+    fn merged_entry_cannot_be_split() {
+        let entries = kirikiri::parse("一行目。\n二行目。", &ParseOptions::default());
+        let merged = merge(&entries, &["1-text".into(), "2-text".into()]).unwrap();
+        let err = split(&merged, "1-text", SplitPoints::Delimiter("。")).unwrap_err();
+        assert!(err.contains("spans several lines"), "{err}");
+    }
+
+    #[test]
+    fn merge_rejects_entries_that_are_not_adjacent() {
+        let entries = kirikiri::parse("一\n@wait time=10\n二", &ParseOptions::default());
+        assert!(merge(&entries, &["1-text".into(), "3-text".into()]).is_err());
+    }
+
+    #[test]
+    fn renumber_follows_split_and_merge() {
+        let text = "一。二。\n@wait time=10\n三\n四\n五";
+        let entries = kirikiri::parse(text, &ParseOptions::default());
+        let entries = split(&entries, "1-text", SplitPoints::Delimiter("。")).unwrap();
+        let entries = merge(&entries, &["3-text".into(), "4-text".into()]).unwrap();
+
+        let renumbered = renumber(&entries);
+        assert_eq!(ids(&renumbered.entries), ["1-text.1", "1-text.2", "2-raw", "3-text", "5-text"]);
+        assert_eq!(renumbered.id_map["5-text"], "5-text");
+        assert_eq!(rebuild::rebuild(&renumbered.entries, &RebuildConfig::default()), text);
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use crate::model::entry::{CoreEntry, EntryStatus};
+use crate::services::qa::{self, Severity};
+
+#[derive(Debug, Serialize)]
+pub struct ProgressReport {
+    /// Translatable entries; structural lines are not counted anywhere.
+    pub total: usize,
+    pub by_status: BTreeMap<EntryStatus, usize>,
+    /// `Translated` plus `Reviewed`, as a percentage of `total`.
+    pub percent_translated: f64,
+    pub percent_reviewed: f64,
+    pub qa_errors: usize,
+    pub qa_warnings: usize,
+    /// Characters of original text still lacking a translation.
+    pub remaining_chars: usize,
+}
+
+pub fn progress<'a>(files: impl IntoIterator<Item = &'a [CoreEntry]>) -> ProgressReport {
+    let mut by_status: BTreeMap<EntryStatus, usize> = BTreeMap::new();
+    let mut total = 0usize;
+    let mut remaining_chars = 0usize;
+    let mut qa_errors = 0usize;
+    let mut qa_warnings = 0usize;
+
+    for entries in files {
+        for e in entries.iter().filter(|e| e.is_translatable) {
+            total += 1;
+            *by_status.entry(e.status).or_insert(0) += 1;
+            if e.translation.trim().is_empty() {
+                remaining_chars += e.original.chars().count();
+            }
+        }
+
+        for issue in qa::run(entries) {
+            match issue.severity {
+                Severity::Error => qa_errors += 1,
+                Severity::Warning => qa_warnings += 1,
+                Severity::Info => {}
+            }
+        }
+    }
+
+    let count = |s: EntryStatus| by_status.get(&s).copied().unwrap_or(0);
+    let percent = |n: usize| if total == 0 { 0.0 } else { n as f64 * 100.0 / total as f64 };
+
+    ProgressReport {
+        total,
+        percent_translated: percent(count(EntryStatus::Translated) + count(EntryStatus::Reviewed)),
+        percent_reviewed: percent(count(EntryStatus::Reviewed)),
+        by_status,
+        qa_errors,
+        qa_warnings,
+        remaining_chars,
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpeakerCount {
+    pub speaker: String,
+    pub count: usize,
+}
+
+/// Distinct speakers with how many entries name them, most frequent first
+/// (ties by name). Each speaker of a shared line counts; entries without a
+/// speaker are left out.
+pub fn speakers(entries: &[CoreEntry]) -> Vec<SpeakerCount> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+
+    for e in entries {
+        let names: Vec<&str> = if e.speakers.is_empty() {
+            e.speaker.as_deref().into_iter().collect()
+        } else {
+            e.speakers.iter().map(String::as_str).collect()
+        };
+        for name in names.into_iter().map(str::trim).filter(|n| !n.is_empty()) {
+            *counts.entry(name).or_insert(0) += 1;
+        }
+    }
+
+    let mut out: Vec<SpeakerCount> = counts
+        .into_iter()
+        .map(|(speaker, count)| SpeakerCount {
+            speaker: speaker.to_string(),
+            count,
+        })
+        .collect();
+    out.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.speaker.cmp(&b.speaker)));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(entry_id: &str, original: &str, translation: &str, status: EntryStatus) -> CoreEntry {
+        let mut e: CoreEntry = serde_json::from_value(json!({
+            "entry_id": entry_id,
+            "original": original,
+            "translation": translation,
+            "is_translatable": true,
+            "prefix": "",
+            "suffix": "",
+        }))
+        .unwrap();
+        e.status = status;
+        e
+    }
+
+    #[test]
+    fn progress_counts_translatable_entries_across_files() {
+        let a = [
+            entry("1-text", "はい", "Sim", EntryStatus::Reviewed),
+            entry("2-text", "いいえ", "", EntryStatus::Untranslated),
+        ];
+        let b = [
+            entry("1-text", "おはよう", "Bom dia", EntryStatus::Translated),
+            entry("2-text", "ね", "", EntryStatus::Translated),
+        ];
+        let raw: Vec<CoreEntry> = serde_json::from_value(json!([{"entry_id": "3-raw", "raw_line": "@wait"}])).unwrap();
+
+        let report = progress([a.as_slice(), b.as_slice(), raw.as_slice()]);
+        assert_eq!(report.total, 4);
+        assert_eq!(report.percent_translated, 75.0);
+        assert_eq!(report.percent_reviewed, 25.0);
+        assert_eq!(report.remaining_chars, 4);
+        assert_eq!(report.qa_errors, 1);
+    }
+
+    #[test]
+    fn empty_project_is_zero_percent() {
+        let report = progress(std::iter::empty());
+        assert_eq!((report.total, report.percent_translated), (0, 0.0));
+    }
+
+    #[test]
+    fn every_speaker_of_a_shared_line_counts() {
+        let entries: Vec<CoreEntry> = serde_json::from_value(json!([
+            {"entry_id": "1-text", "speaker": "A", "speakers": ["A", "B"]},
+            {"entry_id": "2-text", "speaker": "B"},
+            {"entry_id": "3-text", "speaker": "C"},
+            {"entry_id": "4-text"},
+        ]))
+        .unwrap();
+        let counts: Vec<(String, usize)> = speakers(&entries).into_iter().map(|s| (s.speaker, s.count)).collect();
+        assert_eq!(counts, [("B".to_string(), 2), ("A".to_string(), 1), ("C".to_string(), 1)]);
+    }
+}
@@ -0,0 +1,80 @@
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Substitution {
+    pub from: String,
+    #[serde(default)]
+    pub to: String,
+}
+
+/// Rejects rules that would make a second pass change the text again: an
+/// empty `from`, or a `to` that still contains some rule's `from`.
+pub fn validate(subs: &[Substitution]) -> Result<(), String> {
+    for s in subs {
+        if s.from.is_empty() {
+            return Err("substitution `from` must not be empty".into());
+        }
+    }
+    for s in subs {
+        if let Some(other) = subs.iter().find(|o| s.to.contains(o.from.as_str())) {
+            return Err(format!(
+                "substitution {:?} -> {:?} is not idempotent: result contains {:?}",
+                s.from, s.to, other.from
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Single left-to-right pass; at each position the longest matching `from`
+/// wins, and replaced text is never rescanned.
+pub fn apply(text: &str, subs: &[Substitution]) -> String {
+    if subs.is_empty() {
+        return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(ch) = rest.chars().next() {
+        let best = subs
+            .iter()
+            .filter(|s| !s.from.is_empty() && rest.starts_with(s.from.as_str()))
+            .max_by_key(|s| s.from.len());
+
+        match best {
+            Some(s) => {
+                out.push_str(&s.to);
+                rest = &rest[s.from.len()..];
+            }
+            None => {
+                out.push(ch);
+                rest = &rest[ch.len_utf8()..];
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sub(from: &str, to: &str) -> Substitution {
+        Substitution { from: from.into(), to: to.into() }
+    }
+
+    #[test]
+    fn longest_match_wins_in_a_single_pass() {
+        let subs = [sub("…", "..."), sub("……", "—"), sub("é", "e")];
+        assert_eq!(apply("Olé……!…", &subs), "Ole—!...");
+    }
+
+    #[test]
+    fn rules_that_feed_each_other_are_rejected() {
+        assert!(validate(&[sub("a", "b"), sub("b", "c")]).is_err());
+        assert!(validate(&[sub("", "x")]).is_err());
+        assert!(validate(&[sub("ã", "a"), sub("õ", "o")]).is_ok());
+    }
+}
@@ -0,0 +1,140 @@
+use std::ops::Range;
+
+/// Always sentence-final, whatever follows.
+const CJK_TERMINATORS: &[char] = &['。', '！', '？', '…', '‥'];
+/// Sentence-final only when followed by whitespace or the end of the text.
+const LATIN_TERMINATORS: &[char] = &['.', '!', '?'];
+/// Closers that belong to the sentence they end (`「……。」`, `"Go!"`).
+const CLOSERS: &[char] = &['」', '』', '）', ')', '"', '\'', '”', '’', '】'];
+
+const ABBREVIATIONS_EN: &[&str] = &["mr", "mrs", "ms", "dr", "st", "vs", "etc", "e.g", "i.e", "no"];
+const ABBREVIATIONS_PT: &[&str] = &["sr", "sra", "srta", "dr", "dra", "prof", "etc", "p.ex", "n.º", "nº"];
+
+/// Longest prefix of `s` at most `max_bytes` long that ends on a char
+/// boundary. Any cap measured in bytes goes through here: slicing at a raw
+/// offset panics as soon as it lands inside a multibyte (CJK) character.
+pub fn truncate_bytes(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// Byte ranges of the sentences in `text`, without surrounding whitespace.
+///
+/// CJK terminators always end a sentence; `.!?` do so only before whitespace
+/// or the end, and not after an abbreviation of `lang` or a single-letter
+/// initial. Runs of terminators and trailing closing quotes stay with their
+/// sentence. Text without any terminator is one sentence.
+pub fn split_sentences(text: &str, lang: &str) -> Vec<Range<usize>> {
+    let abbreviations = abbreviations_for(lang);
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+
+    let mut out: Vec<Range<usize>> = Vec::new();
+    let mut start = 0usize;
+    let mut i = 0usize;
+
+    while i < chars.len() {
+        let (_, c) = chars[i];
+        let cjk = CJK_TERMINATORS.contains(&c);
+        if !cjk && !LATIN_TERMINATORS.contains(&c) {
+            i += 1;
+            continue;
+        }
+
+        let mut j = i + 1;
+        while j < chars.len() && (CJK_TERMINATORS.contains(&chars[j].1) || LATIN_TERMINATORS.contains(&chars[j].1)) {
+            j += 1;
+        }
+        while j < chars.len() && CLOSERS.contains(&chars[j].1) {
+            j += 1;
+        }
+
+        let end = chars.get(j).map_or(text.len(), |&(b, _)| b);
+        let at_break = j == chars.len() || chars[j].1.is_whitespace();
+        let ends_here = if cjk || chars[i..j].iter().any(|&(_, ch)| CJK_TERMINATORS.contains(&ch)) {
+            true
+        } else {
+            at_break && !(c == '.' && j == i + 1 && is_abbreviation(&text[start..chars[i].0], abbreviations))
+        };
+
+        if ends_here {
+            push_trimmed(&mut out, text, start..end);
+            start = end;
+        }
+        i = j;
+    }
+
+    push_trimmed(&mut out, text, start..text.len());
+    out
+}
+
+fn push_trimmed(out: &mut Vec<Range<usize>>, text: &str, range: Range<usize>) {
+    let slice = &text[range.clone()];
+    let lead = slice.len() - slice.trim_start().len();
+    let trail = slice.len() - slice.trim_end().len();
+    if lead + trail < slice.len() {
+        out.push(range.start + lead..range.end - trail);
+    }
+}
+
+fn abbreviations_for(lang: &str) -> &'static [&'static str] {
+    if lang.to_ascii_lowercase().starts_with("pt") {
+        ABBREVIATIONS_PT
+    } else {
+        ABBREVIATIONS_EN
+    }
+}
+
+/// Whether the word just before a `.` is an abbreviation or an initial.
+fn is_abbreviation(before: &str, abbreviations: &[&str]) -> bool {
+    let word = before.rsplit(|c: char| c.is_whitespace() || c == '(').next().unwrap_or("");
+    if word.is_empty() {
+        return false;
+    }
+
+    let mut letters = word.chars();
+    if let (Some(first), None) = (letters.next(), letters.next()) {
+        if first.is_uppercase() {
+            return true;
+        }
+    }
+
+    let lower = word.to_lowercase();
+    abbreviations.iter().any(|a| *a == lower)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sentences<'t>(text: &'t str, lang: &str) -> Vec<&'t str> {
+        split_sentences(text, lang).into_iter().map(|r| &text[r]).collect()
+    }
+
+    #[test]
+    fn truncation_never_splits_a_character() {
+        assert_eq!(truncate_bytes("日本語", 4), "日");
+        assert_eq!(truncate_bytes("日本語", 2), "");
+        assert_eq!(truncate_bytes("abc", 10), "abc");
+    }
+
+    #[test]
+    fn cjk_sentences_keep_their_closers() {
+        assert_eq!(sentences("「行こう。」そうだね！本当に？", "ja"), ["「行こう。」", "そうだね！", "本当に？"]);
+        assert_eq!(sentences("終わらない", "ja"), ["終わらない"]);
+    }
+
+    #[test]
+    fn latin_sentences_skip_abbreviations_and_initials() {
+        assert_eq!(
+            sentences("Mr. Smith saw J. Doe. \"Really?!\" he asked. 3.5 is fine.", "en"),
+            ["Mr. Smith saw J. Doe.", "\"Really?!\"", "he asked.", "3.5 is fine."]
+        );
+        assert_eq!(sentences("O Sr. Silva chegou. Que bom.", "pt-BR"), ["O Sr. Silva chegou.", "Que bom."]);
+    }
+}
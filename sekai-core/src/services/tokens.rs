@@ -0,0 +1,73 @@
+//! Token counting for estimates.
+//!
+//! With the `tiktoken` feature, OpenAI models are counted with their real BPE
+//! vocabulary. Everything else (other providers, or builds without the
+//! feature) falls back to `heuristic`.
+
+use serde::Serialize;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CountMethod {
+    Bpe,
+    Heuristic,
+}
+
+pub fn count(model: &str, text: &str) -> usize {
+    bpe_count(model, text).unwrap_or_else(|| heuristic(text))
+}
+
+/// How `count` will measure text for `model`.
+pub fn method(model: &str) -> CountMethod {
+    match bpe_count(model, "") {
+        Some(_) => CountMethod::Bpe,
+        None => CountMethod::Heuristic,
+    }
+}
+
+/// About four ASCII characters per token and one token per other char.
+/// Errs high for CJK on newer vocabularies, which merge common kana pairs.
+pub fn heuristic(text: &str) -> usize {
+    let ascii = text.chars().filter(|c| c.is_ascii()).count();
+    let other = text.chars().count() - ascii;
+    ascii.div_ceil(4) + other
+}
+
+#[cfg(feature = "tiktoken")]
+fn bpe_count(model: &str, text: &str) -> Option<usize> {
+    use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+
+    let bpe = match get_tokenizer(model)? {
+        Tokenizer::O200kBase => tiktoken_rs::o200k_base_singleton(),
+        Tokenizer::Cl100kBase => tiktoken_rs::cl100k_base_singleton(),
+        Tokenizer::P50kBase => tiktoken_rs::p50k_base_singleton(),
+        Tokenizer::P50kEdit => tiktoken_rs::p50k_edit_singleton(),
+        Tokenizer::R50kBase | Tokenizer::Gpt2 => tiktoken_rs::r50k_base_singleton(),
+    };
+    let n = bpe.lock().encode_ordinary(text).len();
+    Some(n)
+}
+
+#[cfg(not(feature = "tiktoken"))]
+fn bpe_count(_model: &str, _text: &str) -> Option<usize> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heuristic_counts_ascii_by_four_and_other_chars_by_one() {
+        assert_eq!(heuristic(""), 0);
+        assert_eq!(heuristic("hello"), 2);
+        assert_eq!(heuristic("こんにちは"), 5);
+        assert_eq!(heuristic("hi 世界"), 3);
+    }
+
+    #[test]
+    fn unknown_models_fall_back_to_the_heuristic() {
+        assert_eq!(method("claude-3-haiku"), CountMethod::Heuristic);
+        assert_eq!(count("claude-3-haiku", "こんにちは"), 5);
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use super::model::TMEntry;
+use super::normalize;
+use crate::model::glossary::GlossaryTerm;
+
+#[derive(Debug, Serialize)]
+pub struct Variant {
+    pub translation: String,
+    pub count: usize,
+}
+
+/// A source phrase that maps to more than one translation.
+#[derive(Debug, Serialize)]
+pub struct Inconsistency {
+    pub source_lang: String,
+    pub target_lang: String,
+    /// The shared phrase as compared: normalized, punctuation removed.
+    pub phrase: String,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TermExample {
+    pub original: String,
+    pub translation: String,
+}
+
+/// A glossary term whose target is missing from some TM translations of
+/// originals containing it.
+#[derive(Debug, Serialize)]
+pub struct TermDrift {
+    pub source: String,
+    pub target: String,
+    /// Entries containing the source whose translation uses the target.
+    pub consistent: usize,
+    pub deviating: Vec<TermExample>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditReport {
+    pub phrases: Vec<Inconsistency>,
+    pub terms: Vec<TermDrift>,
+}
+
+/// Groups the TM by language pair and source phrase and reports phrases whose
+/// translations differ, most frequent variant first; then checks each
+/// glossary term with a target against the TM translations.
+///
+/// Phrases compare by `phrase_key`, so `はい`, `はい。` and `はい！` (separate TM
+/// keys) fall in one group while `Sim` and `Sim.` count as one variant.
+/// Phrase key of a translation -> (first spelling seen, count).
+type VariantCounts = BTreeMap<String, (String, usize)>;
+
+pub fn inconsistencies(entries: &[TMEntry], glossary: &[GlossaryTerm]) -> AuditReport {
+    let mut groups: BTreeMap<(&str, &str, String), VariantCounts> = BTreeMap::new();
+    for e in entries.iter().filter(|e| !e.translation.trim().is_empty()) {
+        let key = (e.source_lang.as_str(), e.target_lang.as_str(), phrase_key(&e.original));
+        let slot = groups
+            .entry(key)
+            .or_default()
+            .entry(phrase_key(&e.translation))
+            .or_insert_with(|| (e.translation.clone(), 0));
+        slot.1 += 1;
+    }
+
+    let phrases = groups
+        .into_iter()
+        .filter(|(_, variants)| variants.len() > 1)
+        .map(|((src, tgt, phrase), variants)| {
+            let mut variants: Vec<Variant> = variants
+                .into_values()
+                .map(|(translation, count)| Variant { translation, count })
+                .collect();
+            variants.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.translation.cmp(&b.translation)));
+            Inconsistency {
+                source_lang: src.to_string(),
+                target_lang: tgt.to_string(),
+                phrase,
+                variants,
+            }
+        })
+        .collect();
+
+    // TM entries carry no speaker, so speaker-scoped terms cannot be checked.
+    let terms = glossary
+        .iter()
+        .filter(|t| !t.is_scoped() && !t.source.trim().is_empty() && !t.target.trim().is_empty())
+        .filter_map(|t| {
+            let source = normalize::normalize(&t.source);
+            let target = normalize::normalize(&t.target);
+
+            let mut consistent = 0usize;
+            let mut deviating: Vec<TermExample> = Vec::new();
+            for e in entries.iter().filter(|e| normalize::normalize(&e.original).contains(&source)) {
+                if normalize::normalize(&e.translation).contains(&target) {
+                    consistent += 1;
+                } else {
+                    deviating.push(TermExample {
+                        original: e.original.clone(),
+                        translation: e.translation.clone(),
+                    });
+                }
+            }
+
+            (!deviating.is_empty()).then(|| TermDrift {
+                source: t.source.clone(),
+                target: t.target.clone(),
+                consistent,
+                deviating,
+            })
+        })
+        .collect();
+
+    AuditReport { phrases, terms }
+}
+
+/// `normalize` with punctuation and spacing dropped.
+fn phrase_key(text: &str) -> String {
+    normalize::normalize(text).chars().filter(|c| c.is_alphanumeric()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tm(original: &str, translation: &str) -> TMEntry {
+        TMEntry {
+            source_lang: "ja".into(),
+            target_lang: "pt".into(),
+            original: original.into(),
+            translation: translation.into(),
+            normalized: String::new(),
+            hash: String::new(),
+            source: Default::default(),
+            created_at: 0,
+        }
+    }
+
+    fn term(speaker: Option<&str>) -> GlossaryTerm {
+        GlossaryTerm {
+            source: "魔法".into(),
+            target: "magia".into(),
+            note: String::new(),
+            speaker: speaker.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn phrases_with_several_translations_are_grouped() {
+        let entries = [tm("はい", "Sim"), tm("はい。", "Sim."), tm("はい！", "Claro!"), tm("いいえ", "Não")];
+        let report = inconsistencies(&entries, &[]);
+        assert_eq!(report.phrases.len(), 1);
+        assert_eq!(report.phrases[0].phrase, "はい");
+        let variants: Vec<(&str, usize)> =
+            report.phrases[0].variants.iter().map(|v| (v.translation.as_str(), v.count)).collect();
+        assert_eq!(variants, [("Sim", 2), ("Claro!", 1)]);
+    }
+
+    #[test]
+    fn glossary_drift_lists_deviating_translations() {
+        let entries = [tm("魔法だ", "É magia"), tm("魔法か", "Feitiço?")];
+        let report = inconsistencies(&entries, &[term(None)]);
+        assert_eq!(report.terms.len(), 1);
+        assert_eq!(report.terms[0].consistent, 1);
+        assert_eq!(report.terms[0].deviating[0].translation, "Feitiço?");
+    }
+
+    #[test]
+    fn glossary_drift_skips_scoped_terms() {
+        let entries = [tm("魔法だ", "É magia"), tm("魔法か", "Feitiço?")];
+        assert!(inconsistencies(&entries, &[term(Some("A"))]).terms.is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+use serde::Serialize;
+
+use super::matcher;
+use super::model::TMEntry;
+use crate::model::entry::CoreEntry;
+
+#[derive(Debug, Serialize)]
+pub struct CoverageReport {
+    /// Translatable entries with non-empty original text.
+    pub total: usize,
+    pub exact: usize,
+    /// Entries without an exact hit but with a fuzzy one; always 0 when
+    /// fuzzy matching is off.
+    pub fuzzy: usize,
+    /// `exact + fuzzy` as a percentage of `total`.
+    pub percent_covered: f64,
+    pub uncovered: Vec<String>,
+}
+
+/// How many of `entries` the TM could fill, matched the way
+/// `translate_with_tm` does: exact first, then fuzzy at `fuzzy_threshold`
+/// when one is given. Nothing is modified.
+pub fn coverage(
+    entries: &[CoreEntry],
+    tm: &[TMEntry],
+    source_lang: &str,
+    target_lang: &str,
+    fuzzy_threshold: Option<f32>,
+) -> CoverageReport {
+    let mut total = 0usize;
+    let mut exact = 0usize;
+    let mut fuzzy = 0usize;
+    let mut uncovered: Vec<String> = Vec::new();
+
+    for e in entries.iter().filter(|e| e.is_translatable && !e.original.trim().is_empty()) {
+        total += 1;
+
+        if matcher::exact_match(tm, source_lang, target_lang, &e.original).is_some() {
+            exact += 1;
+        } else if fuzzy_threshold
+            .is_some_and(|min| matcher::fuzzy_match(tm, source_lang, target_lang, &e.original, min).is_some())
+        {
+            fuzzy += 1;
+        } else {
+            uncovered.push(e.entry_id.clone());
+        }
+    }
+
+    let percent_covered = if total == 0 { 0.0 } else { (exact + fuzzy) as f64 * 100.0 / total as f64 };
+
+    CoverageReport {
+        total,
+        exact,
+        fuzzy,
+        percent_covered,
+        uncovered,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::translation_memory::{hash, normalize};
+
+    fn tm(original: &str, translation: &str) -> TMEntry {
+        let normalized = normalize::normalize(original);
+        TMEntry {
+            source_lang: "ja".into(),
+            target_lang: "pt".into(),
+            original: original.into(),
+            translation: translation.into(),
+            hash: hash::hash_norm(&normalized),
+            normalized,
+            source: Default::default(),
+            created_at: 0,
+        }
+    }
+
+    fn line(entry_id: &str, original: &str) -> CoreEntry {
+        serde_json::from_value(serde_json::json!({ "entry_id": entry_id, "original": original, "is_translatable": true })).unwrap()
+    }
+
+    #[test]
+    fn exact_then_fuzzy_coverage() {
+        let memory = [tm("今日はいい天気だね", "Que dia bonito"), tm("はい", "Sim")];
+        let entries = [line("1-text", "はい"), line("2-text", "今日はいい天気だよ"), line("3-text", "明日は雨"), line("4-text", "")];
+
+        let exact_only = coverage(&entries, &memory, "ja", "pt", None);
+        assert_eq!((exact_only.total, exact_only.exact, exact_only.fuzzy), (3, 1, 0));
+        assert_eq!(exact_only.uncovered, ["2-text", "3-text"]);
+
+        let with_fuzzy = coverage(&entries, &memory, "ja", "pt", Some(0.8));
+        assert_eq!(with_fuzzy.fuzzy, 1);
+        assert!((with_fuzzy.percent_covered - 200.0 / 3.0).abs() < 1e-9);
+    }
+}
@@ -22,3 +22,61 @@ pub fn exact_match<'a>(
             && e.normalized == norm
     })
 }
+
+/// Best TM entry whose normalized text is at least `min_ratio` similar to
+/// `original`, together with that similarity (0.0..=1.0).
+pub fn fuzzy_match<'a>(
+    entries: &'a [TMEntry],
+    source_lang: &str,
+    target_lang: &str,
+    original: &str,
+    min_ratio: f32,
+) -> Option<(&'a TMEntry, f32)> {
+    let trimmed = original.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    let norm: Vec<char> = normalize::normalize(trimmed).chars().collect();
+    let mut best: Option<(&TMEntry, f32)> = None;
+
+    for e in entries {
+        if e.source_lang != source_lang || e.target_lang != target_lang {
+            continue;
+        }
+
+        let cand: Vec<char> = e.normalized.chars().collect();
+        let longest = norm.len().max(cand.len());
+        if longest == 0 {
+            continue;
+        }
+
+        let len_diff = norm.len().abs_diff(cand.len());
+        if 1.0 - (len_diff as f32 / longest as f32) < min_ratio {
+            continue;
+        }
+
+        let ratio = 1.0 - levenshtein(&norm, &cand) as f32 / longest as f32;
+        if ratio >= min_ratio && best.is_none_or(|(_, r)| ratio > r) {
+            best = Some((e, ratio));
+        }
+    }
+
+    best
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur: Vec<usize> = vec![0; b.len() + 1];
+
+    for (i, ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+
+    prev[b.len()]
+}
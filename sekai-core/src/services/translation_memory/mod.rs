@@ -1,5 +1,9 @@
+pub mod audit;
+pub mod coverage;
 pub mod hash;
 pub mod matcher;
 pub mod model;
 pub mod normalize;
+pub mod search;
+pub mod seed;
 pub mod store;
@@ -11,4 +11,35 @@ pub struct TMEntry {
     pub normalized: String,
 
     pub hash: String,
+
+    #[serde(default)]
+    pub source: TmSource,
+
+    /// Unix seconds when the entry was recorded; 0 for entries that predate it.
+    #[serde(default)]
+    pub created_at: u64,
+}
+
+/// Where a TM entry's translation came from.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TmSource {
+    Ai,
+    Human,
+    Import,
+    #[default]
+    Unknown,
+}
+
+/// Which entry survives when two share the same language pair and hash.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupStrategy {
+    /// Prefer non-empty, then the longer translation.
+    #[default]
+    Longest,
+    /// Prefer non-empty, then the most recently recorded.
+    Newest,
+    /// Prefer human translations, then fall back to `Longest`.
+    ManualWins,
 }
@@ -1,6 +1,11 @@
+/// Matching key for TM lookups. Only used for hashing and comparison;
+/// rebuilt text keeps whatever spacing the original and translation had.
 pub fn normalize(text: &str) -> String {
     let mut s = text.trim().to_lowercase();
 
+    // Any run of Unicode whitespace becomes one ASCII space, so `A　B`
+    // (U+3000, ideographic space), `A\u{a0}B` and `A  B` share a key with
+    // `A B`. Changing this changes every stored hash.
     s = s.split_whitespace().collect::<Vec<_>>().join(" ");
 
     for ch in ['“', '”', '’', '‘', '…', '"', '\'', '(', ')'] {
@@ -9,3 +14,26 @@ pub fn normalize(text: &str) -> String {
 
     s
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::translation_memory::hash::hash_norm;
+
+    #[test]
+    fn equivalent_strings_share_a_hash() {
+        let a = normalize("  “Good   Morning…”  ");
+        let b = normalize("good morning");
+        assert_eq!(a, "good morning");
+        assert_eq!(hash_norm(&a), hash_norm(&b));
+        assert_ne!(hash_norm(&a), hash_norm(&normalize("good evening")));
+    }
+
+    #[test]
+    fn every_kind_of_whitespace_run_folds_to_one_space() {
+        for variant in ["A　B", "a\u{a0}b", " A  B ", "a\tb", "\"A B\"", "(a b)…"] {
+            assert_eq!(normalize(variant), "a b", "{variant:?}");
+        }
+        assert_eq!(hash_norm(&normalize("はい　そうです")), hash_norm(&normalize("はい そうです")));
+    }
+}
@@ -0,0 +1,71 @@
+use super::model::TMEntry;
+
+pub const DEFAULT_LIMIT: usize = 50;
+
+pub struct SearchQuery<'a> {
+    pub text: &'a str,
+    pub in_original: bool,
+    pub in_translation: bool,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+pub struct SearchPage<'a> {
+    pub total: usize,
+    pub entries: Vec<&'a TMEntry>,
+}
+
+/// Case-insensitive substring search over the selected fields, paginated.
+pub fn search<'a>(entries: &'a [TMEntry], q: &SearchQuery) -> SearchPage<'a> {
+    let needle = q.text.trim().to_lowercase();
+
+    let matches: Vec<&TMEntry> = entries
+        .iter()
+        .filter(|e| {
+            if needle.is_empty() {
+                return true;
+            }
+            (q.in_original && e.original.to_lowercase().contains(&needle))
+                || (q.in_translation && e.translation.to_lowercase().contains(&needle))
+        })
+        .collect();
+
+    let total = matches.len();
+    let entries = matches.into_iter().skip(q.offset).take(q.limit).collect();
+
+    SearchPage { total, entries }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tm(original: &str, translation: &str) -> TMEntry {
+        TMEntry {
+            source_lang: "ja".into(),
+            target_lang: "pt".into(),
+            original: original.into(),
+            translation: translation.into(),
+            normalized: String::new(),
+            hash: String::new(),
+            source: Default::default(),
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn search_filters_fields_and_paginates() {
+        let entries = [tm("魔法", "Magia"), tm("剣", "Espada mágica"), tm("魔法の剣", "Espada")];
+        let q = |text, in_original, in_translation, offset, limit| SearchQuery { text, in_original, in_translation, offset, limit };
+
+        let page = search(&entries, &q(" MÁGICA", false, true, 0, DEFAULT_LIMIT));
+        assert_eq!(page.total, 1);
+        assert_eq!(page.entries[0].original, "剣");
+
+        let page = search(&entries, &q("魔法", true, false, 1, 1));
+        assert_eq!(page.total, 2);
+        assert_eq!(page.entries[0].original, "魔法の剣");
+
+        assert_eq!(search(&entries, &q("", false, false, 0, 2)).entries.len(), 2);
+    }
+}
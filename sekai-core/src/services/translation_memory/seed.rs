@@ -0,0 +1,105 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use super::model::{DedupStrategy, TMEntry, TmSource};
+use super::{hash, normalize, store};
+use crate::model::entry::CoreEntry;
+
+#[derive(Debug, Serialize)]
+pub struct SeedReport {
+    /// Originals not previously in the TM for this language pair.
+    pub added: usize,
+    /// Completed entries whose original was already known (kept per dedup).
+    pub existing: usize,
+    /// Structural entries and entries missing an original or translation.
+    pub skipped: usize,
+}
+
+/// Records every completed translatable entry in the persisted TM.
+pub fn seed_from_entries(
+    entries: &[CoreEntry],
+    source_lang: &str,
+    target_lang: &str,
+    strategy: DedupStrategy,
+) -> Result<SeedReport, String> {
+    store::update_with(strategy, |tm_entries| add_entries(tm_entries, entries, source_lang, target_lang))
+}
+
+fn add_entries(tm_entries: &mut Vec<TMEntry>, entries: &[CoreEntry], source_lang: &str, target_lang: &str) -> SeedReport {
+    let mut known: HashSet<String> = tm_entries
+        .iter()
+        .filter(|t| t.source_lang == source_lang && t.target_lang == target_lang)
+        .map(|t| t.hash.clone())
+        .collect();
+
+    let mut report = SeedReport {
+        added: 0,
+        existing: 0,
+        skipped: 0,
+    };
+    let now = store::unix_now();
+
+    for e in entries {
+        if !e.is_translatable || e.original.trim().is_empty() || e.translation.trim().is_empty() {
+            report.skipped += 1;
+            continue;
+        }
+
+        let norm = normalize::normalize(&e.original);
+        let h = hash::hash_norm(&norm);
+
+        if known.insert(h.clone()) {
+            report.added += 1;
+        } else {
+            report.existing += 1;
+        }
+
+        tm_entries.push(TMEntry {
+            source_lang: source_lang.to_string(),
+            target_lang: target_lang.to_string(),
+            original: e.original.clone(),
+            translation: e.translation.clone(),
+            normalized: norm,
+            hash: h,
+            source: TmSource::Import,
+            created_at: now,
+        });
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::entry::EntryStatus;
+    use serde_json::json;
+
+    fn entry(entry_id: &str, original: &str, translation: &str) -> CoreEntry {
+        serde_json::from_value(json!({
+            "entry_id": entry_id,
+            "original": original,
+            "translation": translation,
+            "is_translatable": true,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn only_completed_translatable_entries_are_seeded() {
+        store::with_temp_tm("tm-seed", |_| {
+            let mut entries = vec![entry("1-text", "はい", "Sim"), entry("2-text", "いいえ", ""), entry("3-text", "はい", "Sim!")];
+            entries[0].status = EntryStatus::Reviewed;
+            entries.push(serde_json::from_value(json!({"entry_id": "4-raw", "raw_line": "@wait"})).unwrap());
+
+            let report = seed_from_entries(&entries, "ja", "pt", DedupStrategy::Longest).unwrap();
+            assert_eq!((report.added, report.existing, report.skipped), (1, 1, 2));
+
+            let tm = store::peek();
+            assert_eq!(tm.len(), 1);
+            assert_eq!(tm[0].translation, "Sim!");
+            assert_eq!(tm[0].source, TmSource::Import);
+        });
+    }
+}
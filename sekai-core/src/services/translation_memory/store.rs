@@ -1,70 +1,161 @@
-use super::model::TMEntry;
+use super::model::{DedupStrategy, TMEntry, TmSource};
 use super::{hash, normalize};
+use crate::services::fsutil::{self, write_atomic};
+use crate::{log_debug, log_info, log_warn};
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     fs,
-    path::{Path, PathBuf},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 const TM_FILE: &str = "translation_memory.json";
 
-pub fn load() -> Vec<TMEntry> {
+/// Runs `f` in a fresh temp dir as the working directory, and so with an
+/// empty TM; tests touching the TM hold a shared lock, as the working
+/// directory is process-wide.
+#[cfg(test)]
+pub(crate) fn with_temp_tm<T>(name: &str, f: impl FnOnce(&Path) -> T) -> T {
+    use std::sync::Mutex;
+
+    static LOCK: Mutex<()> = Mutex::new(());
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = std::env::temp_dir().join(format!("sekai-core-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let previous = std::env::current_dir().unwrap();
+    std::env::set_current_dir(&dir).unwrap();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(&dir)));
+    std::env::set_current_dir(previous).unwrap();
+    result.unwrap_or_else(|p| std::panic::resume_unwind(p))
+}
+
+/// How long a writer waits for another process to finish its update.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+/// An update takes well under this; an older lock was left by a crash.
+const LOCK_STALE_AFTER: Duration = Duration::from_secs(120);
+
+/// Bumped whenever saved entries gain a guarantee that `load_with` would
+/// otherwise have to re-establish. Version 1 is the bare array written
+/// before the header existed.
+const SCHEMA_VERSION: u32 = 2;
+
+/// On-disk shape. A file at the current version was written by `save_with`,
+/// so every entry already has `normalized`/`hash` and is deduplicated and
+/// sorted.
+#[derive(Serialize)]
+struct TmFile<'a> {
+    schema_version: u32,
+    entries: &'a [TMEntry],
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredTm {
+    Versioned { schema_version: u32, entries: Vec<TMEntry> },
+    Legacy(Vec<TMEntry>),
+}
+
+pub fn load_with(strategy: DedupStrategy) -> Vec<TMEntry> {
+    let (entries, migrated) = read(strategy);
+
+    // Rewritten even when nothing changed, so the header is there next time.
+    if migrated {
+        if let Err(e) = update_with(strategy, |_| ()) {
+            log_warn!("tm", "failed to persist migration: {e}");
+        }
+    }
+
+    entries
+}
+
+/// Like `load_with`, but an old file is migrated in memory only and never
+/// rewritten; for read-only reports.
+pub fn peek() -> Vec<TMEntry> {
+    peek_with(DedupStrategy::default())
+}
+
+pub fn peek_with(strategy: DedupStrategy) -> Vec<TMEntry> {
+    read(strategy).0
+}
+
+/// Reads the TM, bringing pre-header files up to the current schema. The
+/// flag says whether that migration happened.
+fn read(strategy: DedupStrategy) -> (Vec<TMEntry>, bool) {
     if !Path::new(TM_FILE).exists() {
-        return Vec::new();
+        return (Vec::new(), false);
     }
 
     let data = match fs::read_to_string(TM_FILE) {
         Ok(s) => s,
         Err(e) => {
-            eprintln!("[TM] failed to read {TM_FILE}: {e}");
-            return Vec::new();
+            log_warn!("tm", "failed to read {TM_FILE}: {e}");
+            return (Vec::new(), false);
         }
     };
 
-    let mut entries: Vec<TMEntry> = match serde_json::from_str(&data) {
-        Ok(v) => v,
+    let (version, mut entries) = match serde_json::from_str(&data) {
+        Ok(StoredTm::Versioned { schema_version, entries }) => (schema_version, entries),
+        Ok(StoredTm::Legacy(entries)) => (1, entries),
         Err(e) => {
-            eprintln!("[TM] failed to parse {TM_FILE}: {e}");
-            return Vec::new();
+            log_warn!("tm", "failed to parse {TM_FILE}: {e}");
+            return (Vec::new(), false);
         }
     };
 
-    let mut migrated = false;
+    if version >= SCHEMA_VERSION {
+        log_debug!("tm", "loaded {} entries (schema {version}, no migration)", entries.len());
+        return (entries, false);
+    }
 
     for e in entries.iter_mut() {
-        migrated |= ensure_norm_hash(e);
+        ensure_norm_hash(e);
     }
 
-    let (deduped, removed) = dedup(entries);
-    if removed > 0 {
-        migrated = true;
-    }
+    let (deduped, removed) = dedup(entries, strategy);
 
     let mut final_entries = deduped;
     sort_entries(&mut final_entries);
 
-    if migrated {
-        if let Err(e) = save(&final_entries) {
-            eprintln!("[TM] failed to persist migration: {e}");
-        }
-    }
+    log_debug!("tm", "migrated {} entries from schema {version} ({} duplicates removed)", final_entries.len(), removed);
+
+    (final_entries, true)
+}
+
+/// Load-modify-save under the TM lock, so concurrent writers take turns
+/// and each sees the others' entries. The TM is re-read inside the lock;
+/// whatever `f` leaves in it is saved.
+pub fn update_with<T>(strategy: DedupStrategy, f: impl FnOnce(&mut Vec<TMEntry>) -> T) -> Result<T, String> {
+    let _lock = fsutil::lock(Path::new(TM_FILE), LOCK_TIMEOUT, LOCK_STALE_AFTER)?;
+
+    let (mut entries, _) = read(strategy);
+    let out = f(&mut entries);
+    write(&entries, strategy)?;
 
-    final_entries
+    Ok(out)
 }
 
-pub fn save(entries: &[TMEntry]) -> Result<(), String> {
+/// Only call with the TM lock held.
+fn write(entries: &[TMEntry], strategy: DedupStrategy) -> Result<(), String> {
     let mut v: Vec<TMEntry> = entries.to_vec();
 
     for e in v.iter_mut() {
         ensure_norm_hash(e);
     }
 
-    let (mut v, _removed) = dedup(v);
+    let (mut v, _removed) = dedup(v, strategy);
     sort_entries(&mut v);
 
-    let json = serde_json::to_string_pretty(&v).map_err(|e| e.to_string())?;
+    let file = TmFile {
+        schema_version: SCHEMA_VERSION,
+        entries: &v,
+    };
+    let json = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
 
     write_atomic(Path::new(TM_FILE), json.as_bytes())?;
+    log_info!("tm", "saved {} entries to {TM_FILE}", v.len());
 
     Ok(())
 }
@@ -86,7 +177,14 @@ fn ensure_norm_hash(e: &mut TMEntry) -> bool {
     changed
 }
 
-fn dedup(entries: Vec<TMEntry>) -> (Vec<TMEntry>, usize) {
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn dedup(entries: Vec<TMEntry>, strategy: DedupStrategy) -> (Vec<TMEntry>, usize) {
     let mut map: HashMap<(String, String, String), TMEntry> = HashMap::new();
     let mut removed = 0usize;
 
@@ -100,7 +198,7 @@ fn dedup(entries: Vec<TMEntry>) -> (Vec<TMEntry>, usize) {
                 map.insert(key, e);
             }
             Some(existing) => {
-                let keep_new = pick_better(existing, &e);
+                let keep_new = pick_better(existing, &e, strategy);
                 if keep_new {
                     *existing = e;
                 }
@@ -113,7 +211,7 @@ fn dedup(entries: Vec<TMEntry>) -> (Vec<TMEntry>, usize) {
     (out, removed)
 }
 
-fn pick_better(current: &TMEntry, candidate: &TMEntry) -> bool {
+fn pick_better(current: &TMEntry, candidate: &TMEntry, strategy: DedupStrategy) -> bool {
     let cur_empty = current.translation.trim().is_empty();
     let cand_empty = candidate.translation.trim().is_empty();
 
@@ -124,10 +222,21 @@ fn pick_better(current: &TMEntry, candidate: &TMEntry) -> bool {
         return false;
     }
 
-    candidate.translation.len() > current.translation.len()
+    match strategy {
+        DedupStrategy::Longest => candidate.translation.len() > current.translation.len(),
+        DedupStrategy::Newest => candidate.created_at >= current.created_at,
+        DedupStrategy::ManualWins => {
+            let cur_human = current.source == TmSource::Human;
+            let cand_human = candidate.source == TmSource::Human;
+            if cur_human != cand_human {
+                return cand_human;
+            }
+            candidate.translation.len() > current.translation.len()
+        }
+    }
 }
 
-fn sort_entries(entries: &mut Vec<TMEntry>) {
+fn sort_entries(entries: &mut [TMEntry]) {
     entries.sort_by(|a, b| {
         (
             a.source_lang.as_str(),
@@ -148,30 +257,72 @@ fn sort_entries(entries: &mut Vec<TMEntry>) {
     });
 }
 
-fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
-    let tmp = tmp_path(path);
-
-    if let Some(parent) = tmp.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tm(translation: &str, source: TmSource, created_at: u64) -> TMEntry {
+        TMEntry {
+            source_lang: "ja".into(),
+            target_lang: "pt".into(),
+            original: "はい".into(),
+            translation: translation.into(),
+            normalized: String::new(),
+            hash: String::new(),
+            source,
+            created_at,
+        }
     }
 
-    fs::write(&tmp, bytes).map_err(|e| e.to_string())?;
-
-    if path.exists() {
-        fs::remove_file(path).map_err(|e| e.to_string())?;
+    fn survivor(strategy: DedupStrategy) -> String {
+        let candidates = vec![
+            tm("", TmSource::Ai, 9),
+            tm("Sim, claro", TmSource::Ai, 1),
+            tm("Sim", TmSource::Human, 2),
+            tm("Pois sim", TmSource::Ai, 3),
+        ];
+        let (kept, removed) = dedup(candidates, strategy);
+        assert_eq!((kept.len(), removed), (1, 3));
+        kept[0].translation.clone()
     }
 
-    fs::rename(&tmp, path).map_err(|e| e.to_string())?;
+    #[test]
+    fn dedup_strategies_pick_their_survivor() {
+        assert_eq!(survivor(DedupStrategy::Longest), "Sim, claro");
+        assert_eq!(survivor(DedupStrategy::Newest), "Pois sim");
+        assert_eq!(survivor(DedupStrategy::ManualWins), "Sim");
+    }
 
-    Ok(())
-}
+    #[test]
+    fn legacy_file_is_migrated_by_load_but_not_by_peek() {
+        with_temp_tm("tm-legacy", |_| {
+            let legacy = serde_json::to_string(&vec![tm("Sim", TmSource::Ai, 1), tm("Sim!", TmSource::Ai, 2)]).unwrap();
+            fs::write(TM_FILE, &legacy).unwrap();
+
+            let peeked = peek();
+            assert_eq!(peeked.len(), 1);
+            assert!(!peeked[0].hash.is_empty());
+            assert_eq!(fs::read_to_string(TM_FILE).unwrap(), legacy);
+
+            assert_eq!(load_with(DedupStrategy::Newest)[0].translation, "Sim!");
+            let saved: serde_json::Value = serde_json::from_str(&fs::read_to_string(TM_FILE).unwrap()).unwrap();
+            assert_eq!(saved["schema_version"], SCHEMA_VERSION);
+            assert_eq!(saved["entries"].as_array().unwrap().len(), 1);
+        });
+    }
 
-fn tmp_path(path: &Path) -> PathBuf {
-    let mut p = path.to_path_buf();
-    let file_name = match path.file_name().and_then(|s| s.to_str()) {
-        Some(n) => n.to_string(),
-        None => "tm".to_string(),
-    };
-    p.set_file_name(format!("{file_name}.tmp"));
-    p
+    #[test]
+    fn updates_are_saved_deduplicated_and_unlocked() {
+        with_temp_tm("tm-update", |_| {
+            update_with(DedupStrategy::Longest, |entries| {
+                entries.push(tm("Sim", TmSource::Ai, 1));
+                entries.push(tm("Sim, sim", TmSource::Ai, 2));
+            })
+            .unwrap();
+            let stored = peek();
+            assert_eq!(stored.len(), 1);
+            assert_eq!(stored[0].translation, "Sim, sim");
+            assert!(!Path::new("translation_memory.json.lock").exists());
+        });
+    }
 }
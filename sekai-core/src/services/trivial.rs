@@ -0,0 +1,79 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::model::entry::{CoreEntry, EntryStatus};
+
+/// What counts as a line with nothing to translate (`……`, `!?`, a lone
+/// `[r]`). Such lines are copied as-is instead of being sent to the AI.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct TrivialRule {
+    pub enabled: bool,
+    /// Ignore `[tag]` and `<tag>` markup when looking for content.
+    pub strip_tags: bool,
+    /// Whether a line of only digits and punctuation (`100!`) still goes to
+    /// the AI, e.g. for number formatting.
+    pub digits_are_content: bool,
+}
+
+impl Default for TrivialRule {
+    fn default() -> Self {
+        TrivialRule { enabled: true, strip_tags: true, digits_are_content: true }
+    }
+}
+
+fn tag_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\[[^\]]*\]|<[^>]*>").unwrap())
+}
+
+impl TrivialRule {
+    /// True when `original` has no letters (any script), and no digits
+    /// unless those are not content; whitespace, punctuation and symbols
+    /// alone never are.
+    pub fn is_trivial(&self, original: &str) -> bool {
+        if !self.enabled || original.trim().is_empty() {
+            return false;
+        }
+
+        let text = if self.strip_tags { tag_re().replace_all(original, "") } else { original.into() };
+        !text
+            .chars()
+            .any(|c| c.is_alphabetic() || (self.digits_are_content && c.is_numeric()))
+    }
+}
+
+/// Uses the original as the translation of a trivial entry.
+pub fn copy_original(e: &mut CoreEntry) {
+    e.set_translation(e.original.clone());
+    e.status = EntryStatus::Translated;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_without_letters_are_trivial() {
+        let rule = TrivialRule::default();
+        for s in ["……", "!?", "[r]", "<b>……</b>"] {
+            assert!(rule.is_trivial(s), "{s}");
+        }
+        for s in ["あ……", "100!", "", "   "] {
+            assert!(!rule.is_trivial(s), "{s}");
+        }
+        assert!(TrivialRule { digits_are_content: false, ..Default::default() }.is_trivial("100!"));
+        assert!(!TrivialRule { enabled: false, ..Default::default() }.is_trivial("……"));
+    }
+
+    #[test]
+    fn copy_original_marks_the_entry_translated() {
+        let mut e: CoreEntry =
+            serde_json::from_value(serde_json::json!({ "entry_id": "1-text", "original": "……", "is_translatable": true })).unwrap();
+        copy_original(&mut e);
+        assert_eq!(e.translation, "……");
+        assert_eq!(e.status, EntryStatus::Translated);
+    }
+}